@@ -16,7 +16,7 @@ async fn init_test_db() -> db::Database {
 
 async fn generate_random_string() -> String {
     let mut rng = rand::thread_rng();
-    let chars: Vec<char> = (0..6).map(|_| rng.gen_range(b'a'..b'z') as char).collect();
+    let chars: Vec<char> = (0..6).map(|_| rng.gen_range(b'a'..=b'z') as char).collect();
     chars.into_iter().collect()
 }
 
@@ -36,6 +36,7 @@ async fn benchmark_store_and_retrieve(db: db::Database) {
                 shortened_url: format!("http://localhost/{}", short_url_id),
                 long_url,
                 ttl: 30,
+                ..Default::default()
             };
 
             // Store data in Redis
@@ -0,0 +1,183 @@
+use crate::routes::ROUTE_NAMES;
+use std::str::FromStr;
+
+/// Characters that may ever appear in an alias: the RFC 3986 unreserved set.
+fn is_url_safe(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~')
+}
+
+/// The characters allowed in custom aliases, written like `a-zA-Z0-9_-`.
+///
+/// Ranges use `x-y`; a `-` at either end is taken literally. Only URL-safe ASCII characters
+/// can be allowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Charset {
+    spec: String,
+    chars: Vec<char>,
+}
+
+impl Charset {
+    pub fn contains(&self, c: char) -> bool {
+        self.chars.contains(&c)
+    }
+}
+
+impl FromStr for Charset {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let spec = spec.trim();
+        let input: Vec<char> = spec.chars().collect();
+        let mut chars = Vec::new();
+        let mut i = 0;
+        while i < input.len() {
+            if i + 2 < input.len() && input[i + 1] == '-' {
+                let (start, end) = (input[i], input[i + 2]);
+                if start > end {
+                    return Err(format!("Invalid range {}-{}", start, end));
+                }
+                chars.extend(start..=end);
+                i += 3;
+            } else {
+                chars.push(input[i]);
+                i += 1;
+            }
+        }
+
+        if chars.is_empty() {
+            return Err("Empty charset".to_string());
+        }
+        if let Some(c) = chars.iter().find(|c| !is_url_safe(**c)) {
+            return Err(format!("`{}` is not URL safe", c));
+        }
+        chars.sort_unstable();
+        chars.dedup();
+        Ok(Charset {
+            spec: spec.to_string(),
+            chars,
+        })
+    }
+}
+
+impl std::fmt::Display for Charset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.spec)
+    }
+}
+
+/// How letter case is treated in custom aliases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AliasCase {
+    /// `Promo` and `promo` are different aliases.
+    #[default]
+    Sensitive,
+    /// Aliases are stored lowercase and resolve whatever case is typed.
+    Insensitive,
+}
+
+impl FromStr for AliasCase {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "sensitive" => Ok(AliasCase::Sensitive),
+            "insensitive" => Ok(AliasCase::Insensitive),
+            other => Err(format!("Unknown alias case policy `{}`", other)),
+        }
+    }
+}
+
+/// Rules applied to user chosen aliases.
+#[derive(Debug, Clone)]
+pub struct AliasRules {
+    pub charset: Charset,
+    pub min_length: usize,
+    pub max_length: usize,
+    pub case: AliasCase,
+    /// Words that may not be used, in addition to the route names.
+    pub reserved: Vec<String>,
+}
+
+impl Default for AliasRules {
+    fn default() -> Self {
+        AliasRules {
+            charset: "a-zA-Z0-9_-".parse().unwrap(),
+            min_length: 3,
+            max_length: 64,
+            case: AliasCase::default(),
+            // Besides admin paths, the frontend's pages share the root namespace.
+            reserved: [
+                "admin", "static", "assets", "generate", "custom", "retrieve", "_next",
+            ]
+            .iter()
+            .map(|word| word.to_string())
+            .collect(),
+        }
+    }
+}
+
+/// Why a custom alias was refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AliasError {
+    TooShort(usize),
+    TooLong(usize),
+    InvalidChars(char),
+    Reserved(String),
+}
+
+impl AliasError {
+    /// Machine readable error code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AliasError::TooShort(_) => "ALIAS_TOO_SHORT",
+            AliasError::TooLong(_) => "ALIAS_TOO_LONG",
+            AliasError::InvalidChars(_) => "ALIAS_INVALID_CHARS",
+            AliasError::Reserved(_) => "ALIAS_RESERVED",
+        }
+    }
+
+    pub fn message(&self, rules: &AliasRules) -> String {
+        match self {
+            AliasError::TooShort(min) => {
+                format!("custom_short must be at least {} characters", min)
+            }
+            AliasError::TooLong(max) => format!("custom_short must be at most {} characters", max),
+            AliasError::InvalidChars(c) => format!(
+                "custom_short contains `{}`; allowed characters are {}",
+                c.escape_default(),
+                rules.charset
+            ),
+            AliasError::Reserved(word) => format!("`{}` is reserved", word),
+        }
+    }
+}
+
+impl AliasRules {
+    /// Validate `alias` and return the key it is stored under.
+    pub fn check(&self, alias: &str) -> Result<String, AliasError> {
+        let length = alias.chars().count();
+        if length < self.min_length {
+            return Err(AliasError::TooShort(self.min_length));
+        }
+        if length > self.max_length {
+            return Err(AliasError::TooLong(self.max_length));
+        }
+        // Reserved words are matched ignoring case whatever the policy.
+        let reserved = ROUTE_NAMES
+            .iter()
+            .copied()
+            .chain(self.reserved.iter().map(String::as_str))
+            .find(|word| word.eq_ignore_ascii_case(alias));
+        if let Some(word) = reserved {
+            return Err(AliasError::Reserved(word.to_string()));
+        }
+        if let Some(c) = alias.chars().find(|c| !self.charset.contains(*c)) {
+            return Err(AliasError::InvalidChars(c));
+        }
+
+        Ok(match self.case {
+            AliasCase::Sensitive => alias.to_string(),
+            AliasCase::Insensitive => alias.to_ascii_lowercase(),
+        })
+    }
+}
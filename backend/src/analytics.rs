@@ -0,0 +1,65 @@
+use crate::db::Database;
+use redis::RedisResult;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use utoipa::ToSchema;
+
+const TOTAL_FIELD: &str = "total";
+const VARIANT_PREFIX: &str = "variant:";
+
+/// Click counters of a link.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ClickStats {
+    /// Redirects served.
+    pub clicks: u64,
+    /// Redirects served per split variant.
+    #[schema(example = json!({"a": 120, "b": 118}))]
+    pub variants: BTreeMap<String, u64>,
+}
+
+fn clicks_key(link_key: &str) -> String {
+    format!("clicks:{}", link_key)
+}
+
+/// Count a redirect of the link stored at `link_key`, and the variant served if any.
+///
+/// The counters expire with the link, `ttl` seconds from now.
+pub async fn record_click(
+    database: Database,
+    link_key: &str,
+    variant: Option<&str>,
+    ttl: u64,
+) -> RedisResult<()> {
+    let key = clicks_key(link_key);
+    let mut pipe = redis::pipe();
+    pipe.hincr(&key, TOTAL_FIELD, 1).ignore();
+    if let Some(variant) = variant {
+        pipe.hincr(&key, format!("{}{}", VARIANT_PREFIX, variant), 1)
+            .ignore();
+    }
+    pipe.expire(&key, ttl.max(1) as i64).ignore();
+
+    let mut conn = database.lock().await;
+    pipe.query_async(&mut *conn).await
+}
+
+/// Read the counters of the link stored at `link_key`.
+pub async fn link_stats(database: Database, link_key: &str) -> RedisResult<ClickStats> {
+    let fields: HashMap<String, u64> = {
+        let mut conn = database.lock().await;
+        redis::cmd("HGETALL")
+            .arg(clicks_key(link_key))
+            .query_async(&mut *conn)
+            .await?
+    };
+
+    let mut stats = ClickStats::default();
+    for (field, count) in fields {
+        if field == TOTAL_FIELD {
+            stats.clicks = count;
+        } else if let Some(variant) = field.strip_prefix(VARIANT_PREFIX) {
+            stats.variants.insert(variant.to_string(), count);
+        }
+    }
+    Ok(stats)
+}
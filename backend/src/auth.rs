@@ -0,0 +1,519 @@
+use crate::config::{Config, JwtConfig};
+use crate::db::{store_nonce, Database};
+use crate::quotas::Quota;
+use crate::ratelimit::RateLimit;
+use crate::utm::UtmTemplate;
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use ring::hmac;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use warp::Filter;
+
+/// Scope required to create short links.
+pub const SCOPE_LINKS_WRITE: &str = "links:write";
+
+/// Scope required to read links and usage.
+pub const SCOPE_LINKS_READ: &str = "links:read";
+
+/// Scope granting every permission; held by API keys unless configured otherwise.
+pub const SCOPE_ALL: &str = "*";
+
+/// Owner assigned to the shared `API_KEY`.
+pub const DEFAULT_OWNER: &str = "default";
+
+/// Default tolerated clock skew for signed requests, in seconds.
+pub const DEFAULT_SIGNATURE_MAX_SKEW: i64 = 300;
+
+/// The authenticated caller of an API route.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    pub owner: String,
+    pub scopes: Vec<String>,
+}
+
+impl Principal {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope || s == SCOPE_ALL)
+    }
+}
+
+/// Credentials presented with a request.
+#[derive(Debug, Clone)]
+pub enum Credentials {
+    ApiKey(String),
+    Bearer(String),
+    Signed(SignedRequest),
+    Missing,
+}
+
+/// HMAC signature headers sent by public widgets in place of an API key.
+#[derive(Debug, Clone, Default)]
+pub struct SignedRequest {
+    /// `X-Key-Id`: owner of the API key whose HMAC secret signed the request.
+    pub key_id: String,
+    /// `X-Timestamp`: unix time in seconds at which the request was signed.
+    pub timestamp: String,
+    /// `X-Nonce`: random value, accepted once per key.
+    pub nonce: String,
+    /// `X-Signature`: hex encoded HMAC-SHA256, see [`sign_request`].
+    pub signature: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AuthError {
+    MissingCredentials,
+    InvalidApiKey,
+    InvalidToken(String),
+    InvalidSignature(String),
+    ReplayedNonce,
+    /// The nonce cache could not be reached.
+    Store(String),
+}
+
+/// Extract the credentials from the `API-Key`, `Authorization` or `X-Signature` headers.
+///
+/// An API key takes precedence when several are sent, then a bearer token.
+pub fn credentials() -> impl Filter<Extract = (Credentials,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("API-Key")
+        .and(warp::header::optional::<String>("Authorization"))
+        .and(signed_request())
+        .map(
+            |api_key: Option<String>,
+             authorization: Option<String>,
+             signed: Option<SignedRequest>| {
+                if let Some(key) = api_key {
+                    return Credentials::ApiKey(key);
+                }
+                if let Some(token) = authorization.as_deref().and_then(bearer_token) {
+                    return Credentials::Bearer(token.to_string());
+                }
+                match signed {
+                    Some(signed) => Credentials::Signed(signed),
+                    None => Credentials::Missing,
+                }
+            },
+        )
+}
+
+fn signed_request(
+) -> impl Filter<Extract = (Option<SignedRequest>,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("X-Signature")
+        .and(warp::header::optional::<String>("X-Key-Id"))
+        .and(warp::header::optional::<String>("X-Timestamp"))
+        .and(warp::header::optional::<String>("X-Nonce"))
+        .map(
+            |signature: Option<String>,
+             key_id: Option<String>,
+             timestamp: Option<String>,
+             nonce: Option<String>| {
+                signature.map(|signature| SignedRequest {
+                    key_id: key_id.unwrap_or_default(),
+                    timestamp: timestamp.unwrap_or_default(),
+                    nonce: nonce.unwrap_or_default(),
+                    signature,
+                })
+            },
+        )
+}
+
+/// Compute the hex encoded HMAC-SHA256 of `"{timestamp}.{nonce}."` followed by the raw body.
+pub fn sign_request(secret: &[u8], timestamp: &str, nonce: &str, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+    let mut context = hmac::Context::with_key(&key);
+    context.update(signing_prefix(timestamp, nonce).as_bytes());
+    context.update(body);
+    hex::encode(context.sign().as_ref())
+}
+
+fn signing_prefix(timestamp: &str, nonce: &str) -> String {
+    format!("{}.{}.", timestamp, nonce)
+}
+
+fn bearer_token(header: &str) -> Option<&str> {
+    let (scheme, token) = header.trim().split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim())
+        .filter(|token| !token.is_empty())
+}
+
+/// An entry of the `API_KEYS_FILE` JSON array.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ApiKeyEntry {
+    pub owner: String,
+    pub key: String,
+    #[serde(default = "all_scopes")]
+    pub scopes: Vec<String>,
+    /// Secret used to verify HMAC signed requests made on behalf of this owner.
+    #[serde(default)]
+    pub hmac_secret: Option<String>,
+    /// Write route limit for this owner, e.g. `"600/60"`; defaults to `RATE_LIMIT_WRITE_KEY`.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimit>,
+    /// Limits on links held by this owner; unset fields use the `QUOTA_*` defaults.
+    #[serde(default)]
+    pub quota: Option<Quota>,
+    /// Short domains this owner creates links on, the first being its default.
+    /// Empty allows every configured domain, defaulting to the public base URL's host.
+    #[serde(default)]
+    pub domains: Vec<String>,
+    /// UTM templates of this owner's campaigns, by campaign name.
+    #[serde(default)]
+    pub campaigns: HashMap<String, UtmTemplate>,
+}
+
+fn all_scopes() -> Vec<String> {
+    vec![SCOPE_ALL.to_string()]
+}
+
+/// Resolves request credentials to a [`Principal`].
+pub struct Authenticator {
+    api_keys: HashMap<String, Principal>,
+    owners: HashMap<String, ApiKeyEntry>,
+    hmac_keys: HashMap<String, (hmac::Key, Principal)>,
+    jwt: Option<JwtValidator>,
+    signature_max_skew: i64,
+}
+
+impl Authenticator {
+    pub fn new(api_keys: Vec<ApiKeyEntry>, jwt: Option<JwtValidator>) -> Self {
+        let mut hmac_keys = HashMap::new();
+        let owners = api_keys
+            .iter()
+            .map(|entry| (entry.owner.clone(), entry.clone()))
+            .collect();
+        let api_keys = api_keys
+            .into_iter()
+            .map(|entry| {
+                let principal = Principal {
+                    owner: entry.owner,
+                    scopes: entry.scopes,
+                };
+                if let Some(secret) = &entry.hmac_secret {
+                    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+                    hmac_keys.insert(principal.owner.clone(), (key, principal.clone()));
+                }
+                (entry.key, principal)
+            })
+            .collect();
+        Authenticator {
+            api_keys,
+            owners,
+            hmac_keys,
+            jwt,
+            signature_max_skew: DEFAULT_SIGNATURE_MAX_SKEW,
+        }
+    }
+
+    /// The `API_KEYS_FILE` entry configured for `owner`, if any.
+    pub fn owner_settings(&self, owner: &str) -> Option<&ApiKeyEntry> {
+        self.owners.get(owner)
+    }
+
+    /// Override how far `X-Timestamp` may drift from the server clock.
+    pub fn with_signature_max_skew(mut self, seconds: i64) -> Self {
+        self.signature_max_skew = seconds;
+        self
+    }
+
+    /// Build the authenticator from the shared key, `API_KEYS_FILE` and JWT settings.
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let mut api_keys = vec![ApiKeyEntry {
+            owner: DEFAULT_OWNER.to_string(),
+            key: config.api_key.clone(),
+            scopes: all_scopes(),
+            hmac_secret: None,
+            rate_limit: None,
+            quota: None,
+            domains: Vec::new(),
+            campaigns: HashMap::new(),
+        }];
+        if let Some(path) = &config.api_keys_file {
+            let raw = read_file(path)?;
+            let entries: Vec<ApiKeyEntry> = serde_json::from_slice(&raw)
+                .map_err(|e| format!("Invalid API keys file {}: {}", path.display(), e))?;
+            for entry in &entries {
+                for (name, template) in &entry.campaigns {
+                    template.check().map_err(|e| {
+                        format!(
+                            "Invalid campaign {} of {}: {}",
+                            name,
+                            entry.owner,
+                            e.message()
+                        )
+                    })?;
+                }
+            }
+            api_keys.extend(entries);
+        }
+
+        let jwt = config
+            .jwt
+            .as_ref()
+            .map(JwtValidator::from_config)
+            .transpose()?;
+        Ok(Authenticator::new(api_keys, jwt).with_signature_max_skew(config.signature_max_skew))
+    }
+
+    pub fn authenticate(&self, credentials: &Credentials) -> Result<Principal, AuthError> {
+        match credentials {
+            Credentials::ApiKey(key) => self
+                .api_keys
+                .get(key)
+                .cloned()
+                .ok_or(AuthError::InvalidApiKey),
+            Credentials::Bearer(token) => match &self.jwt {
+                Some(validator) => validator.validate(token),
+                None => Err(AuthError::InvalidToken(
+                    "Bearer tokens are not enabled".to_string(),
+                )),
+            },
+            Credentials::Signed(_) => Err(AuthError::InvalidSignature(
+                "Signed requests are only accepted on /generate_url".to_string(),
+            )),
+            Credentials::Missing => Err(AuthError::MissingCredentials),
+        }
+    }
+
+    /// Check the signature and timestamp of a signed request against the raw `body`.
+    ///
+    /// This does not consult the nonce cache; see [`Authenticator::authenticate_signed`].
+    pub fn verify_signature(
+        &self,
+        request: &SignedRequest,
+        body: &[u8],
+        now: i64,
+    ) -> Result<Principal, AuthError> {
+        let (key, principal) = self
+            .hmac_keys
+            .get(&request.key_id)
+            .ok_or_else(|| AuthError::InvalidSignature("Unknown X-Key-Id".to_string()))?;
+
+        if request.nonce.is_empty() || request.nonce.len() > 128 {
+            return Err(AuthError::InvalidSignature(
+                "X-Nonce must be 1 to 128 characters".to_string(),
+            ));
+        }
+        let timestamp: i64 = request
+            .timestamp
+            .parse()
+            .map_err(|_| AuthError::InvalidSignature("Invalid X-Timestamp".to_string()))?;
+        if (now - timestamp).abs() > self.signature_max_skew {
+            return Err(AuthError::InvalidSignature(
+                "X-Timestamp is outside the allowed window".to_string(),
+            ));
+        }
+
+        let signature = hex::decode(request.signature.trim())
+            .map_err(|_| AuthError::InvalidSignature("X-Signature must be hex".to_string()))?;
+        let mut message = signing_prefix(&request.timestamp, &request.nonce).into_bytes();
+        message.extend_from_slice(body);
+        hmac::verify(key, &message, &signature)
+            .map_err(|_| AuthError::InvalidSignature("Signature mismatch".to_string()))?;
+
+        Ok(principal.clone())
+    }
+
+    /// Verify a signed request and record its nonce so it cannot be replayed.
+    pub async fn authenticate_signed(
+        &self,
+        request: &SignedRequest,
+        body: &[u8],
+        database: Database,
+    ) -> Result<Principal, AuthError> {
+        let principal = self.verify_signature(request, body, chrono::Utc::now().timestamp())?;
+
+        // Nonces only need to outlive the window in which the timestamp is accepted.
+        let ttl = (2 * self.signature_max_skew).max(1) as u64;
+        let nonce_key = format!("{}:{}", request.key_id, request.nonce);
+        match store_nonce(database, &nonce_key, ttl).await {
+            Ok(true) => Ok(principal),
+            Ok(false) => Err(AuthError::ReplayedNonce),
+            Err(e) => Err(AuthError::Store(e.to_string())),
+        }
+    }
+}
+
+struct VerificationKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+/// Validates bearer tokens signed with one of the configured keys and maps their claims to a [`Principal`].
+pub struct JwtValidator {
+    keys: Vec<VerificationKey>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    owner_claim: String,
+    scopes_claim: String,
+}
+
+impl JwtValidator {
+    /// Load the verification keys referenced by the configuration.
+    pub fn from_config(config: &JwtConfig) -> Result<Self, String> {
+        let mut keys = Vec::new();
+
+        if let Some(path) = &config.hs256_secret_file {
+            let secret = read_file(path)?;
+            let secret = secret.trim_ascii();
+            if secret.is_empty() {
+                return Err(format!("HS256 secret file {} is empty", path.display()));
+            }
+            keys.push(VerificationKey {
+                kid: None,
+                algorithm: Algorithm::HS256,
+                key: DecodingKey::from_secret(secret),
+            });
+        }
+
+        if let Some(path) = &config.rs256_public_key_file {
+            let pem = read_file(path)?;
+            let key = DecodingKey::from_rsa_pem(&pem)
+                .map_err(|e| format!("Invalid RS256 public key {}: {}", path.display(), e))?;
+            keys.push(VerificationKey {
+                kid: None,
+                algorithm: Algorithm::RS256,
+                key,
+            });
+        }
+
+        if let Some(path) = &config.jwks_file {
+            let raw = read_file(path)?;
+            let jwks: JwkSet = serde_json::from_slice(&raw)
+                .map_err(|e| format!("Invalid JWKS file {}: {}", path.display(), e))?;
+            for jwk in &jwks.keys {
+                let unsupported =
+                    |e: String| format!("Unsupported key in {}: {}", path.display(), e);
+                let algorithm = jwk_algorithm(jwk).map_err(unsupported)?;
+                let key = DecodingKey::from_jwk(jwk).map_err(|e| unsupported(e.to_string()))?;
+                keys.push(VerificationKey {
+                    kid: jwk.common.key_id.clone(),
+                    algorithm,
+                    key,
+                });
+            }
+        }
+
+        if keys.is_empty() {
+            return Err("JWT validation is enabled but no verification key is configured".into());
+        }
+
+        Ok(JwtValidator {
+            keys,
+            issuer: config.issuer.clone(),
+            audience: config.audience.clone(),
+            owner_claim: config.owner_claim.clone(),
+            scopes_claim: config.scopes_claim.clone(),
+        })
+    }
+
+    pub fn validate(&self, token: &str) -> Result<Principal, AuthError> {
+        let header = decode_header(token).map_err(|e| AuthError::InvalidToken(e.to_string()))?;
+        if !self.keys.iter().any(|k| k.algorithm == header.alg) {
+            return Err(AuthError::InvalidToken(format!(
+                "Unsupported algorithm {:?}",
+                header.alg
+            )));
+        }
+
+        let validation = self.validation(header.alg);
+        let candidates = self.keys.iter().filter(|k| {
+            k.algorithm == header.alg
+                && match (&header.kid, &k.kid) {
+                    (Some(wanted), Some(kid)) => wanted == kid,
+                    _ => true,
+                }
+        });
+
+        let mut last_error = "No matching verification key".to_string();
+        for candidate in candidates {
+            match decode::<serde_json::Map<String, serde_json::Value>>(
+                token,
+                &candidate.key,
+                &validation,
+            ) {
+                Ok(data) => return self.principal_from_claims(&data.claims),
+                Err(e) => last_error = e.to_string(),
+            }
+        }
+        Err(AuthError::InvalidToken(last_error))
+    }
+
+    fn validation(&self, algorithm: Algorithm) -> Validation {
+        let mut validation = Validation::new(algorithm);
+        match &self.issuer {
+            Some(issuer) => validation.set_issuer(&[issuer]),
+            None => validation.iss = None,
+        }
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        validation
+    }
+
+    fn principal_from_claims(
+        &self,
+        claims: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<Principal, AuthError> {
+        let owner = claims
+            .get(&self.owner_claim)
+            .and_then(|v| v.as_str())
+            .filter(|owner| !owner.is_empty())
+            .ok_or_else(|| {
+                AuthError::InvalidToken(format!("Missing `{}` claim", self.owner_claim))
+            })?;
+
+        let scopes = match claims.get(&self.scopes_claim) {
+            Some(serde_json::Value::String(scopes)) => {
+                scopes.split_whitespace().map(str::to_string).collect()
+            }
+            Some(serde_json::Value::Array(scopes)) => scopes
+                .iter()
+                .filter_map(|s| s.as_str().map(str::to_string))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Ok(Principal {
+            owner: owner.to_string(),
+            scopes,
+        })
+    }
+}
+
+/// The algorithm a JWKS key verifies: its declared `alg`, or the usual one for its key type.
+fn jwk_algorithm(jwk: &Jwk) -> Result<Algorithm, String> {
+    if let Some(alg) = jwk.common.key_algorithm {
+        return alg
+            .to_string()
+            .parse()
+            .map_err(|_| format!("algorithm {} cannot verify signatures", alg));
+    }
+    match &jwk.algorithm {
+        AlgorithmParameters::RSA(_) => Ok(Algorithm::RS256),
+        AlgorithmParameters::OctetKey(_) => Ok(Algorithm::HS256),
+        AlgorithmParameters::EllipticCurve(ec) if ec.curve == EllipticCurve::P256 => {
+            Ok(Algorithm::ES256)
+        }
+        AlgorithmParameters::EllipticCurve(ec) if ec.curve == EllipticCurve::P384 => {
+            Ok(Algorithm::ES384)
+        }
+        AlgorithmParameters::OctetKeyPair(okp) if okp.curve == EllipticCurve::Ed25519 => {
+            Ok(Algorithm::EdDSA)
+        }
+        AlgorithmParameters::EllipticCurve(key) => {
+            Err(format!("curve {:?} is not supported", key.curve))
+        }
+        AlgorithmParameters::OctetKeyPair(key) => {
+            Err(format!("curve {:?} is not supported", key.curve))
+        }
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
@@ -0,0 +1,145 @@
+use crate::routes::ROUTE_NAMES;
+use std::str::FromStr;
+use url::Url;
+use warp::Filter;
+
+/// Where short links are served: scheme, host and an optional path prefix.
+///
+/// `https://sho.rt/go` produces links like `https://sho.rt/go/abc1234`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicBaseUrl {
+    scheme: String,
+    origin: String,
+    host: String,
+    prefix: Vec<String>,
+}
+
+impl PublicBaseUrl {
+    /// Lowercase host the links are served on.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Whether links are served over HTTPS.
+    pub fn is_https(&self) -> bool {
+        self.scheme == "https"
+    }
+
+    /// Path segments placed before the short code.
+    pub fn prefix(&self) -> &[String] {
+        &self.prefix
+    }
+
+    /// The same base served on another host, e.g. a tenant's own short domain.
+    pub fn with_host(&self, host: &str) -> PublicBaseUrl {
+        let host = host.to_ascii_lowercase();
+        PublicBaseUrl {
+            scheme: self.scheme.clone(),
+            origin: format!("{}://{}", self.scheme, host),
+            host,
+            prefix: self.prefix.clone(),
+        }
+    }
+
+    /// The public URL of the short link `code`.
+    pub fn short_url(&self, code: &str) -> String {
+        let mut url = self.origin.clone();
+        for segment in &self.prefix {
+            url.push('/');
+            url.push_str(segment);
+        }
+        url.push('/');
+        url.push_str(code);
+        url
+    }
+
+    /// The short code addressed by `path`, i.e. the single segment following the prefix.
+    pub fn short_code<'a>(&self, path: &'a str) -> Option<&'a str> {
+        match self.short_link(path)? {
+            (code, "") => Some(code),
+            _ => None,
+        }
+    }
+
+    /// The short code addressed by `path` and the rest of the path after it, without its
+    /// leading `/`.
+    pub fn short_link<'a>(&self, path: &'a str) -> Option<(&'a str, &'a str)> {
+        let mut rest = path.trim_start_matches('/');
+        for expected in &self.prefix {
+            let (segment, tail) = rest.split_once('/').unwrap_or((rest, ""));
+            if segment != expected {
+                return None;
+            }
+            rest = tail;
+        }
+        let (code, tail) = rest.split_once('/').unwrap_or((rest, ""));
+        (!code.is_empty()).then_some((code, tail))
+    }
+}
+
+impl Default for PublicBaseUrl {
+    fn default() -> Self {
+        "http://rustyshortener".parse().unwrap()
+    }
+}
+
+impl FromStr for PublicBaseUrl {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let url = Url::parse(value.trim()).map_err(|e| e.to_string())?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("Unsupported scheme `{}`", url.scheme()));
+        }
+        let Some(host) = url.host_str() else {
+            return Err("Missing host".to_string());
+        };
+        if url.query().is_some() || url.fragment().is_some() {
+            return Err("A base URL cannot have a query or fragment".to_string());
+        }
+
+        let prefix = url
+            .path_segments()
+            .map(|segments| {
+                segments
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let origin = url.origin().ascii_serialization();
+        Ok(PublicBaseUrl {
+            scheme: url.scheme().to_string(),
+            origin,
+            host: host.to_string(),
+            prefix,
+        })
+    }
+}
+
+impl std::fmt::Display for PublicBaseUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.origin)?;
+        for segment in &self.prefix {
+            write!(f, "/{}", segment)?;
+        }
+        Ok(())
+    }
+}
+
+/// Match `/{prefix}/{code}[/{rest}]` and extract the code and the rest of the path.
+///
+/// Codes named like one of our routes are left to those routes' rejections.
+pub fn short_link(
+    base_url: PublicBaseUrl,
+) -> impl Filter<Extract = (String, String), Error = warp::Rejection> + Clone {
+    warp::path::tail()
+        .and_then(move |tail: warp::path::Tail| {
+            let link = base_url
+                .short_link(tail.as_str())
+                .filter(|(code, _)| !ROUTE_NAMES.iter().any(|r| r.eq_ignore_ascii_case(code)))
+                .map(|(code, rest)| (code.to_string(), rest.to_string()));
+            async move { link.ok_or_else(warp::reject::not_found) }
+        })
+        .untuple_one()
+}
@@ -0,0 +1,67 @@
+use crate::db::Database;
+use redis::RedisResult;
+
+/// Largest `max_clicks` a link may be created with.
+pub const MAX_CLICKS: u32 = 1_000_000;
+
+// Takes one use if any is left, returning the uses remaining after it, or -1 when exhausted.
+// A missing counter counts as exhausted so a lost key never makes a link unlimited.
+const CONSUME_SCRIPT: &str = r#"
+local left = tonumber(redis.call('GET', KEYS[1]))
+if not left or left <= 0 then
+    return -1
+end
+return redis.call('DECR', KEYS[1])
+"#;
+
+fn uses_key(link_key: &str) -> String {
+    format!("uses:{}", link_key)
+}
+
+/// Check a requested `max_clicks`.
+pub fn check(max_clicks: u32) -> Result<(), String> {
+    if (1..=MAX_CLICKS).contains(&max_clicks) {
+        Ok(())
+    } else {
+        Err(format!("max_clicks ranges from 1 to {}", MAX_CLICKS))
+    }
+}
+
+/// Start the use counter of a new link, expiring with it after `ttl` seconds.
+pub async fn init(
+    database: Database,
+    link_key: &str,
+    max_clicks: u32,
+    ttl: u64,
+) -> RedisResult<()> {
+    let mut conn = database.lock().await;
+    redis::cmd("SET")
+        .arg(uses_key(link_key))
+        .arg(max_clicks)
+        .arg("EX")
+        .arg(ttl.max(1))
+        .query_async(&mut *conn)
+        .await
+}
+
+/// Atomically take one use of the link; `None` when it has none left.
+pub async fn consume(database: Database, link_key: &str) -> RedisResult<Option<u64>> {
+    let left: i64 = {
+        let mut conn = database.lock().await;
+        redis::Script::new(CONSUME_SCRIPT)
+            .key(uses_key(link_key))
+            .invoke_async(&mut *conn)
+            .await?
+    };
+    Ok((left >= 0).then_some(left as u64))
+}
+
+/// Uses the link has left.
+pub async fn remaining(database: Database, link_key: &str) -> RedisResult<u64> {
+    let mut conn = database.lock().await;
+    let left: Option<i64> = redis::cmd("GET")
+        .arg(uses_key(link_key))
+        .query_async(&mut *conn)
+        .await?;
+    Ok(left.unwrap_or(0).max(0) as u64)
+}
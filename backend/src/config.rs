@@ -0,0 +1,316 @@
+use crate::alias::AliasRules;
+use crate::base_url::PublicBaseUrl;
+use crate::fallback::DomainFallback;
+use crate::interstitial::DomainInterstitial;
+use crate::quotas::Quota;
+use crate::ratelimit::{IpNet, RateLimit};
+use crate::redirect::RedirectType;
+use crate::validation::UrlRules;
+use crate::versioning::DeprecationPolicy;
+use std::path::PathBuf;
+use std::str::FromStr;
+use warp::http::StatusCode;
+
+/// Runtime configuration, read from the environment (and `.env` via dotenv).
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Shared API key accepted on the write routes.
+    pub api_key: String,
+    /// Optional JSON file with additional per-owner API keys.
+    pub api_keys_file: Option<PathBuf>,
+    /// JWT bearer validation; `None` when no verification key is configured.
+    pub jwt: Option<JwtConfig>,
+    /// Accepted clock skew, in seconds, for HMAC signed requests.
+    pub signature_max_skew: i64,
+    /// Per client IP limit on the write routes.
+    pub write_ip_limit: Option<RateLimit>,
+    /// Default per owner limit on the write routes, overridable in `API_KEYS_FILE`.
+    pub write_key_limit: Option<RateLimit>,
+    /// Per client IP limit on redirects.
+    pub redirect_ip_limit: Option<RateLimit>,
+    /// Password attempts per protected link and client IP.
+    pub password_attempt_limit: Option<RateLimit>,
+    /// Proxies whose `X-Forwarded-For` header is trusted.
+    pub trusted_proxies: Vec<IpNet>,
+    /// Quota applied to owners without their own `quota` in `API_KEYS_FILE`.
+    pub default_quota: Quota,
+    /// Validation applied to destination URLs.
+    pub url_rules: UrlRules,
+    /// Domains links may not point to, one rule per line.
+    pub domain_blocklist_file: Option<PathBuf>,
+    /// When non-empty, the only domains links may point to.
+    pub domain_allowlist_file: Option<PathBuf>,
+    /// How often the domain lists are checked for changes, in seconds.
+    pub domain_policy_reload_secs: u64,
+    /// Base of the short links handed out, e.g. `https://sho.rt` or `https://example.com/go`.
+    pub public_base_url: PublicBaseUrl,
+    /// Other hosts short links are served on, each with its own namespace of codes.
+    pub short_domains: Vec<String>,
+    /// How many of our own short links a new destination may go through.
+    pub max_redirect_chain_depth: usize,
+    /// Rules applied to custom aliases.
+    pub alias_rules: AliasRules,
+    /// Redirect status of links created without a `redirect_type`.
+    pub default_redirect_type: RedirectType,
+    /// How long clients may cache permanent redirects, in seconds.
+    pub redirect_cache_max_age: u64,
+    /// Status of the error served outside a link's activation window without `inactive_url`.
+    pub inactive_link_status: StatusCode,
+    /// Where visitors of missing or expired codes go, per short domain.
+    pub domain_fallbacks: Vec<DomainFallback>,
+    /// How long a link's `expired_url` is kept after the link expires, in seconds.
+    pub expired_fallback_retention: u64,
+    /// HTML layout of the error pages shown to browsers.
+    pub error_page_template: Option<PathBuf>,
+    /// Warning pages shown before redirecting to matching destination domains.
+    pub interstitial_domains: Vec<DomainInterstitial>,
+    /// File holding the secret signing the interstitials' continue tokens.
+    pub interstitial_secret_file: Option<PathBuf>,
+    /// How long a continue token stays valid, in seconds.
+    pub interstitial_token_ttl: u64,
+    /// MaxMind DB file used to locate visitors for country rules.
+    pub geoip_database: Option<PathBuf>,
+    /// Deprecation and sunset dates announced on the unversioned routes.
+    pub legacy_deprecation: DeprecationPolicy,
+}
+
+/// Settings for validating `Authorization: Bearer` tokens.
+#[derive(Debug, Clone, Default)]
+pub struct JwtConfig {
+    /// File holding the raw HS256 shared secret.
+    pub hs256_secret_file: Option<PathBuf>,
+    /// PEM file holding an RS256 public key.
+    pub rs256_public_key_file: Option<PathBuf>,
+    /// JWKS document (RSA keys) stored on local disk.
+    pub jwks_file: Option<PathBuf>,
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    /// Claim mapped to the link owner.
+    pub owner_claim: String,
+    /// Claim holding scopes, either a space separated string or an array.
+    pub scopes_claim: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            api_key: String::new(),
+            api_keys_file: None,
+            jwt: None,
+            signature_max_skew: crate::auth::DEFAULT_SIGNATURE_MAX_SKEW,
+            write_ip_limit: Some(RateLimit::per_minute(60)),
+            write_key_limit: Some(RateLimit::per_minute(300)),
+            redirect_ip_limit: Some(RateLimit::per_minute(600)),
+            password_attempt_limit: Some(RateLimit {
+                capacity: 5,
+                refill_per_sec: 5.0 / 300.0,
+            }),
+            trusted_proxies: Vec::new(),
+            default_quota: Quota::default(),
+            url_rules: UrlRules::default(),
+            domain_blocklist_file: None,
+            domain_allowlist_file: None,
+            domain_policy_reload_secs: 30,
+            public_base_url: PublicBaseUrl::default(),
+            short_domains: Vec::new(),
+            max_redirect_chain_depth: 5,
+            alias_rules: AliasRules::default(),
+            default_redirect_type: RedirectType::default(),
+            redirect_cache_max_age: 86400,
+            inactive_link_status: StatusCode::NOT_FOUND,
+            domain_fallbacks: Vec::new(),
+            expired_fallback_retention: 30 * 86400,
+            error_page_template: None,
+            interstitial_domains: Vec::new(),
+            interstitial_secret_file: None,
+            interstitial_token_ttl: 300,
+            geoip_database: None,
+            legacy_deprecation: DeprecationPolicy::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Build the configuration from environment variables, falling back to the defaults.
+    pub fn from_env() -> Self {
+        let defaults = Config::default();
+        let api_key = std::env::var("API_KEY").expect("API_KEY must be set");
+
+        let jwt = JwtConfig {
+            hs256_secret_file: env_path("JWT_HS256_SECRET_FILE"),
+            rs256_public_key_file: env_path("JWT_RS256_PUBLIC_KEY_FILE"),
+            jwks_file: env_path("JWT_JWKS_FILE"),
+            issuer: env_string("JWT_ISSUER"),
+            audience: env_string("JWT_AUDIENCE"),
+            owner_claim: env_string("JWT_OWNER_CLAIM").unwrap_or_else(|| "sub".to_string()),
+            scopes_claim: env_string("JWT_SCOPES_CLAIM").unwrap_or_else(|| "scope".to_string()),
+        };
+        let jwt_enabled = jwt.hs256_secret_file.is_some()
+            || jwt.rs256_public_key_file.is_some()
+            || jwt.jwks_file.is_some();
+
+        Config {
+            api_key,
+            api_keys_file: env_path("API_KEYS_FILE"),
+            jwt: jwt_enabled.then_some(jwt),
+            signature_max_skew: env_parse("HMAC_MAX_SKEW_SECS")
+                .unwrap_or(defaults.signature_max_skew),
+            write_ip_limit: env_optional("RATE_LIMIT_WRITE_IP").unwrap_or(defaults.write_ip_limit),
+            write_key_limit: env_optional("RATE_LIMIT_WRITE_KEY")
+                .unwrap_or(defaults.write_key_limit),
+            redirect_ip_limit: env_optional("RATE_LIMIT_REDIRECT_IP")
+                .unwrap_or(defaults.redirect_ip_limit),
+            password_attempt_limit: env_optional("RATE_LIMIT_PASSWORD")
+                .unwrap_or(defaults.password_attempt_limit),
+            trusted_proxies: env_list("TRUSTED_PROXIES").unwrap_or(defaults.trusted_proxies),
+            default_quota: Quota {
+                max_active_links: env_parse("QUOTA_MAX_ACTIVE_LINKS"),
+                max_custom_aliases: env_parse("QUOTA_MAX_CUSTOM_ALIASES"),
+                monthly_creations: env_parse("QUOTA_MONTHLY_CREATIONS"),
+            }
+            .or(defaults.default_quota),
+            url_rules: UrlRules {
+                allowed_schemes: env_list::<String>("ALLOWED_URL_SCHEMES")
+                    .map(|schemes| schemes.iter().map(|s| s.to_ascii_lowercase()).collect())
+                    .unwrap_or(defaults.url_rules.allowed_schemes),
+                max_length: env_parse("MAX_URL_LENGTH").unwrap_or(defaults.url_rules.max_length),
+            },
+            domain_blocklist_file: env_path("DOMAIN_BLOCKLIST_FILE"),
+            domain_allowlist_file: env_path("DOMAIN_ALLOWLIST_FILE"),
+            domain_policy_reload_secs: env_parse("DOMAIN_POLICY_RELOAD_SECS")
+                .unwrap_or(defaults.domain_policy_reload_secs),
+            public_base_url: env_parse("PUBLIC_BASE_URL").unwrap_or(defaults.public_base_url),
+            short_domains: env_list::<String>("SHORT_DOMAINS")
+                .map(|domains| domains.iter().map(|d| d.to_ascii_lowercase()).collect())
+                .unwrap_or(defaults.short_domains),
+            max_redirect_chain_depth: env_parse("MAX_REDIRECT_CHAIN_DEPTH")
+                .unwrap_or(defaults.max_redirect_chain_depth),
+            alias_rules: AliasRules {
+                charset: env_parse("ALIAS_CHARSET").unwrap_or(defaults.alias_rules.charset),
+                min_length: env_parse("ALIAS_MIN_LENGTH")
+                    .unwrap_or(defaults.alias_rules.min_length),
+                max_length: env_parse("ALIAS_MAX_LENGTH")
+                    .unwrap_or(defaults.alias_rules.max_length),
+                case: env_parse("ALIAS_CASE").unwrap_or(defaults.alias_rules.case),
+                // Configured words add to the defaults, like the route names always reserved
+                reserved: defaults
+                    .alias_rules
+                    .reserved
+                    .into_iter()
+                    .chain(env_list("ALIAS_RESERVED_WORDS").unwrap_or_default())
+                    .collect(),
+            },
+            default_redirect_type: env_parse("DEFAULT_REDIRECT_TYPE")
+                .unwrap_or(defaults.default_redirect_type),
+            redirect_cache_max_age: env_parse("REDIRECT_CACHE_MAX_AGE")
+                .unwrap_or(defaults.redirect_cache_max_age),
+            inactive_link_status: env_parse("INACTIVE_LINK_STATUS")
+                .inspect(|status: &StatusCode| {
+                    assert!(
+                        status.is_client_error() || status.is_server_error(),
+                        "INACTIVE_LINK_STATUS must be a 4xx or 5xx status: {}",
+                        status
+                    )
+                })
+                .unwrap_or(defaults.inactive_link_status),
+            domain_fallbacks: env_list("DOMAIN_FALLBACK_URLS").unwrap_or(defaults.domain_fallbacks),
+            expired_fallback_retention: env_parse("EXPIRED_FALLBACK_RETENTION_SECS")
+                .unwrap_or(defaults.expired_fallback_retention),
+            error_page_template: env_path("ERROR_PAGE_TEMPLATE"),
+            interstitial_domains: env_list("INTERSTITIAL_DOMAINS")
+                .unwrap_or(defaults.interstitial_domains),
+            interstitial_secret_file: env_path("INTERSTITIAL_SECRET_FILE"),
+            interstitial_token_ttl: env_parse("INTERSTITIAL_TOKEN_TTL_SECS")
+                .unwrap_or(defaults.interstitial_token_ttl),
+            geoip_database: env_path("GEOIP_DATABASE"),
+            legacy_deprecation: DeprecationPolicy {
+                deprecated_on: env_parse("LEGACY_DEPRECATED_ON")
+                    .unwrap_or(defaults.legacy_deprecation.deprecated_on),
+                sunset_on: env_optional("LEGACY_SUNSET_ON")
+                    .unwrap_or(defaults.legacy_deprecation.sunset_on),
+            },
+        }
+    }
+
+    /// Every host short links may be served on: the public base URL's and `SHORT_DOMAINS`.
+    pub fn own_domains(&self) -> Vec<String> {
+        std::iter::once(self.public_base_url.host().to_string())
+            .chain(self.short_domains.iter().cloned())
+            .collect()
+    }
+
+    /// The short domain a request for `host` addresses.
+    ///
+    /// The `Host` header's port is ignored; hosts that are not one of our domains (e.g. a direct
+    /// call to a backend) fall back to the public base URL's host.
+    pub fn domain_for_host(&self, host: Option<&str>) -> String {
+        let host = host
+            .map(|host| host.rsplit_once(':').map_or(host, |(name, _)| name))
+            .unwrap_or_default()
+            .trim_end_matches('.')
+            .to_ascii_lowercase();
+        if self.short_domains.contains(&host) {
+            host
+        } else {
+            self.public_base_url.host().to_string()
+        }
+    }
+
+    /// Redis key of the link `code` on `domain`.
+    ///
+    /// Links on the public base URL's host keep their bare code so existing links still resolve.
+    pub fn link_key(&self, domain: &str, code: &str) -> String {
+        if domain == self.public_base_url.host() {
+            code.to_string()
+        } else {
+            format!("{}/{}", domain, code)
+        }
+    }
+
+    /// The public URL of the link `code` on `domain`.
+    pub fn short_url(&self, domain: &str, code: &str) -> String {
+        self.public_base_url.with_host(domain).short_url(code)
+    }
+}
+
+/// Read a non-empty environment variable.
+fn env_string(name: &str) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+}
+
+/// Parse an environment variable, panicking on malformed values.
+fn env_parse<T: FromStr>(name: &str) -> Option<T> {
+    env_string(name).map(|value| parse_value(name, &value))
+}
+
+/// Like [`env_parse`], but `off` or `none` explicitly disable the setting.
+fn env_optional<T: FromStr>(name: &str) -> Option<Option<T>> {
+    env_string(name).map(|value| match value.trim().to_ascii_lowercase().as_str() {
+        "off" | "none" => None,
+        _ => Some(parse_value(name, &value)),
+    })
+}
+
+/// Parse a comma separated list.
+fn env_list<T: FromStr>(name: &str) -> Option<Vec<T>> {
+    env_string(name).map(|value| {
+        value
+            .split(',')
+            .filter(|item| !item.trim().is_empty())
+            .map(|item| parse_value(name, item))
+            .collect()
+    })
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str) -> T {
+    value
+        .trim()
+        .parse()
+        .unwrap_or_else(|_| panic!("{} has an invalid value: {}", name, value))
+}
+
+fn env_path(name: &str) -> Option<PathBuf> {
+    env_string(name).map(PathBuf::from)
+}
@@ -1,3 +1,9 @@
+use crate::interstitial::Interstitial;
+use crate::passthrough::Passthrough;
+use crate::redirect::RedirectType;
+use crate::targeting::DestinationRule;
+use crate::utm::UtmTemplate;
+use crate::variants::{SplitMode, Variant};
 use redis::{AsyncCommands, RedisResult};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
@@ -8,15 +14,74 @@ use tokio::sync::Mutex;
 
 pub type Database = Arc<Mutex<redis::aio::MultiplexedConnection>>;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Data {
     pub creation_data: String,
     pub shortened_url: String,
     pub long_url: String,
     pub ttl: u32,
-    
+    /// Owner of the API key or token that created the link.
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// Short domain the link lives on; `None` for links created before domains existed.
+    #[serde(default)]
+    pub domain: Option<String>,
+    /// Redirect status chosen at creation; `None` follows `DEFAULT_REDIRECT_TYPE`.
+    #[serde(default)]
+    pub redirect_type: Option<RedirectType>,
+    /// Query string and trailing path forwarding.
+    #[serde(default)]
+    pub passthrough: Passthrough,
+    /// UTM parameters added at redirect time, over those of `campaign`.
+    #[serde(default)]
+    pub utm: Option<UtmTemplate>,
+    /// Campaign of the owner whose UTM template applies.
+    #[serde(default)]
+    pub campaign: Option<String>,
+    /// Device, platform and language rules tried in order before `long_url`.
+    #[serde(default)]
+    pub rules: Vec<DestinationRule>,
+    /// Weighted destinations splitting the visitors no rule matched.
+    #[serde(default)]
+    pub variants: Vec<Variant>,
+    #[serde(default)]
+    pub split: SplitMode,
+    /// RFC 3339 time the link starts redirecting; `None` when live from creation.
+    #[serde(default)]
+    pub not_before: Option<String>,
+    /// RFC 3339 time the link stops redirecting, independent of `ttl`.
+    #[serde(default)]
+    pub not_after: Option<String>,
+    /// Where visitors go outside the window instead of an error.
+    #[serde(default)]
+    pub inactive_url: Option<String>,
+    /// Where visitors go once the link has expired, also kept under `fallback:{key}`.
+    #[serde(default)]
+    pub expired_url: Option<String>,
+    /// Successful redirects allowed, counted down under `uses:{key}`; `None` is unlimited.
+    #[serde(default)]
+    pub max_clicks: Option<u32>,
+    /// PBKDF2 hash of the password visitors must enter; `None` for open links.
+    #[serde(default)]
+    pub password_hash: Option<String>,
+    /// Warning page shown before redirecting, over the destination domain's.
+    #[serde(default)]
+    pub interstitial: Option<Interstitial>,
 }
 
+impl Data {
+    /// Whether visits must go through this link's own checks: a password, click limit,
+    /// activation window or interstitial.
+    pub fn is_gated(&self) -> bool {
+        self.password_hash.is_some()
+            || self.max_clicks.is_some()
+            || self.not_before.is_some()
+            || self.not_after.is_some()
+            || self
+                .interstitial
+                .is_some_and(|kind| kind != Interstitial::Off)
+    }
+}
 
 /// Create a new Redis database connection
 pub async fn init_db() -> Database {
@@ -62,6 +127,22 @@ pub async fn store_data(database: Database, short_url_id: String, data: Data) ->
     Ok(())
 }
 
+/// Claim `short_url_id` for a link about to be stored, returning `false` if it is taken.
+///
+/// The key holds an empty placeholder, read as missing, until [`store_data`] replaces it.
+pub async fn claim_key(database: Database, short_url_id: &str, ttl: u32) -> RedisResult<bool> {
+    let mut conn = database.lock().await;
+    let claimed: Option<String> = redis::cmd("SET")
+        .arg(short_url_id)
+        .arg("")
+        .arg("NX")
+        .arg("EX")
+        .arg(ttl.max(1))
+        .query_async(&mut *conn)
+        .await?;
+    Ok(claimed.is_some())
+}
+
 /// Retrieve data from Redis asynchronously
 pub async fn retrieve_data(database: Database, short_url_id: &str) -> Option<Data> {
     let mut conn = database.lock().await;
@@ -72,7 +153,20 @@ pub async fn retrieve_data(database: Database, short_url_id: &str) -> Option<Dat
 // Delete expired or invalid data
 pub async fn delete_data(database: Database, short_url_id: &str) -> RedisResult<()> {
     let mut conn = database.lock().await;
-    let _:() = conn.del(short_url_id).await?;
+    let _: () = conn.del(short_url_id).await?;
     Ok(())
 }
 
+/// Record a request nonce for `ttl` seconds, returning `false` if it was already seen.
+pub async fn store_nonce(database: Database, nonce_key: &str, ttl: u64) -> RedisResult<bool> {
+    let mut conn = database.lock().await;
+    let stored: Option<String> = redis::cmd("SET")
+        .arg(format!("nonce:{}", nonce_key))
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(ttl)
+        .query_async(&mut *conn)
+        .await?;
+    Ok(stored.is_some())
+}
@@ -0,0 +1,249 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+/// A single rule of a domain list file.
+///
+/// * `example.com` matches exactly that host.
+/// * `.example.com` matches the domain and all of its subdomains.
+/// * Patterns containing `*` are globs, e.g. `*.evil.*` or `paypa1*.com`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DomainRule {
+    Exact(String),
+    Suffix(String),
+    Wildcard(String),
+}
+
+impl DomainRule {
+    pub fn parse(rule: &str) -> Result<Self, String> {
+        let rule = rule.trim().trim_end_matches('.').to_lowercase();
+        if rule.is_empty() {
+            return Err("Empty domain rule".to_string());
+        }
+        if rule.contains('*') {
+            return Ok(DomainRule::Wildcard(rule));
+        }
+        match rule.strip_prefix('.') {
+            Some(domain) => Ok(DomainRule::Suffix(to_ascii(domain)?)),
+            None => Ok(DomainRule::Exact(to_ascii(&rule)?)),
+        }
+    }
+
+    /// Whether `host` (lowercase, IDNA encoded) is covered by the rule.
+    pub fn matches(&self, host: &str) -> bool {
+        match self {
+            DomainRule::Exact(domain) => host == domain,
+            DomainRule::Suffix(domain) => {
+                host == domain
+                    || host
+                        .strip_suffix(domain.as_str())
+                        .is_some_and(|prefix| prefix.ends_with('.'))
+            }
+            DomainRule::Wildcard(pattern) => glob_match(pattern.as_bytes(), host.as_bytes()),
+        }
+    }
+}
+
+impl std::fmt::Display for DomainRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DomainRule::Exact(domain) => write!(f, "{}", domain),
+            DomainRule::Suffix(domain) => write!(f, ".{}", domain),
+            DomainRule::Wildcard(pattern) => write!(f, "{}", pattern),
+        }
+    }
+}
+
+/// IDNA encode a domain so rules compare equal to normalized URL hosts.
+fn to_ascii(domain: &str) -> Result<String, String> {
+    match url::Host::parse(domain) {
+        Ok(host) => Ok(host.to_string()),
+        Err(e) => Err(format!("Invalid domain `{}`: {}", domain, e)),
+    }
+}
+
+/// Match `text` against `pattern`, where `*` matches any run of characters.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+/// An ordered list of domain rules.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DomainList {
+    rules: Vec<DomainRule>,
+}
+
+impl DomainList {
+    /// Parse one rule per line; blank lines and `#` comments are ignored.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let rules = contents
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default().trim())
+            .filter(|line| !line.is_empty())
+            .map(DomainRule::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(DomainList { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The first rule covering `host`.
+    pub fn find(&self, host: &str) -> Option<&DomainRule> {
+        let host = host.trim_end_matches('.').to_lowercase();
+        self.rules.iter().find(|rule| rule.matches(&host))
+    }
+}
+
+/// Why a destination host is refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DomainDenied {
+    /// The host matches this blocklist rule.
+    Blocked(DomainRule),
+    /// An allowlist is configured and the host is not on it.
+    NotAllowed,
+}
+
+impl DomainDenied {
+    pub fn code(&self) -> &'static str {
+        match self {
+            DomainDenied::Blocked(_) => "DOMAIN_BLOCKED",
+            DomainDenied::NotAllowed => "DOMAIN_NOT_ALLOWED",
+        }
+    }
+
+    pub fn message(&self, host: &str) -> String {
+        match self {
+            DomainDenied::Blocked(rule) => {
+                format!("Links to {} are blocked (rule `{}`)", host, rule)
+            }
+            DomainDenied::NotAllowed => format!("{} is not on the list of allowed domains", host),
+        }
+    }
+}
+
+/// Blocklist and allowlist applied to destination hosts.
+///
+/// The blocklist always wins; when the allowlist is non-empty only hosts on it are accepted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DomainPolicy {
+    pub blocklist: DomainList,
+    pub allowlist: DomainList,
+}
+
+impl DomainPolicy {
+    pub fn check(&self, host: &str) -> Result<(), DomainDenied> {
+        if let Some(rule) = self.blocklist.find(host) {
+            return Err(DomainDenied::Blocked(rule.clone()));
+        }
+        if !self.allowlist.is_empty() && self.allowlist.find(host).is_none() {
+            return Err(DomainDenied::NotAllowed);
+        }
+        Ok(())
+    }
+
+    /// Check the host of a stored destination URL; URLs without a host are left alone.
+    pub fn check_url(&self, url: &str) -> Result<(), (String, DomainDenied)> {
+        let host = url::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string));
+        match host {
+            Some(host) => self.check(&host).map_err(|denied| (host, denied)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The live domain policy, reloaded from its files when they change on disk.
+pub struct DomainPolicyStore {
+    blocklist_file: Option<PathBuf>,
+    allowlist_file: Option<PathBuf>,
+    policy: RwLock<Arc<DomainPolicy>>,
+    modified: Mutex<Vec<Option<SystemTime>>>,
+}
+
+impl DomainPolicyStore {
+    /// Create an empty policy; call [`DomainPolicyStore::reload`] to read the files.
+    pub fn new(blocklist_file: Option<PathBuf>, allowlist_file: Option<PathBuf>) -> Self {
+        DomainPolicyStore {
+            blocklist_file,
+            allowlist_file,
+            policy: RwLock::new(Arc::new(DomainPolicy::default())),
+            modified: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn current(&self) -> Arc<DomainPolicy> {
+        Arc::clone(&self.policy.read().unwrap())
+    }
+
+    /// Replace the policy with one read from the configured files.
+    pub fn reload(&self) -> Result<(), String> {
+        let policy = DomainPolicy {
+            blocklist: read_list(self.blocklist_file.as_deref())?,
+            allowlist: read_list(self.allowlist_file.as_deref())?,
+        };
+        *self.modified.lock().unwrap() = self.modification_times();
+        *self.policy.write().unwrap() = Arc::new(policy);
+        Ok(())
+    }
+
+    /// Reload when either file's modification time changed; returns whether it reloaded.
+    pub fn reload_if_changed(&self) -> Result<bool, String> {
+        if *self.modified.lock().unwrap() == self.modification_times() {
+            return Ok(false);
+        }
+        self.reload().map(|_| true)
+    }
+
+    fn modification_times(&self) -> Vec<Option<SystemTime>> {
+        [&self.blocklist_file, &self.allowlist_file]
+            .iter()
+            .map(|path| {
+                path.as_ref()
+                    .and_then(|path| std::fs::metadata(path).ok())
+                    .and_then(|meta| meta.modified().ok())
+            })
+            .collect()
+    }
+}
+
+fn read_list(path: Option<&Path>) -> Result<DomainList, String> {
+    let Some(path) = path else {
+        return Ok(DomainList::default());
+    };
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    DomainList::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Poll the policy files every `interval`, keeping the previous policy if a reload fails.
+pub async fn watch(store: Arc<DomainPolicyStore>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        match store.reload_if_changed() {
+            Ok(true) => println!("🔄 Reloaded domain policy"),
+            Ok(false) => {}
+            Err(e) => eprintln!("❌ Keeping previous domain policy: {}", e),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use warp::http::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use warp::http::StatusCode;
+use warp::Reply;
+
+/// Media type of [`ApiError`] bodies.
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// A problem with a single request field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, code: &str, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.to_string(),
+            code: code.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+/// The error envelope returned by every route, an RFC 7807 `application/problem+json` document.
+///
+/// `code` is a stable machine readable identifier; `errors` lists field level problems.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub code: String,
+    pub detail: String,
+    pub errors: Vec<FieldError>,
+    headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+/// Serialized form of an [`ApiError`].
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    #[schema(example = "about:blank")]
+    pub kind: String,
+    #[schema(example = "Bad Request")]
+    pub title: String,
+    #[schema(example = 400)]
+    pub status: u16,
+    pub detail: String,
+    #[schema(example = "VALIDATION_FAILED")]
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &str, detail: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            code: code.to_string(),
+            detail: detail.into(),
+            errors: Vec::new(),
+            headers: Vec::new(),
+        }
+    }
+
+    /// A `400` caused by one field; the field error carries the same code.
+    pub fn field(field: &str, code: &str, message: impl Into<String>) -> Self {
+        let message = message.into();
+        ApiError::new(StatusCode::BAD_REQUEST, code, message.clone())
+            .with_errors(vec![FieldError::new(field, code, message)])
+    }
+
+    /// A `400 VALIDATION_FAILED` listing every invalid field.
+    pub fn validation(errors: Vec<FieldError>) -> Self {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "VALIDATION_FAILED",
+            "The request body has invalid fields",
+        )
+        .with_errors(errors)
+    }
+
+    pub fn with_errors(mut self, errors: Vec<FieldError>) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// Add a response header; invalid names or values are dropped.
+    pub fn with_header(mut self, name: &str, value: impl ToString) -> Self {
+        let name = HeaderName::from_bytes(name.as_bytes());
+        let value = HeaderValue::from_str(&value.to_string());
+        if let (Ok(name), Ok(value)) = (name, value) {
+            self.headers.push((name, value));
+        }
+        self
+    }
+
+    /// The JSON body of the problem document.
+    pub fn body(&self) -> serde_json::Value {
+        let problem = ProblemDetails {
+            kind: "about:blank".to_string(),
+            title: self
+                .status
+                .canonical_reason()
+                .unwrap_or("Error")
+                .to_string(),
+            status: self.status.as_u16(),
+            detail: self.detail.clone(),
+            code: self.code.clone(),
+            errors: self.errors.clone(),
+        };
+        serde_json::to_value(problem).unwrap_or_default()
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}: {}", self.status.as_u16(), self.code, self.detail)
+    }
+}
+
+impl warp::reject::Reject for ApiError {}
+
+impl Reply for ApiError {
+    fn into_response(self) -> warp::reply::Response {
+        let mut response =
+            warp::reply::with_status(warp::reply::json(&self.body()), self.status).into_response();
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+        for (name, value) in self.headers {
+            headers.insert(name, value);
+        }
+        response
+    }
+}
+
+/// Turn a handler result into a response, rendering errors as problem documents.
+pub fn respond<R: Reply>(result: Result<R, ApiError>) -> warp::reply::Response {
+    match result {
+        Ok(reply) => reply.into_response(),
+        Err(error) => error.into_response(),
+    }
+}
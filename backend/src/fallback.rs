@@ -0,0 +1,73 @@
+use crate::db::Database;
+use redis::RedisResult;
+use std::str::FromStr;
+use url::Url;
+
+/// Where visitors of missing or expired codes on `domain` are sent, written `domain=url`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomainFallback {
+    pub domain: String,
+    pub url: String,
+}
+
+impl FromStr for DomainFallback {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (domain, url) = s
+            .trim()
+            .split_once('=')
+            .ok_or_else(|| format!("Expected <domain>=<url>, got `{}`", s))?;
+        let domain = domain.trim().to_ascii_lowercase();
+        let parsed =
+            Url::parse(url.trim()).map_err(|e| format!("Invalid URL in `{}`: {}", s, e))?;
+        if domain.is_empty() || !matches!(parsed.scheme(), "http" | "https") {
+            return Err(format!("Expected <domain>=<http(s) url>, got `{}`", s));
+        }
+        Ok(DomainFallback {
+            domain,
+            url: parsed.to_string(),
+        })
+    }
+}
+
+/// The fallback URL configured for `domain`.
+pub fn for_domain<'a>(fallbacks: &'a [DomainFallback], domain: &str) -> Option<&'a str> {
+    fallbacks
+        .iter()
+        .find(|fallback| fallback.domain == domain)
+        .map(|fallback| fallback.url.as_str())
+}
+
+fn fallback_key(link_key: &str) -> String {
+    format!("fallback:{}", link_key)
+}
+
+/// Keep the `expired_url` of a link for `retention` seconds past its `ttl`.
+///
+/// The link record itself expires with its TTL, so the fallback lives under its own key.
+pub async fn store(
+    database: Database,
+    link_key: &str,
+    url: &str,
+    ttl: u64,
+    retention: u64,
+) -> RedisResult<()> {
+    let mut conn = database.lock().await;
+    redis::cmd("SET")
+        .arg(fallback_key(link_key))
+        .arg(url)
+        .arg("EX")
+        .arg(ttl.saturating_add(retention).max(1))
+        .query_async(&mut *conn)
+        .await
+}
+
+/// The `expired_url` kept for the link stored at `link_key`, if it still has one.
+pub async fn lookup(database: Database, link_key: &str) -> RedisResult<Option<String>> {
+    let mut conn = database.lock().await;
+    redis::cmd("GET")
+        .arg(fallback_key(link_key))
+        .query_async(&mut *conn)
+        .await
+}
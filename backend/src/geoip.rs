@@ -0,0 +1,36 @@
+use maxminddb::{geoip2, Reader};
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Country lookups in a local MaxMind DB file, such as GeoLite2-Country or GeoIP2-City.
+pub struct GeoIp {
+    reader: Reader<Vec<u8>>,
+}
+
+impl GeoIp {
+    /// Load the database at `path` into memory.
+    pub fn open(path: &Path) -> Result<GeoIp, String> {
+        let reader = Reader::open_readfile(path)
+            .map_err(|e| format!("cannot read GeoIP database {}: {}", path.display(), e))?;
+        Ok(GeoIp { reader })
+    }
+
+    /// ISO 3166-1 alpha-2 code of the country `ip` is located in, uppercase.
+    pub fn country(&self, ip: IpAddr) -> Option<String> {
+        // Clients reaching us over IPv6 may still carry an IPv4 address
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            IpAddr::V4(_) => ip,
+        };
+        let record: geoip2::Country = self.reader.lookup(ip).ok()?;
+        record
+            .country?
+            .iso_code
+            .map(|code| code.to_ascii_uppercase())
+    }
+}
+
+/// Whether `code` looks like an ISO 3166-1 alpha-2 country code.
+pub fn is_country_code(code: &str) -> bool {
+    code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic())
+}
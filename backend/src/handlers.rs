@@ -1,17 +1,58 @@
-use crate::db::{retrieve_data, store_data, Data, Database};
+use crate::alias::AliasCase;
+use crate::analytics;
+use crate::auth::{AuthError, Credentials, Principal, SCOPE_LINKS_READ, SCOPE_LINKS_WRITE};
+use crate::click_limits;
+use crate::db::{claim_key, delete_data, retrieve_data, store_data, Data, Database};
+use crate::error::{respond, ApiError};
+use crate::fallback;
+use crate::geoip;
+use crate::interstitial;
+use crate::models::{
+    parse_body_with, CreateLinkRequest, CreateLinkResponse, CustomLinkRequest, LinkDetails,
+    LinkOptions, LinkQuery, PasswordForm, UnknownFields,
+};
+use crate::pages::{
+    interstitial_reply, page_reply, password_form_reply, prefers_html, render_preview,
+};
+use crate::password;
+use crate::preview::{self, LinkPreview};
+use crate::quotas::{self, Quota, Reservation};
+use crate::ratelimit::{rate_limited_reply, RateLimited, RouteClass};
+use crate::redirect::RedirectType;
+use crate::routes;
+use crate::schedule::{Window, WindowState};
+use crate::self_links::{self, StoredLink};
+use crate::state::AppState;
+use crate::targeting::{self, Client, DestinationRule};
+use crate::utm::{UtmContext, UtmTemplate};
+use crate::validation::normalize_long_url;
+use crate::variants::{self, SplitMode, Variant};
 use base62;
+use prometheus::{Encoder, TextEncoder};
 use std::collections::HashMap;
+use std::convert::Infallible;
 use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::sync::Mutex;
-use warp::{http::StatusCode, reject, Filter};
+use warp::hyper::body::Bytes;
+use warp::{http::StatusCode, reject, Filter, Reply};
 
 // Define a custom error that implements warp::reject::Reject
 #[derive(Debug)]
 pub struct RedisError(pub String);
 impl reject::Reject for RedisError {}
 
+impl From<RedisError> for ApiError {
+    fn from(error: RedisError) -> Self {
+        eprintln!("❌ {}", error.0);
+        ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "STORAGE_UNAVAILABLE",
+            "The link store is temporarily unavailable",
+        )
+    }
+}
+
 const EPOCH: i64 = 1609459200000; // Custom epoch (e.g., 2021-01-01)
 const NODE_ID_BITS: i64 = 10;
 const SEQUENCE_BITS: i64 = 12;
@@ -19,6 +60,9 @@ const SEQUENCE_BITS: i64 = 12;
 const MAX_NODE_ID: i64 = (1 << NODE_ID_BITS) - 1;
 const MAX_SEQUENCE: i64 = (1 << SEQUENCE_BITS) - 1;
 
+/// Seconds a new link is kept before it expires, unless its window ends later.
+const LINK_TTL: u32 = 30;
+
 /// Extract Redis connection from the Arc<Mutex> and pass it into the handler functions.
 pub fn with_db(
     db: Database,
@@ -26,57 +70,975 @@ pub fn with_db(
     warp::any().map(move || db.clone())
 }
 
-const BASE_URL: &str = "http://rustyshortener";
+/// The problem document for a failed authentication.
+fn auth_error(error: AuthError) -> ApiError {
+    let (code, detail) = match error {
+        AuthError::MissingCredentials => (
+            "MISSING_CREDENTIALS",
+            "Missing API-Key, bearer token or signature".to_string(),
+        ),
+        AuthError::InvalidApiKey => ("INVALID_API_KEY", "Invalid API key".to_string()),
+        AuthError::InvalidToken(reason) => {
+            ("INVALID_TOKEN", format!("Invalid bearer token: {}", reason))
+        }
+        AuthError::InvalidSignature(reason) => (
+            "INVALID_SIGNATURE",
+            format!("Invalid signature: {}", reason),
+        ),
+        AuthError::ReplayedNonce => (
+            "REPLAYED_NONCE",
+            "X-Nonce has already been used".to_string(),
+        ),
+        AuthError::Store(reason) => {
+            return ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "UNAVAILABLE",
+                format!("Nonce cache unavailable: {}", reason),
+            )
+        }
+    };
+    ApiError::new(StatusCode::UNAUTHORIZED, code, detail)
+}
 
-/// Handle the generation of short URLs, storing the information in Redis.
-pub async fn handle_generate_url(
-    key: String,
-    body: serde_json::Value,
-    redis_connection: Arc<Mutex<redis::aio::MultiplexedConnection>>,
-    api_key: String,
-) -> Result<impl warp::Reply, warp::Rejection> {
-    // Verify API key authorization
-    if key != api_key {
-        return Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({ "error": "UNAUTHORIZED" })),
-            StatusCode::UNAUTHORIZED,
+/// Authenticate the caller and check it holds `scope`.
+///
+/// `body` is the raw request body on routes that accept HMAC signed requests.
+async fn authorize(
+    state: &AppState,
+    credentials: &Credentials,
+    body: Option<&[u8]>,
+    scope: &str,
+) -> Result<Principal, ApiError> {
+    let result = match (credentials, body) {
+        (Credentials::Signed(request), Some(body)) => {
+            state
+                .auth
+                .authenticate_signed(request, body, Arc::clone(&state.db))
+                .await
+        }
+        _ => state.auth.authenticate(credentials),
+    };
+
+    let principal = result.map_err(auth_error)?;
+    if !principal.has_scope(scope) {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "FORBIDDEN",
+            format!("Missing scope {}", scope),
         ));
     }
+    Ok(principal)
+}
 
-    // Validate long URL from request body
-    let long_url = body["long_url"].as_str().unwrap_or("");
-    if long_url.is_empty() {
-        return Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({ "error": "INVALID URL" })),
-            StatusCode::BAD_REQUEST,
+/// Validate and canonicalize a destination URL, then apply the self-link and domain checks.
+async fn check_long_url(state: &AppState, long_url: &str) -> Result<String, ApiError> {
+    let long_url = normalize_long_url(long_url, &state.config.url_rules)
+        .map_err(|e| ApiError::field("long_url", e.code(), e.message()))?;
+    let long_url = resolve_self_links(state, long_url).await?;
+    check_destination(state, &long_url)?;
+    Ok(long_url)
+}
+
+/// Like [`check_long_url`], for a destination held in another request field.
+async fn check_long_url_at(
+    state: &AppState,
+    long_url: &str,
+    field: &str,
+) -> Result<String, ApiError> {
+    check_long_url(state, long_url).await.map_err(|mut error| {
+        for error in error.errors.iter_mut().filter(|e| e.field == "long_url") {
+            error.field = field.to_string();
+        }
+        error
+    })
+}
+
+/// Check a canonical destination URL against the domain policy.
+fn check_destination(state: &AppState, long_url: &str) -> Result<(), ApiError> {
+    state
+        .domain_policy
+        .current()
+        .check_url(long_url)
+        .map_err(|(host, denied)| {
+            ApiError::new(StatusCode::FORBIDDEN, denied.code(), denied.message(&host))
+        })
+}
+
+/// Replace a destination on our own domains by the final URL its short links lead to, or by
+/// the first gated short link on the way.
+async fn resolve_self_links(state: &AppState, long_url: String) -> Result<String, ApiError> {
+    self_links::resolve_destination(
+        long_url,
+        &state.config.own_domains(),
+        state.config.public_base_url.prefix(),
+        state.config.max_redirect_chain_depth,
+        |domain, code| async move {
+            find_link(state, &domain, &code)
+                .await
+                .ok()
+                .map(|(_, data)| StoredLink {
+                    gated: data.is_gated(),
+                    passthrough: data.passthrough,
+                    long_url: data.long_url,
+                })
+        },
+    )
+    .await
+    .map_err(|e| ApiError::field("long_url", e.code(), e.message()))
+}
+
+/// The short domain a new link is created on.
+///
+/// Owners listing `domains` in `API_KEYS_FILE` may only use those and default to the first;
+/// everyone else may use any configured domain and defaults to the public base URL's host.
+fn link_domain(
+    state: &AppState,
+    principal: &Principal,
+    requested: Option<&str>,
+) -> Result<String, ApiError> {
+    let owner_domains = state
+        .auth
+        .owner_settings(&principal.owner)
+        .map(|settings| settings.domains.as_slice())
+        .unwrap_or_default();
+
+    let Some(requested) = requested else {
+        return Ok(owner_domains
+            .first()
+            .map(|domain| domain.to_ascii_lowercase())
+            .unwrap_or_else(|| state.config.public_base_url.host().to_string()));
+    };
+
+    let domain = requested.trim().to_ascii_lowercase();
+    if !state.config.own_domains().contains(&domain) {
+        return Err(ApiError::field(
+            "domain",
+            "UNKNOWN_DOMAIN",
+            format!("`{}` is not one of our short domains", requested),
+        ));
+    }
+    if !owner_domains.is_empty()
+        && !owner_domains
+            .iter()
+            .any(|d| d.eq_ignore_ascii_case(&domain))
+    {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "DOMAIN_NOT_ALLOWED",
+            format!("You may not create links on `{}`", domain),
         ));
     }
+    Ok(domain)
+}
+
+/// Validate a link's UTM template and check its campaign belongs to the owner.
+fn check_utm(
+    state: &AppState,
+    principal: &Principal,
+    utm: Option<&UtmTemplate>,
+    campaign: Option<&str>,
+) -> Result<(), ApiError> {
+    if let Some(utm) = utm {
+        utm.check()
+            .map_err(|e| ApiError::field(&format!("utm.{}", e.field()), e.code(), e.message()))?;
+    }
+    if let Some(campaign) = campaign {
+        let known = state
+            .auth
+            .owner_settings(&principal.owner)
+            .is_some_and(|settings| settings.campaigns.contains_key(campaign));
+        if !known {
+            return Err(ApiError::field(
+                "campaign",
+                "UNKNOWN_CAMPAIGN",
+                format!("No campaign named `{}`", campaign),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Validate destination rules like `long_url`, reporting errors against the rule.
+async fn check_rules(
+    state: &AppState,
+    rules: Vec<DestinationRule>,
+) -> Result<Vec<DestinationRule>, ApiError> {
+    if rules.len() > targeting::MAX_RULES {
+        return Err(ApiError::field(
+            "rules",
+            "TOO_MANY_RULES",
+            format!("A link may have at most {} rules", targeting::MAX_RULES),
+        ));
+    }
+
+    let mut checked = Vec::with_capacity(rules.len());
+    for (index, mut rule) in rules.into_iter().enumerate() {
+        let field = format!("rules[{}]", index);
+        if !rule.has_conditions() {
+            return Err(ApiError::field(
+                &field,
+                "RULE_WITHOUT_CONDITIONS",
+                format!(
+                    "{} must set platforms, devices, languages or countries",
+                    field
+                ),
+            ));
+        }
+        if !rule.countries.is_empty() && state.geoip.is_none() {
+            return Err(ApiError::field(
+                &format!("{}.countries", field),
+                "GEOIP_UNAVAILABLE",
+                "Country rules need a GeoIP database, which this server does not have",
+            ));
+        }
+        for (position, country) in rule.countries.iter_mut().enumerate() {
+            if !geoip::is_country_code(country) {
+                return Err(ApiError::field(
+                    &format!("{}.countries[{}]", field, position),
+                    "INVALID_COUNTRY",
+                    "Countries are ISO 3166-1 alpha-2 codes such as `DE`",
+                ));
+            }
+            country.make_ascii_uppercase();
+        }
+        rule.long_url =
+            check_long_url_at(state, &rule.long_url, &format!("{}.long_url", field)).await?;
+        checked.push(rule);
+    }
+    Ok(checked)
+}
+
+async fn check_variants(
+    state: &AppState,
+    variants: Vec<Variant>,
+) -> Result<Vec<Variant>, ApiError> {
+    variants::check(&variants).map_err(|e| ApiError::field(&e.field(), e.code(), e.message()))?;
+
+    let mut checked = Vec::with_capacity(variants.len());
+    for (index, mut variant) in variants.into_iter().enumerate() {
+        let field = format!("variants[{}].long_url", index);
+        variant.long_url = check_long_url_at(state, &variant.long_url, &field).await?;
+        checked.push(variant);
+    }
+    Ok(checked)
+}
+
+/// Parse the activation window of a new link created at `created` and check its fallback
+/// destination, returning the link's storage TTL alongside.
+async fn check_window(
+    state: &AppState,
+    created: chrono::DateTime<chrono::FixedOffset>,
+    not_before: Option<&str>,
+    not_after: Option<&str>,
+    inactive_url: Option<&str>,
+) -> Result<(Window, u32, Option<String>), ApiError> {
+    let (window, ttl) = Window::parse(not_before, not_after)
+        .and_then(|window| Ok((window, window.ttl(created, LINK_TTL)?)))
+        .map_err(|e| ApiError::field(e.field(), e.code(), e.message()))?;
+    let inactive_url = check_optional_url(state, inactive_url, "inactive_url").await?;
+    Ok((window, ttl, inactive_url))
+}
+
+/// Check an optional fallback destination like a `long_url`, reporting errors on `field`.
+async fn check_optional_url(
+    state: &AppState,
+    url: Option<&str>,
+    field: &'static str,
+) -> Result<Option<String>, ApiError> {
+    match url {
+        Some(url) => Ok(Some(check_long_url_at(state, url, field).await?)),
+        None => Ok(None),
+    }
+}
+
+/// Check a new link's password and hash it off the async runtime.
+async fn hash_password(password: Option<String>) -> Result<Option<String>, ApiError> {
+    let Some(password) = password else {
+        return Ok(None);
+    };
+    password::check(&password)
+        .map_err(|message| ApiError::field("password", "INVALID_PASSWORD", message))?;
+    let hash = tokio::task::spawn_blocking(move || password::hash(&password))
+        .await
+        .map_err(|e| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "INTERNAL_ERROR",
+                format!("Password hashing failed: {}", e),
+            )
+        })?;
+    Ok(Some(hash))
+}
+
+/// Gate a protected link: `Ok` once the right password is posted, else the form to show.
+///
+/// Every attempt takes a token from the link's bucket for the client IP, so guessing is slow.
+async fn unlock(
+    state: &AppState,
+    key: &str,
+    hash: &str,
+    params: &HashMap<String, String>,
+) -> Result<(), Box<dyn warp::Reply>> {
+    let Some(attempt) = params.get("password") else {
+        return Err(Box::new(password_form_reply(StatusCode::OK, None)));
+    };
+
+    if let Some(limit) = &state.config.password_attempt_limit {
+        let client = params.get("client_ip").map_or("unknown", String::as_str);
+        let decision = state
+            .limiter
+            .check(RouteClass::Password, &format!("{}:{}", key, client), limit)
+            .await;
+        if !decision.allowed {
+            let mut response = password_form_reply(
+                StatusCode::TOO_MANY_REQUESTS,
+                Some(&format!(
+                    "Too many attempts, try again in {} seconds",
+                    decision.retry_after_secs
+                )),
+            );
+            response.headers_mut().insert(
+                warp::http::header::RETRY_AFTER,
+                decision.retry_after_secs.into(),
+            );
+            return Err(Box::new(response));
+        }
+    }
+
+    let (attempt, hash) = (attempt.clone(), hash.to_string());
+    let valid = attempt.chars().count() <= password::MAX_LENGTH
+        && tokio::task::spawn_blocking(move || password::verify(&attempt, &hash))
+            .await
+            .unwrap_or(false);
+    if !valid {
+        return Err(Box::new(password_form_reply(
+            StatusCode::FORBIDDEN,
+            Some("Incorrect password"),
+        )));
+    }
+    Ok(())
+}
+
+/// The reply to a visit outside the link's activation window.
+fn inactive_reply(
+    state: &AppState,
+    params: &HashMap<String, String>,
+    data: &Data,
+    window: WindowState,
+) -> Box<dyn warp::Reply> {
+    if let Some(reply) = data.inactive_url.as_deref().and_then(fallback_reply) {
+        return reply;
+    }
+    let status = state.config.inactive_link_status;
+    match window {
+        WindowState::Pending(start) => link_error(
+            state,
+            params,
+            "This link is not active yet",
+            ApiError::new(
+                status,
+                "LINK_NOT_YET_ACTIVE",
+                format!("This link opens on {}", start.to_rfc3339()),
+            ),
+        ),
+        _ => link_error(
+            state,
+            params,
+            "This link has ended",
+            ApiError::new(status, "LINK_ENDED", "This link is no longer active"),
+        ),
+    }
+}
+
+/// A temporary redirect to a fallback destination, or `None` if `url` is not a valid URI.
+///
+/// Never cached: the link may open, or the fallback change meaning, at any time.
+fn fallback_reply(url: &str) -> Option<Box<dyn warp::Reply>> {
+    let uri = url.parse::<warp::http::Uri>().ok()?;
+    Some(Box::new(RedirectType::Temporary.reply(&uri, 0)))
+}
+
+/// `error` as the error page for browsers, or as JSON for API clients, per `Accept`.
+fn link_error(
+    state: &AppState,
+    params: &HashMap<String, String>,
+    title: &str,
+    error: ApiError,
+) -> Box<dyn warp::Reply> {
+    let error = error.with_header("Vary", "Accept");
+    if !params
+        .get("accept")
+        .is_some_and(|accept| prefers_html(accept))
+    {
+        return Box::new(error);
+    }
+    let page = state.error_page.render(error.status, title, &error.detail);
+    let mut response =
+        warp::reply::with_status(warp::reply::html(page), error.status).into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        warp::http::header::VARY,
+        warp::http::HeaderValue::from_static("Accept"),
+    );
+    headers.insert(
+        warp::http::header::CACHE_CONTROL,
+        warp::http::HeaderValue::from_static("no-store"),
+    );
+    Box::new(response)
+}
+
+/// Look up the link `code` on `domain`, returning its key, or every key tried when missing.
+async fn find_link(
+    state: &AppState,
+    domain: &str,
+    code: &str,
+) -> Result<(String, Data), Vec<String>> {
+    let key = state.config.link_key(domain, code);
+    if let Some(data) = retrieve_data(Arc::clone(&state.db), &key).await {
+        return Ok((key, data));
+    }
+    let mut tried = vec![key];
+    // Case-insensitive aliases are stored lowercase
+    if state.config.alias_rules.case == AliasCase::Insensitive
+        && code.chars().any(|c| c.is_ascii_uppercase())
+    {
+        let key = state.config.link_key(domain, &code.to_ascii_lowercase());
+        if let Some(data) = retrieve_data(Arc::clone(&state.db), &key).await {
+            return Ok((key, data));
+        }
+        tried.push(key);
+    }
+    Err(tried)
+}
+
+/// When a link stops working: its TTL counted from its creation.
+fn expiration_time(data: &Data) -> chrono::DateTime<chrono::FixedOffset> {
+    chrono::DateTime::parse_from_rfc3339(&data.creation_data).unwrap()
+        + chrono::Duration::seconds(data.ttl.into())
+}
+
+/// Where visitors of a link missing under `keys` go: the `expired_url` it was created with,
+/// if still kept, then the fallback of `domain`.
+async fn missing_link_fallback(state: &AppState, domain: &str, keys: &[String]) -> Option<String> {
+    for key in keys {
+        match fallback::lookup(Arc::clone(&state.db), key).await {
+            Ok(Some(url)) => return Some(url),
+            Ok(None) => {}
+            Err(e) => eprintln!("❌ Redis fallback error: {}", e),
+        }
+    }
+    fallback::for_domain(&state.config.domain_fallbacks, domain).map(str::to_string)
+}
+
+/// `destination` with the UTM template of `data`, and its campaign's, applied for a click.
+fn with_utm(state: &AppState, data: &Data, destination: &str, code: &str, domain: &str) -> String {
+    let campaign = data.campaign.as_deref().and_then(|campaign| {
+        state
+            .auth
+            .owner_settings(data.owner.as_deref()?)?
+            .campaigns
+            .get(campaign)
+    });
+    let template = match (&data.utm, campaign) {
+        (Some(utm), Some(campaign)) => utm.clone().or(campaign),
+        (Some(utm), None) => utm.clone(),
+        (None, Some(campaign)) => campaign.clone(),
+        (None, None) => return destination.to_string(),
+    };
+    let context = UtmContext {
+        code,
+        domain,
+        campaign: data.campaign.as_deref().unwrap_or_default(),
+        date: chrono::Utc::now().format("%Y-%m-%d").to_string(),
+    };
+    template.apply(destination, &context)
+}
+
+/// Take a token from the write bucket of the principal's owner.
+async fn limit_owner(state: &AppState, principal: &Principal) -> Result<(), ApiError> {
+    let limit = state
+        .auth
+        .owner_settings(&principal.owner)
+        .and_then(|settings| settings.rate_limit)
+        .or(state.config.write_key_limit);
+
+    if let Some(limit) = limit {
+        let subject = format!("key:{}", principal.owner);
+        let decision = state
+            .limiter
+            .check(RouteClass::Write, &subject, &limit)
+            .await;
+        if !decision.allowed {
+            return Err(rate_limited_reply(&decision));
+        }
+    }
+    Ok(())
+}
 
-    // Generate the short URL
-    let id = generate_short_url_id(long_url);
-    let full = format!("{}/dns_resolver/{}", BASE_URL, id);
+/// The quota of `owner`: its `API_KEYS_FILE` settings completed by the configured defaults.
+fn owner_quota(state: &AppState, owner: &str) -> Quota {
+    state
+        .auth
+        .owner_settings(owner)
+        .and_then(|settings| settings.quota)
+        .unwrap_or_default()
+        .or(state.config.default_quota)
+}
 
+/// Check the options shared by every new link and build its data, returning its key.
+///
+/// `code` gives the link's short code once its destination is known.
+async fn new_link(
+    state: &AppState,
+    principal: &Principal,
+    options: LinkOptions,
+    code: impl FnOnce(&str) -> Result<String, ApiError>,
+) -> Result<(String, Data), ApiError> {
+    let domain = link_domain(state, principal, options.domain.as_deref())?;
+    check_utm(
+        state,
+        principal,
+        options.utm.as_ref(),
+        options.campaign.as_deref(),
+    )?;
+    let long_url = check_long_url(state, &options.long_url).await?;
+    let rules = check_rules(state, options.rules).await?;
+    let variants = check_variants(state, options.variants).await?;
+    let created = chrono::Local::now();
+    let (window, ttl, inactive_url) = check_window(
+        state,
+        created.fixed_offset(),
+        options.not_before.as_deref(),
+        options.not_after.as_deref(),
+        options.inactive_url.as_deref(),
+    )
+    .await?;
+    let (not_before, not_after) = window.to_stored();
+    let expired_url =
+        check_optional_url(state, options.expired_url.as_deref(), "expired_url").await?;
+    if let Some(max_clicks) = options.max_clicks {
+        click_limits::check(max_clicks)
+            .map_err(|message| ApiError::field("max_clicks", "INVALID_MAX_CLICKS", message))?;
+    }
+    let password_hash = hash_password(options.password).await?;
+
+    let code = code(&long_url)?;
+    let key = state.config.link_key(&domain, &code);
     let data = Data {
-        creation_data: chrono::Local::now().to_rfc3339(),
-        shortened_url: full.clone(),
-        long_url: long_url.to_string(),
-        ttl: 30,
+        creation_data: created.to_rfc3339(),
+        shortened_url: state.config.short_url(&domain, &code),
+        long_url,
+        ttl,
+        owner: Some(principal.owner.clone()),
+        domain: Some(domain),
+        redirect_type: options.redirect_type,
+        passthrough: options.passthrough,
+        utm: options.utm,
+        campaign: options.campaign,
+        rules,
+        variants,
+        split: options.split,
+        not_before,
+        not_after,
+        inactive_url,
+        expired_url,
+        max_clicks: options.max_clicks,
+        password_hash,
+        interstitial: options.interstitial,
     };
+    Ok((key, data))
+}
 
-    store_data(redis_connection, id.clone(), data)
+/// Count a new link against the owner's quota.
+async fn reserve_quota(
+    state: &AppState,
+    principal: &Principal,
+    id: &str,
+    ttl: u32,
+    custom: bool,
+) -> Result<Reservation, ApiError> {
+    let quota = owner_quota(state, &principal.owner);
+    let expires_at = chrono::Utc::now().timestamp() + i64::from(ttl);
+    let reservation = quotas::reserve(
+        Arc::clone(&state.db),
+        &principal.owner,
+        id,
+        expires_at,
+        custom,
+        &quota,
+    )
+    .await
+    .map_err(|e| RedisError(format!("Redis quota error: {}", e)))?
+    .map_err(|exceeded| {
+        ApiError::new(StatusCode::FORBIDDEN, "QUOTA_EXCEEDED", exceeded.message())
+    })?;
+    publish_usage(state, &principal.owner).await;
+    Ok(reservation)
+}
+
+/// Refresh the usage gauges of `owner` if it is configured in `API_KEYS_FILE`.
+///
+/// Failures are only logged: the gauges must never fail the request that changed the usage.
+async fn publish_usage(state: &AppState, owner: &str) {
+    if state.auth.owner_settings(owner).is_none() {
+        return;
+    }
+    let quota = owner_quota(state, owner);
+    match quotas::usage(Arc::clone(&state.db), owner, &quota).await {
+        Ok(usage) => usage.publish(),
+        Err(e) => eprintln!("❌ Redis quota error: {}", e),
+    }
+}
+
+/// Store a link whose quota was reserved, releasing the reservation if storing fails.
+///
+/// The key is claimed first, so a link taken meanwhile is answered with a conflict and none
+/// of its keys are overwritten.
+async fn store_reserved(
+    state: &AppState,
+    principal: &Principal,
+    reservation: Reservation,
+    id: String,
+    data: Data,
+) -> Result<(), ApiError> {
+    let release = || async {
+        match quotas::release(Arc::clone(&state.db), &principal.owner, &id, &reservation).await {
+            Ok(()) => publish_usage(state, &principal.owner).await,
+            Err(e) => eprintln!("❌ Redis quota error: {}", e),
+        }
+    };
+    match claim_key(Arc::clone(&state.db), &id, data.ttl).await {
+        Ok(true) => {}
+        Ok(false) => {
+            release().await;
+            return Err(ApiError::new(
+                StatusCode::CONFLICT,
+                "ALIAS_EXISTS",
+                format!(
+                    "Alias already exists on {}",
+                    data.domain.as_deref().unwrap_or_default()
+                ),
+            ));
+        }
+        Err(e) => {
+            release().await;
+            return Err(RedisError(format!("Redis storage error: {}", e)).into());
+        }
+    }
+
+    // The use counter goes before the data, so a stored click-limited link always has one
+    let stored = async {
+        if let Some(max_clicks) = data.max_clicks {
+            click_limits::init(Arc::clone(&state.db), &id, max_clicks, data.ttl.into()).await?;
+        }
+        if let Some(url) = &data.expired_url {
+            let retention = state.config.expired_fallback_retention;
+            fallback::store(Arc::clone(&state.db), &id, url, data.ttl.into(), retention).await?;
+        }
+        store_data(Arc::clone(&state.db), id.clone(), data).await
+    };
+    if let Err(e) = stored.await {
+        let _ = delete_data(Arc::clone(&state.db), &id).await;
+        release().await;
+        return Err(RedisError(format!("Redis storage error: {}", e)).into());
+    }
+    Ok(())
+}
+
+/// Report the caller's current quota usage.
+#[utoipa::path(
+    get,
+    path = "/api/v1/usage",
+    tag = "links",
+    responses(
+        (status = 200, description = "Usage and limits of the caller", body = Usage),
+        (status = 401, description = "Missing or invalid credentials", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Missing the links:read scope", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub async fn handle_usage(
+    credentials: Credentials,
+    state: AppState,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(respond(usage(credentials, state).await))
+}
+
+async fn usage(credentials: Credentials, state: AppState) -> Result<impl Reply, ApiError> {
+    let principal = authorize(&state, &credentials, None, SCOPE_LINKS_READ).await?;
+
+    let quota = owner_quota(&state, &principal.owner);
+    let usage = quotas::usage(Arc::clone(&state.db), &principal.owner, &quota)
         .await
-        .map_err(|e| reject::custom(RedisError(format!("Redis storage error: {}", e))))?;
+        .map_err(|e| RedisError(format!("Redis quota error: {}", e)))?;
+    Ok(warp::reply::json(&usage))
+}
 
-    let body = serde_json::json!({
-      "status": "success",
-      "short_url": full
-    });
-    Ok(warp::reply::with_status(
-        warp::reply::json(&body),
-        StatusCode::OK,
+/// Find one of the caller's links, returning its storage key, domain and record.
+async fn owned_link(
+    state: &AppState,
+    credentials: &Credentials,
+    code: &str,
+    query: &LinkQuery,
+) -> Result<(String, String, Data), ApiError> {
+    let principal = authorize(state, credentials, None, SCOPE_LINKS_READ).await?;
+    let domain = link_domain(state, &principal, query.domain.as_deref())?;
+
+    // Links of other owners are reported as missing
+    let key = state.config.link_key(&domain, code);
+    match retrieve_data(Arc::clone(&state.db), &key).await {
+        Some(data) if data.owner.as_deref() == Some(principal.owner.as_str()) => {
+            Ok((key, domain, data))
+        }
+        _ => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "LINK_NOT_FOUND",
+            "Short URL not found",
+        )),
+    }
+}
+
+/// Describe one of the caller's links, including the redirects it has left.
+#[utoipa::path(
+    get,
+    path = "/api/v1/links/{code}",
+    tag = "links",
+    params(("code" = String, Path, description = "Short code of the link"), LinkQuery),
+    responses(
+        (status = 200, description = "The link", body = LinkDetails),
+        (status = 400, description = "Unknown domain", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Missing the links:read scope or the domain", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such link for the caller", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 503, description = "Storage unavailable", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub async fn handle_link_details(
+    code: String,
+    query: LinkQuery,
+    credentials: Credentials,
+    state: AppState,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(respond(link_details(code, query, credentials, state).await))
+}
+
+async fn link_details(
+    code: String,
+    query: LinkQuery,
+    credentials: Credentials,
+    state: AppState,
+) -> Result<impl Reply, ApiError> {
+    let (key, domain, data) = owned_link(&state, &credentials, &code, &query).await?;
+    let remaining_clicks = match data.max_clicks {
+        Some(_) => Some(
+            click_limits::remaining(Arc::clone(&state.db), &key)
+                .await
+                .map_err(|e| RedisError(format!("Redis click limit error: {}", e)))?,
+        ),
+        None => None,
+    };
+    Ok(warp::reply::json(&LinkDetails {
+        short_url: data.shortened_url,
+        long_url: data.long_url,
+        domain,
+        created_at: data.creation_data,
+        not_before: data.not_before,
+        not_after: data.not_after,
+        max_clicks: data.max_clicks,
+        remaining_clicks,
+        password_protected: data.password_hash.is_some(),
+        interstitial: data.interstitial,
+    }))
+}
+
+/// Report the click counters of one of the caller's links.
+#[utoipa::path(
+    get,
+    path = "/api/v1/links/{code}/stats",
+    tag = "links",
+    params(("code" = String, Path, description = "Short code of the link"), LinkQuery),
+    responses(
+        (status = 200, description = "Clicks of the link, per split variant", body = ClickStats),
+        (status = 400, description = "Unknown domain", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Missing the links:read scope or the domain", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No such link for the caller", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 503, description = "Storage unavailable", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub async fn handle_link_stats(
+    code: String,
+    query: LinkQuery,
+    credentials: Credentials,
+    state: AppState,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(respond(link_stats(code, query, credentials, state).await))
+}
+
+async fn link_stats(
+    code: String,
+    query: LinkQuery,
+    credentials: Credentials,
+    state: AppState,
+) -> Result<impl Reply, ApiError> {
+    let (key, _, _) = owned_link(&state, &credentials, &code, &query).await?;
+    let stats = analytics::link_stats(Arc::clone(&state.db), &key)
+        .await
+        .map_err(|e| RedisError(format!("Redis stats error: {}", e)))?;
+    Ok(warp::reply::json(&stats))
+}
+
+/// Liveness check.
+#[utoipa::path(
+    get,
+    path = "/ping",
+    tag = "operations",
+    responses((status = 200, description = "The service is up", body = String, example = json!("pong")))
+)]
+pub async fn handle_ping() -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(&"pong"))
+}
+
+/// Prometheus metrics in the text exposition format.
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Current metrics", body = String, content_type = "text/plain"),
+        (status = 500, description = "Metrics could not be encoded", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn handle_metrics() -> Result<impl warp::Reply, warp::Rejection> {
+    let encoder = TextEncoder::new();
+    let metric_families = prometheus::gather();
+    let mut buffer = Vec::new();
+    let result = match encoder.encode(&metric_families, &mut buffer) {
+        Ok(_) => Ok(warp::reply::with_header(
+            String::from_utf8(buffer).unwrap(),
+            "Content-Type",
+            encoder.format_type(),
+        )),
+        Err(e) => Err(ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "METRICS_UNAVAILABLE",
+            format!("Failed to encode metrics: {}", e),
+        )),
+    };
+    Ok(respond(result))
+}
+
+/// Turn every rejection into a problem document.
+pub async fn handle_rejection(err: warp::Rejection) -> Result<warp::reply::Response, Infallible> {
+    let error = if let Some(error) = err.find::<ApiError>() {
+        error.clone()
+    } else if let Some(RateLimited(decision)) = err.find::<RateLimited>() {
+        rate_limited_reply(decision)
+    } else if let Some(error) = err.find::<RedisError>() {
+        ApiError::from(RedisError(error.0.clone()))
+    } else if err.is_not_found() {
+        ApiError::new(StatusCode::NOT_FOUND, "NOT_FOUND", "No such route")
+    } else if let Some(e) = err.find::<warp::filters::body::BodyDeserializeError>() {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "INVALID_JSON",
+            format!("The request body is not valid JSON: {}", e),
+        )
+    } else if err.find::<reject::PayloadTooLarge>().is_some() {
+        ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "PAYLOAD_TOO_LARGE",
+            "The request body is too large",
+        )
+    } else if err.find::<reject::UnsupportedMediaType>().is_some() {
+        ApiError::new(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "UNSUPPORTED_MEDIA_TYPE",
+            "The request body must be application/json",
+        )
+    } else if let Some(e) = err.find::<reject::MissingHeader>() {
+        ApiError::new(StatusCode::BAD_REQUEST, "INVALID_HEADER", e.to_string())
+    } else if let Some(e) = err.find::<reject::InvalidHeader>() {
+        ApiError::new(StatusCode::BAD_REQUEST, "INVALID_HEADER", e.to_string())
+    } else if let Some(e) = err.find::<reject::InvalidQuery>() {
+        ApiError::new(StatusCode::BAD_REQUEST, "INVALID_QUERY", e.to_string())
+    } else if err.find::<reject::MethodNotAllowed>().is_some() {
+        ApiError::new(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "METHOD_NOT_ALLOWED",
+            "Method not allowed on this route",
+        )
+    } else {
+        eprintln!("❌ Unhandled rejection: {:?}", err);
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "INTERNAL_ERROR",
+            "Internal server error",
+        )
+    };
+    Ok(error.into_response())
+}
+
+/// Handle the generation of short URLs, storing the information in Redis.
+///
+/// The raw body is taken so HMAC signed requests can be verified before parsing. Unknown
+/// fields are rejected on `/api/v1` and ignored on the legacy route, as `unknown` says.
+#[utoipa::path(
+    post,
+    path = "/api/v1/links",
+    tag = "links",
+    request_body = CreateLinkRequest,
+    responses(
+        (status = 200, description = "Link created", body = CreateLinkResponse),
+        (status = 400, description = "Invalid body or destination", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Scope, domain policy or quota denied the link", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limited", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 503, description = "Storage unavailable", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("api_key" = []), ("bearer" = []), ("signature" = []))
+)]
+pub async fn handle_generate_url(
+    credentials: Credentials,
+    body: Bytes,
+    state: AppState,
+    unknown: UnknownFields,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(respond(
+        generate_url(credentials, body, state, unknown).await,
     ))
 }
 
+async fn generate_url(
+    credentials: Credentials,
+    body: Bytes,
+    state: AppState,
+    unknown: UnknownFields,
+) -> Result<impl Reply, ApiError> {
+    // Verify API key, bearer token or request signature
+    let principal = authorize(&state, &credentials, Some(&body), SCOPE_LINKS_WRITE).await?;
+    limit_owner(&state, &principal).await?;
+
+    let request: CreateLinkRequest = parse_body_with(&body, unknown)?;
+    let (key, data) = new_link(&state, &principal, request.options, |long_url| {
+        // Generate the short URL
+        let mut id = generate_short_url_id(long_url);
+        while routes::ROUTE_NAMES.contains(&id.as_str()) {
+            id = generate_short_url_id(long_url);
+        }
+        Ok(id)
+    })
+    .await?;
+    let full = data.shortened_url.clone();
+
+    let reservation = reserve_quota(&state, &principal, &key, data.ttl, false).await?;
+    store_reserved(&state, &principal, reservation, key, data).await?;
+
+    Ok(warp::reply::json(&CreateLinkResponse::success(full)))
+}
+
 /// Generate a unique short URL from the long URL.
 pub struct SnowflakeGenerator {
     node_id: i64,
@@ -87,7 +1049,7 @@ pub struct SnowflakeGenerator {
 impl SnowflakeGenerator {
     pub fn new(node_id: i64) -> Self {
         assert!(
-            node_id >= 0 && node_id <= MAX_NODE_ID,
+            (0..=MAX_NODE_ID).contains(&node_id),
             "Node ID must be between 0 and {}",
             MAX_NODE_ID
         );
@@ -143,108 +1105,477 @@ pub fn generate_short_url_id(_long_url: &str) -> String {
 }
 
 /// Handle redirect for a given short URL.
+///
+/// `params` holds the `short_url` code and the request's `host`, which selects the domain
+/// namespace the code is looked up in, the trailing `path` and raw `query` that links with
+/// passthrough forward to their destination, the lowercase [`targeting::CLIENT_HEADERS`]
+/// the destination rules are matched against, the `cookie` header keeping split visitors on
+/// their variant, the resolved `client_ip`, the `password` posted to a protected link, and the
+/// `accept` header choosing between an HTML error page and a JSON error.
+#[utoipa::path(
+    get,
+    path = "/{short_url}",
+    tag = "redirect",
+    params(("short_url" = String, Path, description = "Short code or custom alias, optionally followed by a path forwarded by links with `passthrough.path`")),
+    responses(
+        (status = 200, description = "Password form of a protected link, or the interstitial warning shown until `rs_continue` carries its token", body = String, content_type = "text/html"),
+        (status = 301, description = "Permanent redirect to the destination URL, cacheable"),
+        (status = 302, description = "Redirect to the destination URL for legacy clients"),
+        (status = 307, description = "Temporary redirect to the destination URL (default), or to the fallback URL of a missing, expired or inactive link"),
+        (status = 308, description = "Permanent redirect keeping the request method, cacheable"),
+        (status = 403, description = "Destination disabled by the domain policy", body = String, content_type = "text/html"),
+        (status = 404, description = "Unknown or expired link; an HTML page when `Accept` prefers `text/html`", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limited", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
 pub async fn handle_redirect_url(
     params: HashMap<String, String>,
-    redis_connection: Arc<Mutex<redis::aio::MultiplexedConnection>>,
+    state: AppState,
 ) -> Result<Box<dyn warp::Reply>, warp::Rejection> {
     let short_url = params.get("short_url").cloned().unwrap_or_default();
+    let domain = state
+        .config
+        .domain_for_host(params.get("host").map(String::as_str));
+
+    // Links leave Redis when their TTL runs out; their `expired_url` is kept a while longer
+    let (key, data) = match find_link(&state, &domain, &short_url).await {
+        Ok(found) => found,
+        Err(tried) => {
+            let fallback = missing_link_fallback(&state, &domain, &tried).await;
+            if let Some(reply) = fallback.as_deref().and_then(fallback_reply) {
+                return Ok(reply);
+            }
+            return Ok(link_error(
+                &state,
+                &params,
+                "Link not found",
+                ApiError::new(
+                    StatusCode::NOT_FOUND,
+                    "LINK_NOT_FOUND",
+                    "Short URL not found",
+                ),
+            ));
+        }
+    };
 
-    if let Some(data) = retrieve_data(redis_connection, &short_url).await {
-        let now = chrono::Local::now();
-        let expiration_time = chrono::DateTime::parse_from_rfc3339(&data.creation_data).unwrap()
-            + chrono::Duration::seconds(data.ttl.into());
-
-        if now > expiration_time {
-            return Ok(Box::new(warp::reply::with_status(
-                warp::reply::json(&serde_json::json!({
-                    "status": "error",
-                    "message": "Short URL has expired!"
-                })),
+    let now = chrono::Local::now();
+    let expiration_time = expiration_time(&data);
+
+    if now > expiration_time {
+        let fallback = data
+            .expired_url
+            .as_deref()
+            .or_else(|| fallback::for_domain(&state.config.domain_fallbacks, &domain));
+        if let Some(reply) = fallback.and_then(fallback_reply) {
+            return Ok(reply);
+        }
+        return Ok(link_error(
+            &state,
+            &params,
+            "Link expired",
+            ApiError::new(
                 StatusCode::NOT_FOUND,
-            )));
+                "LINK_EXPIRED",
+                "Short URL has expired!",
+            ),
+        ));
+    }
+
+    // Outside its activation window the link serves its fallback or an error
+    let window = Window::from_stored(data.not_before.as_deref(), data.not_after.as_deref());
+    let now = now.fixed_offset();
+    match window.state(now) {
+        WindowState::Active => {}
+        inactive => return Ok(inactive_reply(&state, &params, &data, inactive)),
+    }
+
+    // Only protected links take a posted form; anything else would consume a click
+    if params.contains_key("password") && data.password_hash.is_none() {
+        return Ok(Box::new(
+            ApiError::new(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "METHOD_NOT_ALLOWED",
+                "This link is not password protected",
+            )
+            .with_header("Allow", "GET"),
+        ));
+    }
+
+    // The interstitial's continue token is only handed out once any password was entered
+    let client_ip = params
+        .get("client_ip")
+        .map(String::as_str)
+        .unwrap_or_default();
+    let (token, query) =
+        interstitial::take_token(params.get("query").map(String::as_str).unwrap_or_default());
+    let confirmed = token.is_some_and(|token| {
+        state
+            .interstitial
+            .verify(&token, &key, client_ip, now.timestamp())
+    });
+
+    // Protected links show a form until the right password is posted
+    if let Some(hash) = data.password_hash.as_ref().filter(|_| !confirmed) {
+        if let Err(reply) = unlock(&state, &key, hash, &params).await {
+            return Ok(reply);
         }
+    }
 
-        // Perform HTTP redirect to the long URL
-        if let Ok(uri) = data.long_url.parse::<warp::http::Uri>() {
-            return Ok(Box::new(warp::redirect::temporary(uri)));
-        } else {
-            return Ok(Box::new(warp::reply::with_status(
-                warp::reply::json(&serde_json::json!({
-                    "status": "error",
-                    "message": "Invalid long URL format"
-                })),
-                StatusCode::BAD_REQUEST,
-            )));
+    let remaining = expiration_time
+        .signed_duration_since(now)
+        .num_seconds()
+        .max(0) as u64;
+
+    // Pick the destination for this visitor's device, platform, language and country,
+    // then split the remaining visitors between the link's variants
+    let mut client = Client::detect(|name| params.get(name).map(String::as_str));
+    if let Some(geoip) = &state.geoip {
+        client.country = params
+            .get("client_ip")
+            .and_then(|ip| ip.parse().ok())
+            .and_then(|ip| geoip.country(ip));
+    }
+    let rule = targeting::select(&data.rules, &client);
+    let mut variant = None;
+    let mut assigned = false;
+    if rule.is_none() && !data.variants.is_empty() {
+        let cookie = variants::cookie_name(&short_url);
+        variant = params
+            .get("cookie")
+            .filter(|_| data.split == SplitMode::Sticky)
+            .and_then(|header| variants::read_cookie(header, &cookie))
+            .and_then(|name| variants::find(&data.variants, name));
+        if variant.is_none() {
+            variant = variants::pick_random(&data.variants);
+            assigned = data.split == SplitMode::Sticky;
         }
     }
+    let destination = match (rule, variant) {
+        (Some(rule), _) => rule.long_url.as_str(),
+        (None, Some(variant)) => variant.long_url.as_str(),
+        (None, None) => data.long_url.as_str(),
+    };
+
+    // Re-check the destination so links to newly blocked domains stop resolving
+    if let Err((host, denied)) = state.domain_policy.current().check_url(destination) {
+        return Ok(Box::new(page_reply(
+            StatusCode::FORBIDDEN,
+            "This link has been disabled",
+            &format!(
+                "This short link points to {}, which is not permitted by our link policy. {}",
+                host,
+                denied.message(&host)
+            ),
+        )));
+    }
 
-    // URL not found
-    Ok(Box::new(warp::reply::with_status(
-        warp::reply::json(&serde_json::json!({
-            "status": "error",
-            "message": "Short URL not found"
-        })),
-        StatusCode::NOT_FOUND,
-    )))
+    // Warn visitors leaving for external or reported sites until they choose to continue
+    let path = params.get("path").map(String::as_str).unwrap_or_default();
+    let warning = interstitial::select(
+        &state.config.interstitial_domains,
+        data.interstitial,
+        destination,
+    );
+    if let Some(kind) = warning.filter(|_| !confirmed) {
+        let token = state.interstitial.sign(&key, client_ip, now.timestamp());
+        let short_link = state.config.short_url(&domain, &short_url);
+        return Ok(Box::new(interstitial_reply(
+            kind,
+            destination,
+            &interstitial::continue_url(&short_link, path, &query, &token),
+        )));
+    }
+
+    // Add UTM parameters, then forward the trailing path and query string when allowed
+    let destination = with_utm(&state, &data, destination, &short_url, &domain);
+    let Some(long_url) = data.passthrough.apply(&destination, path, &query) else {
+        return Ok(link_error(
+            &state,
+            &params,
+            "Link not found",
+            ApiError::new(
+                StatusCode::NOT_FOUND,
+                "LINK_NOT_FOUND",
+                "Short URL not found",
+            ),
+        ));
+    };
+
+    // Perform HTTP redirect to the long URL; permanent redirects are cached until expiry at most
+    let redirect_type = data
+        .redirect_type
+        .unwrap_or(state.config.default_redirect_type);
+    // Split links are never cached, or every visit would land on the same variant;
+    // neither are country rules, as no header tells caches where the visitor is,
+    // nor click-limited links, whose every visit must be counted, nor links behind an
+    // interstitial, which must show on every visit
+    let by_country = data.rules.iter().any(|rule| !rule.countries.is_empty());
+    let max_age = if data.variants.is_empty()
+        && !by_country
+        && data.max_clicks.is_none()
+        && warning.is_none()
+    {
+        state
+            .config
+            .redirect_cache_max_age
+            .min(remaining)
+            .min(window.remaining(now).unwrap_or(u64::MAX))
+    } else {
+        0
+    };
+    match long_url.parse::<warp::http::Uri>() {
+        Ok(uri) => {
+            // Take one of the link's uses; exhausted links stay stored but disabled
+            if data.max_clicks.is_some() {
+                match click_limits::consume(Arc::clone(&state.db), &key).await {
+                    Ok(Some(_)) => {}
+                    Ok(None) => {
+                        return Ok(link_error(
+                            &state,
+                            &params,
+                            "Link no longer available",
+                            ApiError::new(
+                                StatusCode::GONE,
+                                "LINK_EXHAUSTED",
+                                "This link has reached its click limit",
+                            ),
+                        ))
+                    }
+                    Err(e) => {
+                        return Ok(Box::new(ApiError::from(RedisError(format!(
+                            "Redis click limit error: {}",
+                            e
+                        )))))
+                    }
+                }
+            }
+
+            let mut response = redirect_type.reply(&uri, max_age);
+            // The form was posted: send the browser on with a GET, and never cache the result
+            if data.password_hash.is_some() {
+                *response.status_mut() = StatusCode::SEE_OTHER;
+                response.headers_mut().insert(
+                    warp::http::header::CACHE_CONTROL,
+                    warp::http::HeaderValue::from_static("no-store"),
+                );
+            }
+            let headers = response.headers_mut();
+            // Caches must not hand one visitor's destination to another
+            let vary = match (data.rules.is_empty(), data.variants.is_empty()) {
+                (true, true) => None,
+                (true, false) => Some("Cookie"),
+                (false, true) => {
+                    Some("User-Agent, Accept-Language, Sec-CH-UA-Platform, Sec-CH-UA-Mobile")
+                }
+                (false, false) => Some(
+                    "User-Agent, Accept-Language, Sec-CH-UA-Platform, Sec-CH-UA-Mobile, Cookie",
+                ),
+            };
+            if let Some(vary) = vary {
+                headers.insert(
+                    warp::http::header::VARY,
+                    warp::http::HeaderValue::from_static(vary),
+                );
+            }
+            if let Some(variant) = variant.filter(|_| assigned) {
+                let cookie = variants::set_cookie(
+                    &short_url,
+                    &variant.name,
+                    remaining,
+                    state.config.public_base_url.is_https(),
+                );
+                if let Ok(value) = warp::http::HeaderValue::from_str(&cookie) {
+                    headers.insert(warp::http::header::SET_COOKIE, value);
+                }
+            }
+
+            // Count the click without delaying the redirect
+            let db = Arc::clone(&state.db);
+            let variant = variant.map(|variant| variant.name.clone());
+            tokio::spawn(async move {
+                if let Err(e) =
+                    analytics::record_click(db, &key, variant.as_deref(), remaining).await
+                {
+                    eprintln!("❌ Redis analytics error: {}", e);
+                }
+            });
+            Ok(Box::new(response))
+        }
+        Err(_) => Ok(Box::new(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "INVALID_DESTINATION",
+            "Invalid long URL format",
+        ))),
+    }
+}
+
+/// Submit the password of a protected link, from the form its `GET` shows.
+#[utoipa::path(
+    post,
+    path = "/{short_url}",
+    tag = "redirect",
+    params(("short_url" = String, Path, description = "Short code or custom alias")),
+    request_body(content = PasswordForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Correct password: redirect to the destination URL"),
+        (status = 403, description = "Incorrect password, with the form again", body = String, content_type = "text/html"),
+        (status = 404, description = "Unknown or expired link", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 405, description = "The link is not password protected", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Too many attempts for this link from the client IP", body = String, content_type = "text/html"),
+    )
+)]
+pub async fn handle_unlock_url(
+    mut params: HashMap<String, String>,
+    form: PasswordForm,
+    state: AppState,
+) -> Result<Box<dyn warp::Reply>, warp::Rejection> {
+    params.insert("password".to_string(), form.password);
+    handle_redirect_url(params, state).await
+}
+
+/// Show where a link goes without following it; the visit is not counted as a click.
+///
+/// Served at `/{short_url}/preview` and `/{short_url}+`, with the same `params` as
+/// [`handle_redirect_url`].
+#[utoipa::path(
+    get,
+    path = "/{short_url}/preview",
+    tag = "redirect",
+    params(("short_url" = String, Path, description = "Short code or custom alias; `/{short_url}+` shows the same page")),
+    responses(
+        (status = 200, description = "Preview page with the destination, creation and expiry times", body = String, content_type = "text/html"),
+        (status = 404, description = "Unknown or expired link; an HTML page when `Accept` prefers `text/html`", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limited", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn handle_preview_url(
+    params: HashMap<String, String>,
+    state: AppState,
+) -> Result<Box<dyn warp::Reply>, warp::Rejection> {
+    let short_url = params
+        .get("short_url")
+        .map(String::as_str)
+        .unwrap_or_default();
+    let path = params.get("path").map(String::as_str).unwrap_or_default();
+    let code = preview::preview_code(short_url, path).unwrap_or(short_url);
+    let domain = state
+        .config
+        .domain_for_host(params.get("host").map(String::as_str));
+
+    let now = chrono::Local::now();
+    let data = match find_link(&state, &domain, code).await {
+        Ok((_, data)) if now <= expiration_time(&data) => data,
+        found => {
+            let (title, error) = match found {
+                Ok(_) => (
+                    "Link expired",
+                    ApiError::new(
+                        StatusCode::NOT_FOUND,
+                        "LINK_EXPIRED",
+                        "Short URL has expired!",
+                    ),
+                ),
+                Err(_) => (
+                    "Link not found",
+                    ApiError::new(
+                        StatusCode::NOT_FOUND,
+                        "LINK_NOT_FOUND",
+                        "Short URL not found",
+                    ),
+                ),
+            };
+            return Ok(link_error(&state, &params, title, error));
+        }
+    };
+
+    // Flag the link if any visitor could be sent to a domain the policy denies, or that
+    // was reported, without naming the host of a protected link
+    let destinations: Vec<&str> = std::iter::once(data.long_url.as_str())
+        .chain(data.rules.iter().map(|rule| rule.long_url.as_str()))
+        .chain(
+            data.variants
+                .iter()
+                .map(|variant| variant.long_url.as_str()),
+        )
+        .collect();
+    let warning = preview::warning(
+        &state.domain_policy.current(),
+        &state.config.interstitial_domains,
+        &destinations,
+        data.password_hash.is_some(),
+    );
+
+    let window = Window::from_stored(data.not_before.as_deref(), data.not_after.as_deref());
+    let preview = LinkPreview {
+        short_url: data.shortened_url.clone(),
+        destination: data.password_hash.is_none().then(|| data.long_url.clone()),
+        varies: !data.rules.is_empty() || !data.variants.is_empty(),
+        created_at: chrono::DateTime::parse_from_rfc3339(&data.creation_data).unwrap(),
+        expires_at: expiration_time(&data),
+        not_before: window.not_before,
+        not_after: window.not_after,
+        warning,
+    };
+    let mut response = warp::reply::html(render_preview(&preview)).into_response();
+    response.headers_mut().insert(
+        warp::http::header::CACHE_CONTROL,
+        warp::http::HeaderValue::from_static("no-store"),
+    );
+    Ok(Box::new(response))
 }
 
 /// Handle creation of a user-defined custom short URL.
+///
+/// Unknown fields are rejected on `/api/v1` and ignored on the legacy route, as `unknown` says.
+#[utoipa::path(
+    post,
+    path = "/api/v1/links/custom",
+    tag = "links",
+    request_body = CustomLinkRequest,
+    responses(
+        (status = 200, description = "Link created", body = CreateLinkResponse),
+        (status = 400, description = "Invalid body, destination or alias", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Scope, domain policy or quota denied the link", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "Alias already exists", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limited", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 503, description = "Storage unavailable", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
 pub async fn handle_custom_url(
-    key: String,
-    body: serde_json::Value,
-    redis_connection: Arc<Mutex<redis::aio::MultiplexedConnection>>,
-    expected_api_key: String,
+    credentials: Credentials,
+    body: Bytes,
+    state: AppState,
+    unknown: UnknownFields,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    // Verify API key
-    if key != expected_api_key {
-        return Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({ "error": "UNAUTHORIZED" })),
-            StatusCode::UNAUTHORIZED,
-        ));
-    }
+    Ok(respond(custom_url(credentials, body, state, unknown).await))
+}
+
+async fn custom_url(
+    credentials: Credentials,
+    body: Bytes,
+    state: AppState,
+    unknown: UnknownFields,
+) -> Result<impl Reply, ApiError> {
+    // Verify API key or bearer token
+    let principal = authorize(&state, &credentials, None, SCOPE_LINKS_WRITE).await?;
+    limit_owner(&state, &principal).await?;
 
     // Validate input fields
-    let long_url = body["long_url"].as_str().unwrap_or("");
-    let custom_short = body["custom_short"].as_str().unwrap_or("");
-    if long_url.is_empty() || custom_short.is_empty() {
-        return Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({
-                "error": "Missing long_url or custom_short"
-            })),
-            StatusCode::BAD_REQUEST,
-        ));
-    }
+    let request: CustomLinkRequest = parse_body_with(&body, unknown)?;
+    let alias_rules = &state.config.alias_rules;
+    let (key, data) = new_link(&state, &principal, request.options, |_| {
+        alias_rules
+            .check(&request.custom_short)
+            .map_err(|e| ApiError::field("custom_short", e.code(), e.message(alias_rules)))
+    })
+    .await?;
+    let full = data.shortened_url.clone();
 
-    // Check for existing alias (collision detection)
-    if retrieve_data(Arc::clone(&redis_connection), &custom_short).await.is_some() {
-        return Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({ "error": "Alias already exists" })),
-            StatusCode::CONFLICT,
-        ));
-    }
+    // Count the alias against the owner's quota, then claim it and store it in Redis
+    let reservation = reserve_quota(&state, &principal, &key, data.ttl, true).await?;
+    store_reserved(&state, &principal, reservation, key, data).await?;
 
-    // Build full URL and data struct
-    let full = format!("{}/dns_resolver/{}", BASE_URL, custom_short);
-    let data = Data {
-        creation_data: chrono::Local::now().to_rfc3339(),
-        shortened_url: full.clone(),
-        long_url: long_url.to_string(),
-        ttl: 30,
-    };
-
-    // Attempt to store in Redis
-    let key = custom_short.to_string();
-    match store_data(redis_connection, key.clone(), data).await {
-        Ok(_) => Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({
-                "status": "success",
-                "short_url": full
-            })),
-            StatusCode::OK,
-        )),
-        Err(e) => Err(reject::custom(RedisError(format!(
-            "Redis storage error: {}",
-            e
-        )))),
-    }
+    Ok(warp::reply::json(&CreateLinkResponse::success(full)))
 }
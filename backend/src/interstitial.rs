@@ -0,0 +1,224 @@
+use crate::domain_policy::DomainRule;
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+/// Query parameter carrying the token of the interstitial's continue button.
+pub const TOKEN_PARAM: &str = "rs_continue";
+
+/// Page shown before redirecting visitors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Interstitial {
+    /// Redirect straight away, unless the destination's domain was reported.
+    Off,
+    /// "You are leaving our site".
+    Leaving,
+    /// "This link was reported".
+    Reported,
+}
+
+impl Interstitial {
+    pub fn title(&self) -> &'static str {
+        match self {
+            Interstitial::Off => "Redirecting",
+            Interstitial::Leaving => "You are leaving our site",
+            Interstitial::Reported => "This link was reported",
+        }
+    }
+
+    /// Explain the page to a visitor about to go to `host`.
+    pub fn message(&self, host: &str) -> String {
+        match self {
+            Interstitial::Off => format!("You are being redirected to {}.", host),
+            Interstitial::Leaving => format!(
+                "This link takes you to {}, an external site we do not control.",
+                host
+            ),
+            Interstitial::Reported => format!(
+                "This link takes you to {}, which has been reported as unsafe. \
+                 Only continue if you trust it.",
+                host
+            ),
+        }
+    }
+}
+
+impl FromStr for Interstitial {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(Interstitial::Off),
+            "leaving" => Ok(Interstitial::Leaving),
+            "reported" => Ok(Interstitial::Reported),
+            _ => Err(format!("Expected off, leaving or reported, got `{}`", s)),
+        }
+    }
+}
+
+/// The interstitial shown for destinations matching `rule`, written `rule=kind`.
+///
+/// Rules are those of the domain lists, e.g. `.example.com=off` or `*=leaving`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomainInterstitial {
+    pub rule: DomainRule,
+    pub kind: Interstitial,
+}
+
+impl FromStr for DomainInterstitial {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rule, kind) = s
+            .trim()
+            .rsplit_once('=')
+            .ok_or_else(|| format!("Expected <domain rule>=<kind>, got `{}`", s))?;
+        Ok(DomainInterstitial {
+            rule: DomainRule::parse(rule)?,
+            kind: kind.parse()?,
+        })
+    }
+}
+
+/// The interstitial to show before redirecting to `url`, if any.
+///
+/// The first rule matching the destination's host applies unless the link sets its own;
+/// a `reported` domain always wins, so links cannot opt out of the warning.
+pub fn select(
+    rules: &[DomainInterstitial],
+    link: Option<Interstitial>,
+    url: &str,
+) -> Option<Interstitial> {
+    let host = host(url);
+    let domain = rules
+        .iter()
+        .find(|rule| rule.rule.matches(&host))
+        .map(|rule| rule.kind);
+    let kind = match (domain, link) {
+        (Some(Interstitial::Reported), _) => Interstitial::Reported,
+        (_, Some(link)) => link,
+        (Some(domain), None) => domain,
+        (None, None) => Interstitial::Off,
+    };
+    (kind != Interstitial::Off).then_some(kind)
+}
+
+/// The host of `url`, lowercase; empty when it has none.
+pub fn host(url: &str) -> String {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+        .unwrap_or_default()
+}
+
+/// The short link visited with its trailing `path` and `query`, plus the continue `token`.
+pub fn continue_url(short_link: &str, path: &str, query: &str, token: &str) -> String {
+    let mut url = short_link.to_string();
+    if !path.is_empty() {
+        url.push('/');
+        url.push_str(path);
+    }
+    url.push('?');
+    if !query.is_empty() {
+        url.push_str(query);
+        url.push('&');
+    }
+    url.push_str(&format!("{}={}", TOKEN_PARAM, token));
+    url
+}
+
+/// Split the continue token off a raw query string, returning it and the remaining query.
+pub fn take_token(query: &str) -> (Option<String>, String) {
+    let mut token = None;
+    let rest: Vec<&str> = query
+        .split('&')
+        .filter(|pair| {
+            match pair
+                .strip_prefix(TOKEN_PARAM)
+                .and_then(|v| v.strip_prefix('='))
+            {
+                Some(value) => {
+                    token = Some(value.to_string());
+                    false
+                }
+                None => !pair.is_empty(),
+            }
+        })
+        .collect();
+    (token, rest.join("&"))
+}
+
+/// Signs the short-lived tokens of the interstitials' continue buttons.
+///
+/// A token is bound to the link and the visitor's IP, so it cannot be reused elsewhere.
+pub struct TokenSigner {
+    key: hmac::Key,
+    ttl: u64,
+}
+
+impl TokenSigner {
+    /// Sign with `secret`; every backend must share it for tokens to work across them.
+    pub fn new(secret: &[u8], ttl: u64) -> Self {
+        TokenSigner {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+            ttl,
+        }
+    }
+
+    /// Sign with the secret stored in the file at `path`.
+    pub fn load(path: &Path, ttl: u64) -> Result<Self, String> {
+        let secret =
+            std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let secret = secret.trim_ascii();
+        if secret.is_empty() {
+            return Err(format!(
+                "Interstitial secret file {} is empty",
+                path.display()
+            ));
+        }
+        Ok(TokenSigner::new(secret, ttl))
+    }
+
+    /// Sign with a random secret, only valid within this process.
+    pub fn random(ttl: u64) -> Self {
+        let mut secret = [0u8; 32];
+        SystemRandom::new()
+            .fill(&mut secret)
+            .expect("system random generator failed");
+        TokenSigner::new(&secret, ttl)
+    }
+
+    /// A token for `client` to continue to the link stored at `link_key`, as of `now`.
+    pub fn sign(&self, link_key: &str, client: &str, now: i64) -> String {
+        let expires = now.saturating_add(self.ttl as i64);
+        let tag = hmac::sign(&self.key, message(link_key, client, expires).as_bytes());
+        format!("{}.{}", expires, hex::encode(tag.as_ref()))
+    }
+
+    /// Whether `token` was signed for `client` and `link_key` and has not expired at `now`.
+    pub fn verify(&self, token: &str, link_key: &str, client: &str, now: i64) -> bool {
+        let Some((expires, tag)) = token.split_once('.') else {
+            return false;
+        };
+        let (Ok(expires), Ok(tag)) = (expires.parse::<i64>(), hex::decode(tag)) else {
+            return false;
+        };
+        if now > expires || expires > now.saturating_add(self.ttl as i64) {
+            return false;
+        }
+        hmac::verify(
+            &self.key,
+            message(link_key, client, expires).as_bytes(),
+            &tag,
+        )
+        .is_ok()
+    }
+}
+
+fn message(link_key: &str, client: &str, expires: i64) -> String {
+    format!("{}\n{}\n{}", link_key, client, expires)
+}
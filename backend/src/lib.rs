@@ -1,2 +1,31 @@
+pub mod alias;
+pub mod analytics;
+pub mod auth;
+pub mod base_url;
+pub mod click_limits;
+pub mod config;
 pub mod db;
+pub mod domain_policy;
+pub mod error;
+pub mod fallback;
+pub mod geoip;
 pub mod handlers;
+pub mod interstitial;
+pub mod models;
+pub mod openapi;
+pub mod pages;
+pub mod passthrough;
+pub mod password;
+pub mod preview;
+pub mod quotas;
+pub mod ratelimit;
+pub mod redirect;
+pub mod routes;
+pub mod schedule;
+pub mod self_links;
+pub mod state;
+pub mod targeting;
+pub mod utm;
+pub mod validation;
+pub mod variants;
+pub mod versioning;
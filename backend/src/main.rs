@@ -1,128 +1,58 @@
 use dotenv::dotenv;
-use handlers::with_db;
-use std::collections::HashMap;
-use warp::cors;
-use warp::Filter;
+use url_shortener::auth::Authenticator;
+use url_shortener::config::Config;
+use url_shortener::db;
+use url_shortener::domain_policy;
+use url_shortener::geoip::GeoIp;
+use url_shortener::interstitial::TokenSigner;
+use url_shortener::pages::PageTemplate;
+use url_shortener::routes;
+use url_shortener::state::AppState;
 
-mod db;
-mod handlers;
-
-use prometheus::{register_counter, register_histogram_vec, Encoder, TextEncoder};
 use std::net::SocketAddr;
 use std::sync::Arc;
-
-#[derive(Debug)]
-struct PrometheusErrorWrapper(prometheus::Error);
-impl warp::reject::Reject for PrometheusErrorWrapper {}
+use std::time::Duration;
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
-    let api_key = std::env::var("API_KEY").expect("API_KEY must be set");
+    let config = Config::from_env();
+    let authenticator = Authenticator::from_config(&config).expect("Invalid auth configuration");
     let database: db::Database = db::init_db().await;
-
-    let generate_url_counter = register_counter!(
-        "generate_url_requests_total",
-        "Total number of generate_url requests"
-    )
-    .unwrap();
-
-    let buckets = prometheus::linear_buckets(0.01, 0.05, 20).unwrap();
-    let generate_url_duration = register_histogram_vec!(
-        "http_request_duration_seconds",
-        "Request duration for generate_url",
-        &["endpoint"],
-        buckets
-    )
-    .unwrap();
-
-    let cors = cors()
-        .allow_any_origin()
-        .allow_methods(vec!["GET", "POST", "PUT", "DELETE", "OPTIONS"])
-        .allow_headers(vec!["Content-Type", "API-Key", "Authorization"])
-        .build();
-
-    // Route: /generate_url
-    let db1 = Arc::clone(&database);
-    let api_key_generate = api_key.clone();
-    let generate_url = warp::path("generate_url")
-        .and(warp::post())
-        .and(warp::header::<String>("API-Key"))
-        .and(warp::path::end())
-        .and(warp::body::json())
-        .and(with_db(db1))
-        .and_then(move |key, body: serde_json::Value, db| {
-            generate_url_counter.inc();
-            let histogram = generate_url_duration.with_label_values(&["generate_url"]);
-            let timer = histogram.start_timer();
-            let api_key = api_key_generate.to_string();
-            let fut = handlers::handle_generate_url(key, body, db, api_key);
-            async move {
-                let result = fut.await;
-                timer.observe_duration();
-                result
-            }
-        });
-
-    // Route: /custom_url
-    let db2 = Arc::clone(&database);
-    let api_key_custom = api_key.clone();
-    let custom_url = warp::path("custom_url")
-        .and(warp::post())
-        .and(warp::header::<String>("API-Key"))
-        .and(warp::path::end())
-        .and(warp::body::json())
-        .and(with_db(db2))
-        .and_then(move |key, body: serde_json::Value, db| {
-            let api_key = api_key_custom.to_string();
-            handlers::handle_custom_url(key, body, db, api_key)
-        })
-        .with(cors.clone());
-
-    // Route: /dns_resolver/:short_url
-    let db3 = Arc::clone(&database);
-    let redirect_route = warp::path!("dns_resolver" / String)
-        .and(with_db(db3))
-        .and_then(|short_url: String, db| {
-            let mut map = HashMap::new();
-            map.insert("short_url".to_string(), short_url);
-            handlers::handle_redirect_url(map, db)
-        })
-        .with(cors.clone());
-
-    // Route: /ping
-    let ping = warp::path("ping").map(|| warp::reply::json(&"pong"));
-
-    // Route: /metrics
-    let metrics = warp::path("metrics").and_then(|| async move {
-        let encoder = TextEncoder::new();
-        let metric_families = prometheus::gather();
-        let mut buffer = Vec::new();
-        match encoder.encode(&metric_families, &mut buffer) {
-            Ok(_) => {
-                let output = String::from_utf8(buffer.clone()).unwrap();
-                Ok::<_, warp::Rejection>(warp::reply::with_header(
-                    output,
-                    "Content-Type",
-                    encoder.format_type(),
-                ))
-            }
-            Err(e) => Err(warp::reject::custom(PrometheusErrorWrapper(e))),
+    let geoip = config
+        .geoip_database
+        .as_deref()
+        .map(|path| GeoIp::open(path).expect("Invalid GeoIP database"));
+    let error_page = config
+        .error_page_template
+        .as_deref()
+        .map(|path| PageTemplate::load(path).expect("Invalid error page template"))
+        .unwrap_or_default();
+    let signer = match &config.interstitial_secret_file {
+        Some(path) => TokenSigner::load(path, config.interstitial_token_ttl)
+            .expect("Invalid interstitial secret file"),
+        None => {
+            println!("⚠️ INTERSTITIAL_SECRET_FILE is not set, continue tokens only work on this instance");
+            TokenSigner::random(config.interstitial_token_ttl)
         }
-    });
-
-    let api_routes = generate_url
-        .or(custom_url)
-        .or(ping)
-        .or(metrics)
-        .with(cors.clone());
-
-    let routes = api_routes.or(redirect_route);
+    };
+    let state = AppState::new(database, config, authenticator)
+        .with_geoip(geoip)
+        .with_error_page(error_page)
+        .with_interstitial_signer(signer);
+    state
+        .domain_policy
+        .reload()
+        .expect("Invalid domain policy files");
+    tokio::spawn(domain_policy::watch(
+        Arc::clone(&state.domain_policy),
+        Duration::from_secs(state.config.domain_policy_reload_secs.max(1)),
+    ));
 
     let socket_addr: SocketAddr = "0.0.0.0:8000"
         .parse()
         .expect("Failed to parse socket address");
 
     println!("Server starting, listening on {}", socket_addr);
-    warp::serve(routes).run(socket_addr).await;
+    warp::serve(routes::filter(state)).run(socket_addr).await;
 }
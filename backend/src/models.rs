@@ -0,0 +1,316 @@
+use crate::error::{ApiError, FieldError};
+use crate::interstitial::Interstitial;
+use crate::passthrough::Passthrough;
+use crate::redirect::RedirectType;
+use crate::targeting::DestinationRule;
+use crate::utm::UtmTemplate;
+use crate::variants::{SplitMode, Variant};
+use serde::de::{DeserializeOwned, Deserializer, Error as _};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::{IntoParams, ToSchema};
+
+/// Field level checks run after a request body is deserialized.
+pub trait Validate {
+    fn validate(&self) -> Vec<FieldError>;
+
+    /// Fields of the body the request does not know, if it collects them.
+    fn unknown_fields(&self) -> Vec<&str> {
+        Vec::new()
+    }
+}
+
+/// What to do with fields a request body does not know.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownFields {
+    /// Report them under `FIELD_UNKNOWN`, as `/api/v1` does.
+    Reject,
+    /// Drop them, so old clients of the legacy routes keep working.
+    Ignore,
+}
+
+/// Options shared by every way of creating a link.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct LinkOptions {
+    /// Absolute destination URL.
+    #[serde(default)]
+    #[schema(required = true, example = "https://example.com/some/long/path")]
+    pub long_url: String,
+    /// Short domain to create the link on; defaults to the caller's default domain.
+    #[serde(default)]
+    #[schema(example = "go.example.com")]
+    pub domain: Option<String>,
+    /// Redirect status: 301, 302, 307 or 308; defaults to the server's `DEFAULT_REDIRECT_TYPE`.
+    #[serde(default)]
+    #[schema(value_type = Option<u16>, example = 301)]
+    pub redirect_type: Option<RedirectType>,
+    /// Forward the visitor's query string and trailing path to the destination.
+    #[serde(default)]
+    pub passthrough: Passthrough,
+    /// UTM parameters added to the destination when the link is resolved.
+    #[serde(default)]
+    pub utm: Option<UtmTemplate>,
+    /// One of the caller's campaigns, whose UTM template fills the fields `utm` leaves unset.
+    #[serde(default)]
+    #[schema(example = "spring-sale")]
+    pub campaign: Option<String>,
+    /// Destinations for specific platforms, devices, languages or countries; the first match wins.
+    #[serde(default)]
+    pub rules: Vec<DestinationRule>,
+    /// Weighted destinations to split visitors between instead of `long_url`.
+    #[serde(default)]
+    pub variants: Vec<Variant>,
+    /// Whether visitors keep their variant across visits (`sticky`) or draw on each (`random`).
+    #[serde(default)]
+    pub split: SplitMode,
+    /// RFC 3339 time the link starts redirecting.
+    #[serde(default)]
+    #[schema(example = "2030-03-01T09:00:00+01:00")]
+    pub not_before: Option<String>,
+    /// RFC 3339 time the link stops redirecting.
+    #[serde(default)]
+    #[schema(example = "2030-03-31T23:59:59+01:00")]
+    pub not_after: Option<String>,
+    /// Destination outside the `not_before`..`not_after` window, instead of an error.
+    #[serde(default)]
+    #[schema(example = "https://example.com/campaign-over")]
+    pub inactive_url: Option<String>,
+    /// Destination once the link has expired, instead of the domain's fallback or an error.
+    #[serde(default)]
+    #[schema(example = "https://example.com/offer-ended")]
+    pub expired_url: Option<String>,
+    /// Successful redirects after which the link stops working, from 1 to 1000000.
+    #[serde(default)]
+    #[schema(example = 1)]
+    pub max_clicks: Option<u32>,
+    /// Password visitors must enter before being redirected; stored only as a hash.
+    #[serde(default)]
+    #[schema(write_only, example = "correct horse")]
+    pub password: Option<String>,
+    /// Warning page shown before redirecting; overrides `INTERSTITIAL_DOMAINS` unless reported.
+    #[serde(default)]
+    pub interstitial: Option<Interstitial>,
+}
+
+impl Validate for LinkOptions {
+    fn validate(&self) -> Vec<FieldError> {
+        required("long_url", &self.long_url).into_iter().collect()
+    }
+}
+
+/// Body of `POST /generate_url`.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct CreateLinkRequest {
+    #[serde(flatten, deserialize_with = "flattened")]
+    pub options: LinkOptions,
+    /// Fields unknown to this version: rejected on `/api/v1`, ignored on the legacy routes.
+    #[serde(flatten)]
+    pub unknown: HashMap<String, serde_json::Value>,
+}
+
+impl Validate for CreateLinkRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        self.options.validate()
+    }
+
+    fn unknown_fields(&self) -> Vec<&str> {
+        sorted_keys(&self.unknown)
+    }
+}
+
+/// Body of `POST /custom_url`.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct CustomLinkRequest {
+    /// Alias used as the short code.
+    #[serde(default)]
+    #[schema(required = true, example = "spring-sale")]
+    pub custom_short: String,
+    #[serde(flatten, deserialize_with = "flattened")]
+    pub options: LinkOptions,
+    /// Fields unknown to this version: rejected on `/api/v1`, ignored on the legacy routes.
+    #[serde(flatten)]
+    pub unknown: HashMap<String, serde_json::Value>,
+}
+
+impl Validate for CustomLinkRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = self.options.validate();
+        errors.extend(required("custom_short", &self.custom_short));
+        errors
+    }
+
+    fn unknown_fields(&self) -> Vec<&str> {
+        sorted_keys(&self.unknown)
+    }
+}
+
+/// Query of `GET /api/v1/links/{code}` and `GET /api/v1/links/{code}/stats`.
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LinkQuery {
+    /// Short domain of the link; defaults to the caller's default domain.
+    pub domain: Option<String>,
+}
+
+/// Reply to a successful link creation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CreateLinkResponse {
+    #[schema(example = "success")]
+    pub status: String,
+    pub short_url: String,
+}
+
+impl CreateLinkResponse {
+    pub fn success(short_url: String) -> Self {
+        CreateLinkResponse {
+            status: "success".to_string(),
+            short_url,
+        }
+    }
+}
+
+/// Reply to `GET /api/v1/links/{code}`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LinkDetails {
+    #[schema(example = "http://rustyshortener/spring-sale")]
+    pub short_url: String,
+    #[schema(example = "https://example.com/spring-sale")]
+    pub long_url: String,
+    #[schema(example = "rustyshortener")]
+    pub domain: String,
+    /// RFC 3339 creation time.
+    pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub not_before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub not_after: Option<String>,
+    /// Successful redirects allowed in total; absent on unlimited links.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_clicks: Option<u32>,
+    /// Successful redirects left before the link stops working; absent on unlimited links.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_clicks: Option<u64>,
+    /// Visitors must enter a password.
+    pub password_protected: bool,
+    /// Warning page set on the link itself.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interstitial: Option<Interstitial>,
+}
+
+/// Form posted to a password-protected link.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct PasswordForm {
+    #[serde(default)]
+    pub password: String,
+}
+
+fn required(field: &str, value: &str) -> Option<FieldError> {
+    value
+        .trim()
+        .is_empty()
+        .then(|| FieldError::new(field, "FIELD_REQUIRED", format!("{} is required", field)))
+}
+
+/// Deserialize and validate a JSON request body.
+///
+/// Malformed JSON is an `INVALID_JSON` error; wrong types, unknown fields and failed
+/// [`Validate`] checks are reported per field under `VALIDATION_FAILED`.
+pub fn parse_body<T: DeserializeOwned + Validate>(body: &[u8]) -> Result<T, ApiError> {
+    parse_body_with(body, UnknownFields::Reject)
+}
+
+/// [`parse_body`], choosing what to do with the fields a request collects as unknown.
+pub fn parse_body_with<T: DeserializeOwned + Validate>(
+    body: &[u8],
+    unknown: UnknownFields,
+) -> Result<T, ApiError> {
+    let mut deserializer = serde_json::Deserializer::from_slice(body);
+    let request: T = serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        let inner = e.into_inner();
+        if !inner.is_data() {
+            return ApiError::new(
+                warp::http::StatusCode::BAD_REQUEST,
+                "INVALID_JSON",
+                format!("The request body is not valid JSON: {}", inner),
+            );
+        }
+        let message = strip_position(&inner);
+        let (path, message) = match flattened_error(&message) {
+            Some((path, message)) => (path.to_string(), message.to_string()),
+            None => (path, message),
+        };
+        let (field, code) = match unknown_field(&message) {
+            Some(field) => (field, "FIELD_UNKNOWN"),
+            None if path == "." => ("body".to_string(), "INVALID_TYPE"),
+            None => (path, "INVALID_TYPE"),
+        };
+        ApiError::validation(vec![FieldError::new(&field, code, message)])
+    })?;
+    deserializer.end().map_err(|e| {
+        ApiError::new(
+            warp::http::StatusCode::BAD_REQUEST,
+            "INVALID_JSON",
+            format!("The request body is not valid JSON: {}", e),
+        )
+    })?;
+
+    if unknown == UnknownFields::Reject {
+        let errors: Vec<FieldError> = request
+            .unknown_fields()
+            .into_iter()
+            .map(|field| {
+                FieldError::new(field, "FIELD_UNKNOWN", format!("unknown field `{}`", field))
+            })
+            .collect();
+        if !errors.is_empty() {
+            return Err(ApiError::validation(errors));
+        }
+    }
+    let errors = request.validate();
+    if !errors.is_empty() {
+        return Err(ApiError::validation(errors));
+    }
+    Ok(request)
+}
+
+/// Deserialize a `#[serde(flatten)]` field, keeping the path of its errors.
+///
+/// serde reports errors inside flattened fields without their path, so it is put in the
+/// message and read back by [`flattened_error`].
+fn flattened<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        D::Error::custom(format!("at `{}`: {}", path, e.into_inner()))
+    })
+}
+
+/// The path and message of an error from [`flattened`].
+fn flattened_error(message: &str) -> Option<(&str, &str)> {
+    message.strip_prefix("at `")?.split_once("`: ")
+}
+
+fn sorted_keys(map: &HashMap<String, serde_json::Value>) -> Vec<&str> {
+    let mut keys: Vec<&str> = map.keys().map(String::as_str).collect();
+    keys.sort_unstable();
+    keys
+}
+
+/// The field named by serde's "unknown field `x`" message.
+fn unknown_field(message: &str) -> Option<String> {
+    let rest = message.strip_prefix("unknown field `")?;
+    rest.split('`').next().map(str::to_string)
+}
+
+/// Drop the "at line 1 column 5" suffix serde_json appends to its messages.
+fn strip_position(error: &serde_json::Error) -> String {
+    let message = error.to_string();
+    match message.rfind(" at line ") {
+        Some(index) => message[..index].to_string(),
+        None => message,
+    }
+}
@@ -0,0 +1,206 @@
+use crate::analytics::ClickStats;
+use crate::error::{FieldError, ProblemDetails};
+use crate::handlers;
+use crate::interstitial::Interstitial;
+use crate::models::{
+    CreateLinkRequest, CreateLinkResponse, CustomLinkRequest, LinkDetails, LinkOptions,
+    PasswordForm,
+};
+use crate::passthrough::{Passthrough, QueryConflict};
+use crate::quotas::{Quota, Usage};
+use crate::routes::{self, ENDPOINTS};
+use crate::targeting::{DestinationRule, Device, Platform};
+use crate::utm::UtmTemplate;
+use crate::variants::{SplitMode, Variant};
+use once_cell::sync::Lazy;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{Deprecated, PathItemType};
+use utoipa::{Modify, OpenApi};
+
+/// The OpenAPI 3 document, built from the handler annotations and the request/response models.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Rusty Shortener API",
+        description = "Create short links and resolve them. Every error is an RFC 7807 problem document."
+    ),
+    paths(
+        handlers::handle_generate_url,
+        handlers::handle_custom_url,
+        handlers::handle_link_details,
+        handlers::handle_link_stats,
+        handlers::handle_redirect_url,
+        handlers::handle_unlock_url,
+        handlers::handle_preview_url,
+        handlers::handle_usage,
+        handlers::handle_ping,
+        handlers::handle_metrics,
+        handle_openapi,
+        handle_docs,
+    ),
+    components(schemas(
+        LinkOptions,
+        CreateLinkRequest,
+        CustomLinkRequest,
+        CreateLinkResponse,
+        LinkDetails,
+        PasswordForm,
+        Interstitial,
+        Passthrough,
+        QueryConflict,
+        UtmTemplate,
+        DestinationRule,
+        Platform,
+        Device,
+        Variant,
+        SplitMode,
+        ClickStats,
+        Usage,
+        Quota,
+        ProblemDetails,
+        FieldError,
+    )),
+    modifiers(&SecuritySchemes, &LegacyRoutes),
+    tags(
+        (name = "links", description = "Link creation and usage"),
+        (name = "redirect", description = "Short link resolution"),
+        (name = "operations", description = "Health, metrics and documentation"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Registers the API key, bearer token and HMAC signature schemes.
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("API-Key"))),
+        );
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "signature",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                "X-Signature",
+                "HMAC-SHA256 of the request, sent with X-Key-Id, X-Timestamp and X-Nonce",
+            ))),
+        );
+    }
+}
+
+/// Documents the deprecated unversioned routes as copies of their successors.
+struct LegacyRoutes;
+
+impl Modify for LegacyRoutes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let paths = &mut openapi.paths.paths;
+        for endpoint in ENDPOINTS.iter().filter(|e| e.deprecated) {
+            let mut item = match endpoint.successor {
+                Some(successor) => match paths.get(successor) {
+                    Some(item) => item.clone(),
+                    None => continue,
+                },
+                None => match paths.remove(endpoint.path) {
+                    Some(item) => item,
+                    None => continue,
+                },
+            };
+            // The successor may take more methods than the legacy route, e.g. `POST /{short_url}`
+            item.operations
+                .retain(|kind, _| method_name(kind) == endpoint.method);
+            for operation in item.operations.values_mut() {
+                operation.deprecated = Some(Deprecated::True);
+                if endpoint.successor.is_some() {
+                    operation.operation_id = operation
+                        .operation_id
+                        .as_ref()
+                        .map(|id| format!("{}_legacy", id));
+                }
+            }
+            // Legacy routes taking several methods, e.g. `/dns_resolver/{short_url}`, share a path
+            match paths.get_mut(endpoint.path) {
+                Some(existing) => existing.operations.extend(item.operations),
+                None => {
+                    paths.insert(endpoint.path.to_string(), item);
+                }
+            }
+        }
+    }
+}
+
+/// The method of a path item operation, as written in [`ENDPOINTS`].
+fn method_name(kind: &PathItemType) -> &'static str {
+    match kind {
+        PathItemType::Get => "GET",
+        PathItemType::Post => "POST",
+        PathItemType::Put => "PUT",
+        PathItemType::Delete => "DELETE",
+        PathItemType::Options => "OPTIONS",
+        PathItemType::Head => "HEAD",
+        PathItemType::Patch => "PATCH",
+        PathItemType::Trace => "TRACE",
+        PathItemType::Connect => "CONNECT",
+    }
+}
+
+static SPEC_JSON: Lazy<String> = Lazy::new(|| {
+    ApiDoc::openapi()
+        .to_pretty_json()
+        .expect("OpenAPI document serializes")
+});
+
+/// The OpenAPI document describing this service.
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    tag = "operations",
+    responses((status = 200, description = "OpenAPI 3 document", content_type = "application/json"))
+)]
+pub async fn handle_openapi() -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::with_header(
+        SPEC_JSON.as_str(),
+        "Content-Type",
+        "application/json",
+    ))
+}
+
+/// Interactive API documentation rendered with Redoc.
+#[utoipa::path(
+    get,
+    path = "/docs",
+    tag = "operations",
+    responses((status = 200, description = "HTML documentation page", body = String, content_type = "text/html"))
+)]
+pub async fn handle_docs() -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::html(docs_page()))
+}
+
+/// Redoc page loading the spec from `/openapi.json`.
+pub fn docs_page() -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Rusty Shortener API</title>
+</head>
+<body>
+<redoc spec-url="/{spec}"></redoc>
+<script src="https://cdn.redoc.ly/redoc/latest/bundles/redoc.standalone.js"></script>
+</body>
+</html>
+"#,
+        spec = routes::OPENAPI
+    )
+}
@@ -0,0 +1,247 @@
+use crate::interstitial::{self, Interstitial};
+use crate::preview::LinkPreview;
+use chrono::{DateTime, FixedOffset};
+use std::path::Path;
+use warp::http::StatusCode;
+use warp::Reply;
+
+/// Escape text for inclusion in HTML element content or attribute values.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Render a minimal standalone page; `title` and `message` are escaped.
+pub fn render_page(title: &str, message: &str) -> String {
+    render_document(title, &format!("<p>{}</p>", escape_html(message)))
+}
+
+/// Render the form asking for the password of a protected link, with an optional error.
+///
+/// The form posts back to the URL it was served from, so passthrough paths and queries survive.
+pub fn render_password_form(error: Option<&str>) -> String {
+    let error = error.map_or(String::new(), |error| {
+        format!("<p class=\"error\">{}</p>\n", escape_html(error))
+    });
+    render_document(
+        "This link is password protected",
+        &format!(
+            r#"{error}<form method="post">
+<label for="password">Password</label>
+<input id="password" name="password" type="password" autocomplete="current-password" required autofocus>
+<button type="submit">Continue</button>
+</form>"#
+        ),
+    )
+}
+
+/// Render the preview of a link: where it goes, when it works and any policy warning.
+pub fn render_preview(preview: &LinkPreview) -> String {
+    let time = |time: &DateTime<FixedOffset>| {
+        format!(
+            r#"<time datetime="{}">{}</time>"#,
+            time.to_rfc3339(),
+            time.format("%Y-%m-%d %H:%M %:z")
+        )
+    };
+    let mut body = String::new();
+    if let Some(warning) = &preview.warning {
+        body.push_str(&format!(
+            "<p class=\"error\"><strong>Warning:</strong> {}</p>\n",
+            escape_html(warning)
+        ));
+    }
+    let destination = match &preview.destination {
+        Some(url) => format!("<code>{}</code>", escape_html(url)),
+        None => "Hidden until the link's password is entered".to_string(),
+    };
+    body.push_str(&format!(
+        "<dl>\n<dt>Short link</dt><dd><code>{}</code></dd>\n<dt>Destination</dt><dd>{}</dd>\n",
+        escape_html(&preview.short_url),
+        destination
+    ));
+    if preview.varies {
+        body.push_str("<dd>Some visitors are sent to a different destination.</dd>\n");
+    }
+    body.push_str(&format!(
+        "<dt>Created</dt><dd>{}</dd>\n",
+        time(&preview.created_at)
+    ));
+    if let Some(not_before) = &preview.not_before {
+        body.push_str(&format!("<dt>Opens</dt><dd>{}</dd>\n", time(not_before)));
+    }
+    if let Some(not_after) = &preview.not_after {
+        body.push_str(&format!("<dt>Closes</dt><dd>{}</dd>\n", time(not_after)));
+    }
+    body.push_str(&format!(
+        "<dt>Expires</dt><dd>{}</dd>\n</dl>\n<p><a href=\"{}\" rel=\"nofollow\">Continue to the link</a></p>",
+        time(&preview.expires_at),
+        escape_html(&preview.short_url)
+    ));
+    render_document("Link preview", &body)
+}
+
+/// Render the warning shown before sending a visitor on to `destination`.
+///
+/// The continue button links to `continue_url`, which carries the signed continue token.
+pub fn render_interstitial(kind: Interstitial, destination: &str, continue_url: &str) -> String {
+    let class = match kind {
+        Interstitial::Reported => " class=\"error\"",
+        _ => "",
+    };
+    render_document(
+        kind.title(),
+        &format!(
+            r#"<p{class}>{message}</p>
+<p><code>{destination}</code></p>
+<p><a class="button" href="{continue_url}" rel="nofollow noreferrer">Continue</a></p>"#,
+            message = escape_html(&kind.message(&interstitial::host(destination))),
+            destination = escape_html(destination),
+            continue_url = escape_html(continue_url),
+        ),
+    )
+}
+
+/// Wrap `body`, which must already be escaped, in the shared page layout.
+fn render_document(title: &str, body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="robots" content="noindex">
+<title>{title}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; max-width: 36rem; margin: 4rem auto; padding: 0 1rem; color: #222; }}
+h1 {{ font-size: 1.5rem; }}
+.error {{ color: #b00020; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+{body}
+</body>
+</html>
+"#,
+        title = escape_html(title),
+    )
+}
+
+/// A customizable HTML layout for error pages, with `{{title}}`, `{{message}}` and
+/// `{{status}}` placeholders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageTemplate {
+    source: String,
+}
+
+impl Default for PageTemplate {
+    fn default() -> Self {
+        PageTemplate {
+            source: render_document("{{title}}", "<p>{{message}}</p>"),
+        }
+    }
+}
+
+impl PageTemplate {
+    /// Use `source` as the layout; it must show the `{{message}}`.
+    pub fn new(source: String) -> Result<PageTemplate, String> {
+        if !source.contains("{{message}}") {
+            return Err("the page template has no {{message}} placeholder".to_string());
+        }
+        Ok(PageTemplate { source })
+    }
+
+    /// Read the layout from the file at `path`.
+    pub fn load(path: &Path) -> Result<PageTemplate, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        PageTemplate::new(source).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Fill in the placeholders; `title` and `message` are escaped.
+    pub fn render(&self, status: StatusCode, title: &str, message: &str) -> String {
+        self.source
+            .replace("{{status}}", status.as_str())
+            .replace("{{title}}", &escape_html(title))
+            .replace("{{message}}", &escape_html(message))
+    }
+}
+
+/// Whether a client sending `accept` prefers an HTML page to a JSON error.
+///
+/// Browsers list `text/html` first; API clients and `*/*` get JSON.
+pub fn prefers_html(accept: &str) -> bool {
+    let quality = |media_type: &str| {
+        let (kind, _) = media_type.split_once('/').unwrap_or((media_type, ""));
+        accept
+            .split(',')
+            .filter_map(|range| {
+                let mut params = range.split(';');
+                let range = params.next()?.trim().to_ascii_lowercase();
+                let specificity = if range == media_type {
+                    2
+                } else if range == format!("{}/*", kind) {
+                    1
+                } else if range == "*/*" {
+                    0
+                } else {
+                    return None;
+                };
+                let q = params
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+                Some((specificity, q))
+            })
+            .max_by_key(|(specificity, _)| *specificity)
+            .map_or(0.0, |(_, q)| q)
+    };
+    let json = quality("application/json").max(quality("application/problem+json"));
+    quality("text/html") > json
+}
+
+/// An HTML page reply with the given status.
+pub fn page_reply(
+    status: StatusCode,
+    title: &str,
+    message: &str,
+) -> warp::reply::WithStatus<warp::reply::Html<String>> {
+    warp::reply::with_status(warp::reply::html(render_page(title, message)), status)
+}
+
+/// The interstitial reply; never cached, as its continue token is short-lived.
+pub fn interstitial_reply(
+    kind: Interstitial,
+    destination: &str,
+    continue_url: &str,
+) -> warp::reply::Response {
+    let page = render_interstitial(kind, destination, continue_url);
+    let mut response = warp::reply::html(page).into_response();
+    response.headers_mut().insert(
+        warp::http::header::CACHE_CONTROL,
+        warp::http::HeaderValue::from_static("no-store"),
+    );
+    response
+}
+
+/// The password form reply with the given status; never cached, as it may carry an error.
+pub fn password_form_reply(status: StatusCode, error: Option<&str>) -> warp::reply::Response {
+    let mut response =
+        warp::reply::with_status(warp::reply::html(render_password_form(error)), status)
+            .into_response();
+    response.headers_mut().insert(
+        warp::http::header::CACHE_CONTROL,
+        warp::http::HeaderValue::from_static("no-store"),
+    );
+    response
+}
@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
+use url::Url;
+use utoipa::ToSchema;
+
+/// Which value wins when the visitor's query string and the destination share a parameter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum QueryConflict {
+    /// The destination's own value is kept and the visitor's is dropped.
+    #[default]
+    Keep,
+    /// The visitor's value replaces the destination's.
+    Override,
+    /// Both values are sent, the destination's first.
+    Append,
+}
+
+/// What a link forwards from the request to its destination.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Passthrough {
+    /// Merge the visitor's query string into the destination's.
+    pub query: bool,
+    /// Append path segments after the code (`/{code}/docs/intro`) to the destination's path.
+    pub path: bool,
+    /// How clashing query parameters are merged.
+    pub query_conflict: QueryConflict,
+}
+
+impl Passthrough {
+    pub fn is_enabled(&self) -> bool {
+        self.query || self.path
+    }
+
+    /// The destination for a visit with the trailing path `rest` and the raw `query`.
+    ///
+    /// Returns `None` when the visit carries a path the link does not forward, or one that
+    /// would climb out of the destination's path with `.` or `..` segments.
+    pub fn apply(&self, destination: &str, rest: &str, query: &str) -> Option<String> {
+        let rest = rest.trim_matches('/');
+        if rest.is_empty() && (query.is_empty() || !self.query) {
+            return Some(destination.to_string());
+        }
+        if !rest.is_empty() && !self.path {
+            return None;
+        }
+
+        let mut url = Url::parse(destination).ok()?;
+        if !rest.is_empty() {
+            if rest.split('/').any(is_dot_segment) {
+                return None;
+            }
+            let path = format!("{}/{}", url.path().trim_end_matches('/'), rest);
+            url.set_path(&path);
+        }
+        if self.query && !query.is_empty() {
+            let merged = merge_query(url.query().unwrap_or_default(), query, self.query_conflict);
+            url.set_query((!merged.is_empty()).then_some(merged.as_str()));
+        }
+        Some(url.to_string())
+    }
+}
+
+/// `.` and `..`, including their percent-encoded spellings.
+fn is_dot_segment(segment: &str) -> bool {
+    let decoded = segment.to_ascii_lowercase().replace("%2e", ".");
+    decoded == "." || decoded == ".."
+}
+
+/// Merge the visitor's query string into the destination's according to `conflict`.
+///
+/// The destination's values win unless `conflict` is [`QueryConflict::Override`] or
+/// [`QueryConflict::Append`].
+pub fn merge_query(destination: &str, incoming: &str, conflict: QueryConflict) -> String {
+    let own: Vec<(String, String)> = form_urlencoded::parse(destination.as_bytes())
+        .into_owned()
+        .collect();
+    let visitor: Vec<(String, String)> = form_urlencoded::parse(incoming.as_bytes())
+        .into_owned()
+        .collect();
+    let has_key = |pairs: &[(String, String)], key: &str| pairs.iter().any(|(k, _)| k == key);
+
+    let pairs: Vec<&(String, String)> = match conflict {
+        QueryConflict::Keep => own
+            .iter()
+            .chain(visitor.iter().filter(|(key, _)| !has_key(&own, key)))
+            .collect(),
+        QueryConflict::Override => own
+            .iter()
+            .filter(|(key, _)| !has_key(&visitor, key))
+            .chain(visitor.iter())
+            .collect(),
+        QueryConflict::Append => own.iter().chain(visitor.iter()).collect(),
+    };
+
+    form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(pairs)
+        .finish()
+}
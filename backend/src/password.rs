@@ -0,0 +1,87 @@
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{digest, pbkdf2};
+use std::num::NonZeroU32;
+
+/// PBKDF2-HMAC-SHA256 rounds for new hashes.
+pub const DEFAULT_ITERATIONS: u32 = 600_000;
+
+/// Shortest password a link may be protected with.
+pub const MIN_LENGTH: usize = 4;
+
+/// Longest password accepted, so hashing cost stays bounded.
+pub const MAX_LENGTH: usize = 128;
+
+const SCHEME: &str = "pbkdf2-sha256";
+const SALT_LEN: usize = 16;
+const HASH_LEN: usize = digest::SHA256_OUTPUT_LEN;
+
+/// Check a password requested for a new link.
+pub fn check(password: &str) -> Result<(), String> {
+    let length = password.chars().count();
+    if (MIN_LENGTH..=MAX_LENGTH).contains(&length) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Passwords are {} to {} characters long",
+            MIN_LENGTH, MAX_LENGTH
+        ))
+    }
+}
+
+/// Hash `password` for storage with [`DEFAULT_ITERATIONS`].
+pub fn hash(password: &str) -> String {
+    hash_with(password, DEFAULT_ITERATIONS)
+}
+
+/// Hash `password` as `pbkdf2-sha256$<iterations>$<salt hex>$<hash hex>`.
+pub fn hash_with(password: &str, iterations: u32) -> String {
+    let iterations = NonZeroU32::new(iterations.max(1)).unwrap();
+    let mut salt = [0u8; SALT_LEN];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .expect("system random generator failed");
+    let mut hash = [0u8; HASH_LEN];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        &salt,
+        password.as_bytes(),
+        &mut hash,
+    );
+    format!(
+        "{}${}${}${}",
+        SCHEME,
+        iterations,
+        hex::encode(salt),
+        hex::encode(hash)
+    )
+}
+
+/// Whether `password` matches a hash made by [`hash_with`], compared in constant time.
+pub fn verify(password: &str, stored: &str) -> bool {
+    let mut parts = stored.split('$');
+    let (Some(SCHEME), Some(iterations), Some(salt), Some(hash), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return false;
+    };
+    let (Some(iterations), Ok(salt), Ok(hash)) = (
+        iterations.parse().ok().and_then(NonZeroU32::new),
+        hex::decode(salt),
+        hex::decode(hash),
+    ) else {
+        return false;
+    };
+    pbkdf2::verify(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        &salt,
+        password.as_bytes(),
+        &hash,
+    )
+    .is_ok()
+}
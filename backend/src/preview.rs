@@ -0,0 +1,80 @@
+use crate::domain_policy::DomainPolicy;
+use crate::interstitial::{self, DomainInterstitial, Interstitial};
+use chrono::{DateTime, FixedOffset};
+use std::collections::HashMap;
+
+/// Appended to a code, e.g. `/abc1234+`, shows the link's preview.
+pub const SUFFIX: char = '+';
+
+/// Trailing segment showing the link's preview, e.g. `/abc1234/preview`.
+pub const SEGMENT: &str = "preview";
+
+/// The code whose preview is asked for by visiting `code` followed by `path`, if any.
+pub fn preview_code<'a>(code: &'a str, path: &str) -> Option<&'a str> {
+    if path.trim_end_matches('/') == SEGMENT {
+        return Some(code);
+    }
+    code.strip_suffix(SUFFIX)
+        .filter(|code| !code.is_empty() && path.is_empty())
+}
+
+/// Whether the `short_url` and `path` of redirect `params` ask for a preview.
+pub fn is_preview(params: &HashMap<String, String>) -> bool {
+    let get = |name: &str| params.get(name).map(String::as_str).unwrap_or_default();
+    preview_code(get("short_url"), get("path")).is_some()
+}
+
+/// Why a link whose visitors may go to `destinations` is flagged: one of them is denied by
+/// `policy` or on a domain `reported` by the interstitial rules.
+///
+/// The warning of a `protected` link does not name the host, which would give its
+/// destination away.
+pub fn warning(
+    policy: &DomainPolicy,
+    reported: &[DomainInterstitial],
+    destinations: &[&str],
+    protected: bool,
+) -> Option<String> {
+    if let Some((host, denied)) = destinations
+        .iter()
+        .find_map(|url| policy.check_url(url).err())
+    {
+        if protected {
+            return Some(
+                "This link leads to a site that is not permitted by our link policy.".into(),
+            );
+        }
+        return Some(format!(
+            "This link leads to {}, which is not permitted by our link policy. {}",
+            host,
+            denied.message(&host)
+        ));
+    }
+    let url = destinations
+        .iter()
+        .find(|url| interstitial::select(reported, None, url) == Some(Interstitial::Reported))?;
+    if protected {
+        return Some(
+            "This link leads to a site that has been reported as unsafe. \
+             Only continue if you trust it."
+                .into(),
+        );
+    }
+    Some(Interstitial::Reported.message(&interstitial::host(url)))
+}
+
+/// What the preview page tells a visitor about a link before they follow it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkPreview {
+    pub short_url: String,
+    /// Where the link goes; `None` when a password protects it.
+    pub destination: Option<String>,
+    /// Some visitors are sent elsewhere by rules or split variants.
+    pub varies: bool,
+    pub created_at: DateTime<FixedOffset>,
+    pub expires_at: DateTime<FixedOffset>,
+    pub not_before: Option<DateTime<FixedOffset>>,
+    pub not_after: Option<DateTime<FixedOffset>>,
+    /// Why one of the link's destinations is flagged by the domain policy.
+    pub warning: Option<String>,
+}
@@ -0,0 +1,286 @@
+use crate::db::Database;
+use once_cell::sync::Lazy;
+use prometheus::{register_int_counter_vec, register_int_gauge_vec, IntCounterVec, IntGaugeVec};
+use redis::RedisResult;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Monthly counters outlive the longest month so the final count stays readable.
+const MONTHLY_COUNTER_TTL: u64 = 32 * 24 * 60 * 60;
+
+/// Check every limit and record the new link in one step.
+///
+/// KEYS: active links zset, custom aliases zset, monthly creation counter.
+/// ARGV: now, link expiry, member, is custom (0/1), max active, max aliases, max monthly,
+/// counter ttl. Limits below zero are unlimited.
+/// Returns `{status, used}` where status 0 is success and 1-3 name the exhausted quota.
+const RESERVE_SCRIPT: &str = r#"
+local now = tonumber(ARGV[1])
+local is_custom = ARGV[4] == '1'
+local max_active = tonumber(ARGV[5])
+local max_aliases = tonumber(ARGV[6])
+local max_monthly = tonumber(ARGV[7])
+redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', now)
+redis.call('ZREMRANGEBYSCORE', KEYS[2], '-inf', now)
+local active = redis.call('ZCARD', KEYS[1])
+if max_active >= 0 and active >= max_active then
+  return {1, active}
+end
+if is_custom then
+  local aliases = redis.call('ZCARD', KEYS[2])
+  if max_aliases >= 0 and aliases >= max_aliases then
+    return {2, aliases}
+  end
+end
+local monthly = tonumber(redis.call('GET', KEYS[3]) or '0')
+if max_monthly >= 0 and monthly >= max_monthly then
+  return {3, monthly}
+end
+redis.call('ZADD', KEYS[1], ARGV[2], ARGV[3])
+if is_custom then
+  redis.call('ZADD', KEYS[2], ARGV[2], ARGV[3])
+end
+redis.call('INCR', KEYS[3])
+redis.call('EXPIRE', KEYS[3], ARGV[8])
+return {0, 0}
+"#;
+
+static ACTIVE_LINKS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "quota_active_links",
+        "Live links held by each owner",
+        &["owner"]
+    )
+    .unwrap()
+});
+
+static CUSTOM_ALIASES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "quota_custom_aliases",
+        "Live custom aliases held by each owner",
+        &["owner"]
+    )
+    .unwrap()
+});
+
+static MONTHLY_CREATIONS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "quota_monthly_creations",
+        "Links created by each owner in the current month",
+        &["owner"]
+    )
+    .unwrap()
+});
+
+static RESERVATIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "quota_reservations_total",
+        "Link creations checked against quotas, by outcome: reserved or the exhausted quota",
+        &["outcome"]
+    )
+    .unwrap()
+});
+
+/// Caps on what a single owner may hold; `None` is unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Quota {
+    #[serde(default)]
+    pub max_active_links: Option<u64>,
+    #[serde(default)]
+    pub max_custom_aliases: Option<u64>,
+    #[serde(default)]
+    pub monthly_creations: Option<u64>,
+}
+
+impl Quota {
+    /// Fill the limits left unset by `self` from `defaults`.
+    pub fn or(self, defaults: Quota) -> Quota {
+        Quota {
+            max_active_links: self.max_active_links.or(defaults.max_active_links),
+            max_custom_aliases: self.max_custom_aliases.or(defaults.max_custom_aliases),
+            monthly_creations: self.monthly_creations.or(defaults.monthly_creations),
+        }
+    }
+}
+
+/// The quota a creation would have exceeded, with its limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaExceeded {
+    ActiveLinks(u64),
+    CustomAliases(u64),
+    MonthlyCreations(u64),
+}
+
+impl QuotaExceeded {
+    /// Name of the exhausted quota.
+    pub fn quota(&self) -> &'static str {
+        match self {
+            QuotaExceeded::ActiveLinks(_) => "active_links",
+            QuotaExceeded::CustomAliases(_) => "custom_aliases",
+            QuotaExceeded::MonthlyCreations(_) => "monthly_creations",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            QuotaExceeded::ActiveLinks(max) => format!("Active link limit of {} reached", max),
+            QuotaExceeded::CustomAliases(max) => {
+                format!("Custom alias limit of {} reached", max)
+            }
+            QuotaExceeded::MonthlyCreations(max) => {
+                format!("Monthly creation limit of {} reached", max)
+            }
+        }
+    }
+}
+
+/// A link counted against its owner's quota by [`reserve`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reservation {
+    /// Month (`YYYY-MM`) whose creation counter was incremented.
+    pub period: String,
+}
+
+/// Current consumption of an owner.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Usage {
+    pub owner: String,
+    pub period: String,
+    pub active_links: u64,
+    pub custom_aliases: u64,
+    pub monthly_creations: u64,
+    pub limits: Quota,
+}
+
+impl Usage {
+    /// Publish this usage to the per-owner Prometheus gauges.
+    pub fn publish(&self) {
+        ACTIVE_LINKS
+            .with_label_values(&[&self.owner])
+            .set(self.active_links as i64);
+        CUSTOM_ALIASES
+            .with_label_values(&[&self.owner])
+            .set(self.custom_aliases as i64);
+        MONTHLY_CREATIONS
+            .with_label_values(&[&self.owner])
+            .set(self.monthly_creations as i64);
+    }
+}
+
+fn active_key(owner: &str) -> String {
+    format!("quota:{}:links", owner)
+}
+
+fn aliases_key(owner: &str) -> String {
+    format!("quota:{}:aliases", owner)
+}
+
+fn monthly_key(owner: &str, period: &str) -> String {
+    format!("quota:{}:created:{}", owner, period)
+}
+
+fn current_period() -> String {
+    chrono::Utc::now().format("%Y-%m").to_string()
+}
+
+fn limit_arg(limit: Option<u64>) -> i64 {
+    limit.map_or(-1, |limit| limit.min(i64::MAX as u64) as i64)
+}
+
+/// Atomically check `quota` for `owner` and count the new link `link_id` against it.
+///
+/// The link stays counted as active until `expires_at` (unix seconds).
+pub async fn reserve(
+    database: Database,
+    owner: &str,
+    link_id: &str,
+    expires_at: i64,
+    custom: bool,
+    quota: &Quota,
+) -> RedisResult<Result<Reservation, QuotaExceeded>> {
+    let now = chrono::Utc::now().timestamp();
+    let period = current_period();
+    let (status, used): (i64, i64) = {
+        let mut conn = database.lock().await;
+        redis::Script::new(RESERVE_SCRIPT)
+            .key(active_key(owner))
+            .key(aliases_key(owner))
+            .key(monthly_key(owner, &period))
+            .arg(now)
+            .arg(expires_at)
+            .arg(link_id)
+            .arg(if custom { 1 } else { 0 })
+            .arg(limit_arg(quota.max_active_links))
+            .arg(limit_arg(quota.max_custom_aliases))
+            .arg(limit_arg(quota.monthly_creations))
+            .arg(MONTHLY_COUNTER_TTL)
+            .invoke_async(&mut *conn)
+            .await?
+    };
+
+    let used = used as u64;
+    let exceeded = match status {
+        1 => QuotaExceeded::ActiveLinks(quota.max_active_links.unwrap_or(used)),
+        2 => QuotaExceeded::CustomAliases(quota.max_custom_aliases.unwrap_or(used)),
+        3 => QuotaExceeded::MonthlyCreations(quota.monthly_creations.unwrap_or(used)),
+        _ => {
+            RESERVATIONS.with_label_values(&["reserved"]).inc();
+            return Ok(Ok(Reservation { period }));
+        }
+    };
+    RESERVATIONS.with_label_values(&[exceeded.quota()]).inc();
+    Ok(Err(exceeded))
+}
+
+/// Undo a successful [`reserve`] when the link could not be stored.
+///
+/// The creation is uncounted from the month of `reservation`, even once another has begun.
+pub async fn release(
+    database: Database,
+    owner: &str,
+    link_id: &str,
+    reservation: &Reservation,
+) -> RedisResult<()> {
+    let mut conn = database.lock().await;
+    redis::pipe()
+        .atomic()
+        .zrem(active_key(owner), link_id)
+        .ignore()
+        .zrem(aliases_key(owner), link_id)
+        .ignore()
+        .decr(monthly_key(owner, &reservation.period), 1)
+        .ignore()
+        .query_async(&mut *conn)
+        .await
+}
+
+/// Read the current usage of `owner`.
+pub async fn usage(database: Database, owner: &str, quota: &Quota) -> RedisResult<Usage> {
+    let now = chrono::Utc::now().timestamp();
+    let period = current_period();
+    let (active_links, custom_aliases, monthly_creations): (u64, u64, Option<u64>) = {
+        let mut conn = database.lock().await;
+        redis::pipe()
+            .cmd("ZCOUNT")
+            .arg(active_key(owner))
+            .arg(format!("({}", now))
+            .arg("+inf")
+            .cmd("ZCOUNT")
+            .arg(aliases_key(owner))
+            .arg(format!("({}", now))
+            .arg("+inf")
+            .get(monthly_key(owner, &period))
+            .query_async(&mut *conn)
+            .await?
+    };
+    let monthly_creations = monthly_creations.unwrap_or(0);
+
+    Ok(Usage {
+        owner: owner.to_string(),
+        period,
+        active_links,
+        custom_aliases,
+        monthly_creations,
+        limits: *quota,
+    })
+}
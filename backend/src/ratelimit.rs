@@ -0,0 +1,349 @@
+use crate::db::Database;
+use crate::error::ApiError;
+use crate::state::{with_state, AppState};
+use dashmap::DashMap;
+use serde::Deserialize;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use warp::http::StatusCode;
+use warp::{reject, Filter};
+
+/// Upper bound on in-memory buckets kept while Redis is unreachable.
+const MAX_LOCAL_BUCKETS: usize = 100_000;
+
+/// Atomically refill and take one token from the bucket stored at `KEYS[1]`.
+///
+/// ARGV: capacity, refill rate per second, current time in milliseconds.
+/// Returns `{allowed, tokens_left}`; the token count is a string to keep fractions.
+const TOKEN_BUCKET_SCRIPT: &str = r#"
+local capacity = tonumber(ARGV[1])
+local rate = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+local state = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(state[1]) or capacity
+local ts = tonumber(state[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - ts) / 1000 * rate)
+local allowed = 0
+if tokens >= 1 then
+  tokens = tokens - 1
+  allowed = 1
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', tostring(now))
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity / rate * 1000) + 1000)
+return {allowed, tostring(tokens)}
+"#;
+
+/// A token bucket: up to `capacity` requests, refilled at `refill_per_sec`.
+///
+/// Written as `"<requests>/<seconds>"`, e.g. `"60/60"` for sixty requests per minute.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct RateLimit {
+    pub capacity: u32,
+    pub refill_per_sec: f64,
+}
+
+impl RateLimit {
+    pub fn per_minute(requests: u32) -> Self {
+        RateLimit {
+            capacity: requests,
+            refill_per_sec: requests as f64 / 60.0,
+        }
+    }
+}
+
+impl FromStr for RateLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (requests, seconds) = s
+            .trim()
+            .split_once('/')
+            .ok_or_else(|| format!("Expected <requests>/<seconds>, got `{}`", s))?;
+        let capacity: u32 = requests
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid request count in `{}`", s))?;
+        let seconds: f64 = seconds
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid period in `{}`", s))?;
+        if capacity == 0 || seconds <= 0.0 {
+            return Err(format!("Rate limit `{}` must be positive", s));
+        }
+        Ok(RateLimit {
+            capacity,
+            refill_per_sec: capacity as f64 / seconds,
+        })
+    }
+}
+
+impl TryFrom<String> for RateLimit {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// Outcome of taking a token from a bucket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again.
+    pub reset_secs: u64,
+    /// Seconds until the next request would be allowed; zero when allowed.
+    pub retry_after_secs: u64,
+}
+
+impl Decision {
+    fn new(limit: &RateLimit, allowed: bool, tokens: f64) -> Self {
+        let tokens = tokens.max(0.0);
+        let retry_after_secs = if allowed {
+            0
+        } else {
+            ((1.0 - tokens) / limit.refill_per_sec).ceil().max(1.0) as u64
+        };
+        Decision {
+            allowed,
+            limit: limit.capacity,
+            remaining: tokens.floor() as u32,
+            reset_secs: ((limit.capacity as f64 - tokens) / limit.refill_per_sec).ceil() as u64,
+            retry_after_secs,
+        }
+    }
+}
+
+/// Refill a bucket holding `tokens` last updated at `last_ms` and try to take one token.
+///
+/// Returns whether the request is allowed and the tokens left. This mirrors the Lua script.
+pub fn take_token(limit: &RateLimit, tokens: f64, last_ms: i64, now_ms: i64) -> (bool, f64) {
+    let elapsed = (now_ms - last_ms).max(0) as f64 / 1000.0;
+    let tokens = (tokens + elapsed * limit.refill_per_sec).min(limit.capacity as f64);
+    if tokens >= 1.0 {
+        (true, tokens - 1.0)
+    } else {
+        (false, tokens)
+    }
+}
+
+/// Warp rejection raised when a bucket is empty.
+#[derive(Debug)]
+pub struct RateLimited(pub Decision);
+impl reject::Reject for RateLimited {}
+
+/// Build the `429 Too Many Requests` reply with `Retry-After` and `RateLimit-*` headers.
+pub fn rate_limited_reply(decision: &Decision) -> ApiError {
+    ApiError::new(
+        StatusCode::TOO_MANY_REQUESTS,
+        "RATE_LIMITED",
+        format!(
+            "Too many requests, retry in {} seconds",
+            decision.retry_after_secs
+        ),
+    )
+    .with_header("Retry-After", decision.retry_after_secs)
+    .with_header("RateLimit-Limit", decision.limit)
+    .with_header("RateLimit-Remaining", decision.remaining)
+    .with_header("RateLimit-Reset", decision.reset_secs)
+}
+
+/// Class of route a bucket applies to, so redirects and writes are limited separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteClass {
+    Write,
+    Redirect,
+    /// Password attempts on a protected link, per link and client IP.
+    Password,
+}
+
+impl RouteClass {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RouteClass::Write => "write",
+            RouteClass::Redirect => "redirect",
+            RouteClass::Password => "password",
+        }
+    }
+}
+
+/// Token bucket limiter backed by Redis, falling back to process memory when Redis fails.
+pub struct RateLimiter {
+    db: Database,
+    script: redis::Script,
+    local: DashMap<String, (f64, i64)>,
+}
+
+impl RateLimiter {
+    pub fn new(db: Database) -> Self {
+        RateLimiter {
+            db,
+            script: redis::Script::new(TOKEN_BUCKET_SCRIPT),
+            local: DashMap::new(),
+        }
+    }
+
+    /// Take a token from the bucket of `subject` (an owner or client IP) for `class` routes.
+    pub async fn check(&self, class: RouteClass, subject: &str, limit: &RateLimit) -> Decision {
+        let key = format!("ratelimit:{}:{}", class.as_str(), subject);
+        let now_ms = chrono::Utc::now().timestamp_millis();
+
+        let result: redis::RedisResult<(i64, String)> = {
+            let mut conn = self.db.lock().await;
+            self.script
+                .key(&key)
+                .arg(limit.capacity)
+                .arg(limit.refill_per_sec)
+                .arg(now_ms)
+                .invoke_async(&mut *conn)
+                .await
+        };
+
+        match result {
+            Ok((allowed, tokens)) => {
+                Decision::new(limit, allowed == 1, tokens.parse().unwrap_or(0.0))
+            }
+            Err(e) => {
+                eprintln!("⚠️ Rate limiter falling back to memory: {}", e);
+                self.check_local(&key, limit, now_ms)
+            }
+        }
+    }
+
+    fn check_local(&self, key: &str, limit: &RateLimit, now_ms: i64) -> Decision {
+        if self.local.len() >= MAX_LOCAL_BUCKETS {
+            // Drop buckets that have been idle long enough to be full again.
+            let idle_ms = (limit.capacity as f64 / limit.refill_per_sec * 1000.0) as i64;
+            self.local.retain(|_, (_, last)| now_ms - *last < idle_ms);
+        }
+
+        let mut bucket = self
+            .local
+            .entry(key.to_string())
+            .or_insert((limit.capacity as f64, now_ms));
+        let (allowed, tokens) = take_token(limit, bucket.0, bucket.1, now_ms);
+        *bucket = (tokens, now_ms);
+        Decision::new(limit, allowed, tokens)
+    }
+}
+
+/// An IP network in CIDR notation; a bare address is a single-host network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl FromStr for IpNet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("Invalid IP address `{}`", s))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| format!("Invalid prefix length in `{}`", s))?,
+            None => max,
+        };
+        Ok(IpNet { addr, prefix })
+    }
+}
+
+impl IpNet {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Determine the client address of a request.
+///
+/// `X-Forwarded-For` is only honoured when the peer is a trusted proxy; the client is then
+/// the right-most hop that is not itself a trusted proxy.
+pub fn resolve_client_ip(
+    remote: Option<IpAddr>,
+    forwarded_for: Option<&str>,
+    trusted_proxies: &[IpNet],
+) -> Option<IpAddr> {
+    let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+
+    let remote = remote?;
+    if !is_trusted(&remote) {
+        return Some(remote);
+    }
+
+    let hops: Vec<IpAddr> = forwarded_for
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|hop| hop.trim().parse().ok())
+        .collect();
+    hops.iter()
+        .rev()
+        .find(|hop| !is_trusted(hop))
+        .or(hops.first())
+        .copied()
+        .or(Some(remote))
+}
+
+/// Extract the client IP (see [`resolve_client_ip`]) for use in handlers.
+pub fn client_ip(
+    trusted_proxies: Vec<IpNet>,
+) -> impl Filter<Extract = (Option<IpAddr>,), Error = warp::Rejection> + Clone {
+    warp::addr::remote()
+        .and(warp::header::optional::<String>("X-Forwarded-For"))
+        .map(
+            move |remote: Option<SocketAddr>, forwarded: Option<String>| {
+                resolve_client_ip(
+                    remote.map(|addr| addr.ip()),
+                    forwarded.as_deref(),
+                    &trusted_proxies,
+                )
+            },
+        )
+}
+
+/// Reject requests once the calling client IP has exhausted its bucket for `class` routes.
+pub fn limit_client_ip(
+    state: AppState,
+    class: RouteClass,
+) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    client_ip(state.config.trusted_proxies.clone())
+        .and(with_state(state))
+        .and_then(move |ip: Option<IpAddr>, state: AppState| async move {
+            let limit = match class {
+                RouteClass::Write => state.config.write_ip_limit,
+                RouteClass::Redirect => state.config.redirect_ip_limit,
+                RouteClass::Password => state.config.password_attempt_limit,
+            };
+            if let (Some(ip), Some(limit)) = (ip, limit) {
+                let subject = format!("ip:{}", ip);
+                let decision = state.limiter.check(class, &subject, &limit).await;
+                if !decision.allowed {
+                    return Err(reject::custom(RateLimited(decision)));
+                }
+            }
+            Ok(())
+        })
+        .untuple_one()
+}
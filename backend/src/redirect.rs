@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use warp::http::{header, Response, StatusCode};
+use warp::hyper::Body;
+
+/// HTTP status a short link redirects with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "u16", into = "u16")]
+pub enum RedirectType {
+    /// 301 Moved Permanently: cached, and passes ranking signals to the destination.
+    MovedPermanently,
+    /// 302 Found: for legacy clients that mishandle 307.
+    Found,
+    /// 307 Temporary Redirect: never cached, keeps the request method.
+    #[default]
+    Temporary,
+    /// 308 Permanent Redirect: like 301, but keeps the request method.
+    Permanent,
+}
+
+impl RedirectType {
+    pub fn status(self) -> StatusCode {
+        match self {
+            RedirectType::MovedPermanently => StatusCode::MOVED_PERMANENTLY,
+            RedirectType::Found => StatusCode::FOUND,
+            RedirectType::Temporary => StatusCode::TEMPORARY_REDIRECT,
+            RedirectType::Permanent => StatusCode::PERMANENT_REDIRECT,
+        }
+    }
+
+    pub fn is_permanent(self) -> bool {
+        matches!(
+            self,
+            RedirectType::MovedPermanently | RedirectType::Permanent
+        )
+    }
+
+    /// `Cache-Control` for the redirect.
+    ///
+    /// Permanent redirects may be cached for `max_age` seconds, which callers cap at the link's
+    /// remaining lifetime; temporary ones are never stored so every click reaches us.
+    pub fn cache_control(self, max_age: u64) -> String {
+        if self.is_permanent() {
+            format!("public, max-age={}", max_age)
+        } else {
+            "no-store".to_string()
+        }
+    }
+
+    /// The redirect response to `location`.
+    pub fn reply(self, location: &warp::http::Uri, max_age: u64) -> Response<Body> {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = self.status();
+        let headers = response.headers_mut();
+        if let Ok(value) = location.to_string().parse() {
+            headers.insert(header::LOCATION, value);
+        }
+        if let Ok(value) = self.cache_control(max_age).parse() {
+            headers.insert(header::CACHE_CONTROL, value);
+        }
+        response
+    }
+}
+
+impl TryFrom<u16> for RedirectType {
+    type Error = String;
+
+    fn try_from(status: u16) -> Result<Self, Self::Error> {
+        match status {
+            301 => Ok(RedirectType::MovedPermanently),
+            302 => Ok(RedirectType::Found),
+            307 => Ok(RedirectType::Temporary),
+            308 => Ok(RedirectType::Permanent),
+            _ => Err(format!(
+                "unsupported redirect status {}, expected 301, 302, 307 or 308",
+                status
+            )),
+        }
+    }
+}
+
+impl From<RedirectType> for u16 {
+    fn from(redirect: RedirectType) -> u16 {
+        redirect.status().as_u16()
+    }
+}
+
+impl FromStr for RedirectType {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let status: u16 = value
+            .trim()
+            .parse()
+            .map_err(|_| format!("`{}` is not a status code", value))?;
+        RedirectType::try_from(status)
+    }
+}
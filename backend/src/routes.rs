@@ -0,0 +1,363 @@
+//! Path segments served by the backend, and the routes mounted on them.
+//!
+//! Every top-level segment is listed in [`ROUTE_NAMES`] so custom aliases can never shadow it,
+//! and every endpoint in [`ENDPOINTS`], which the OpenAPI document is checked against.
+
+use crate::auth;
+use crate::base_url;
+use crate::handlers;
+use crate::models::{LinkQuery, PasswordForm, UnknownFields};
+use crate::openapi;
+use crate::preview;
+use crate::ratelimit::{self, RouteClass};
+use crate::state::{with_state, AppState};
+use crate::targeting;
+use crate::versioning;
+use once_cell::sync::Lazy;
+use prometheus::{register_counter, register_histogram_vec, Counter, HistogramVec};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::IpAddr;
+use warp::cors;
+use warp::http::HeaderMap;
+use warp::{Filter, Reply};
+
+/// Largest password form body accepted, in bytes.
+const PASSWORD_FORM_LIMIT: u64 = 4096;
+
+static GENERATE_URL_REQUESTS: Lazy<Counter> = Lazy::new(|| {
+    register_counter!(
+        "generate_url_requests_total",
+        "Total number of generate_url requests"
+    )
+    .unwrap()
+});
+
+static REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    let buckets = prometheus::linear_buckets(0.01, 0.05, 20).unwrap();
+    register_histogram_vec!(
+        "http_request_duration_seconds",
+        "Request duration for generate_url",
+        &["endpoint"],
+        buckets
+    )
+    .unwrap()
+});
+
+/// Versioned management API: `/api/v1/...`.
+pub const API: &str = "api";
+pub const V1: &str = "v1";
+pub const LINKS: &str = "links";
+pub const CUSTOM: &str = "custom";
+pub const STATS: &str = "stats";
+pub const USAGE: &str = "usage";
+
+pub const DNS_RESOLVER: &str = "dns_resolver";
+pub const PING: &str = "ping";
+pub const METRICS: &str = "metrics";
+pub const OPENAPI: &str = "openapi.json";
+pub const DOCS: &str = "docs";
+
+/// Deprecated unversioned aliases of the `/api/v1` routes.
+pub const GENERATE_URL: &str = "generate_url";
+pub const CUSTOM_URL: &str = "custom_url";
+
+pub const LINKS_PATH: &str = "/api/v1/links";
+pub const CUSTOM_LINKS_PATH: &str = "/api/v1/links/custom";
+pub const LINK_PATH: &str = "/api/v1/links/{code}";
+pub const LINK_STATS_PATH: &str = "/api/v1/links/{code}/stats";
+pub const USAGE_PATH: &str = "/api/v1/usage";
+/// Short links, relative to `PUBLIC_BASE_URL`.
+pub const REDIRECT_PATH: &str = "/{short_url}";
+pub const PREVIEW_PATH: &str = "/{short_url}/preview";
+
+/// All registered top-level route names.
+pub const ROUTE_NAMES: &[&str] = &[
+    API,
+    GENERATE_URL,
+    CUSTOM_URL,
+    DNS_RESOLVER,
+    USAGE,
+    PING,
+    METRICS,
+    OPENAPI,
+    DOCS,
+];
+
+/// A method and path template, written the way OpenAPI writes paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Endpoint {
+    pub method: &'static str,
+    pub path: &'static str,
+    /// The route is kept for old clients; `successor` replaces it when set.
+    pub deprecated: bool,
+    pub successor: Option<&'static str>,
+}
+
+const fn endpoint(method: &'static str, path: &'static str) -> Endpoint {
+    Endpoint {
+        method,
+        path,
+        deprecated: false,
+        successor: None,
+    }
+}
+
+const fn legacy(
+    method: &'static str,
+    path: &'static str,
+    successor: Option<&'static str>,
+) -> Endpoint {
+    Endpoint {
+        method,
+        path,
+        deprecated: true,
+        successor,
+    }
+}
+
+/// Every route mounted by [`filter`].
+pub const ENDPOINTS: &[Endpoint] = &[
+    endpoint("POST", LINKS_PATH),
+    endpoint("POST", CUSTOM_LINKS_PATH),
+    endpoint("GET", LINK_PATH),
+    endpoint("GET", LINK_STATS_PATH),
+    endpoint("GET", USAGE_PATH),
+    endpoint("GET", "/ping"),
+    endpoint("GET", "/metrics"),
+    endpoint("GET", "/openapi.json"),
+    endpoint("GET", "/docs"),
+    endpoint("GET", REDIRECT_PATH),
+    endpoint("POST", REDIRECT_PATH),
+    endpoint("GET", PREVIEW_PATH),
+    legacy("POST", "/generate_url", Some(LINKS_PATH)),
+    legacy("POST", "/custom_url", Some(CUSTOM_LINKS_PATH)),
+    legacy("GET", "/usage", Some(USAGE_PATH)),
+    legacy("GET", "/dns_resolver/{short_url}", Some(REDIRECT_PATH)),
+    legacy("POST", "/dns_resolver/{short_url}", Some(REDIRECT_PATH)),
+];
+
+/// Every route of the backend, serving `state`; rejections become problem documents.
+pub fn filter(
+    state: AppState,
+) -> impl Filter<Extract = (impl Reply,), Error = Infallible> + Clone + Send + Sync + 'static {
+    let cors = cors()
+        .allow_any_origin()
+        .allow_methods(vec!["GET", "POST", "PUT", "DELETE", "OPTIONS"])
+        .allow_headers(vec![
+            "Content-Type",
+            "API-Key",
+            "Authorization",
+            "X-Key-Id",
+            "X-Timestamp",
+            "X-Nonce",
+            "X-Signature",
+        ])
+        .build();
+
+    let legacy_policy = state.config.legacy_deprecation;
+
+    // Create a link; mounted at /api/v1/links and /generate_url
+    let create_link = |unknown: UnknownFields| {
+        warp::post()
+            .and(auth::credentials())
+            .and(warp::path::end())
+            .and(ratelimit::limit_client_ip(state.clone(), RouteClass::Write))
+            .and(warp::body::bytes())
+            .and(with_state(state.clone()))
+            .and_then(move |credentials, body, state| {
+                GENERATE_URL_REQUESTS.inc();
+                let histogram = REQUEST_DURATION.with_label_values(&["generate_url"]);
+                let timer = histogram.start_timer();
+                let fut = handlers::handle_generate_url(credentials, body, state, unknown);
+                async move {
+                    let result = fut.await;
+                    timer.observe_duration();
+                    result
+                }
+            })
+    };
+
+    // Create a custom alias; mounted at /api/v1/links/custom and /custom_url
+    let create_custom_link = |unknown: UnknownFields| {
+        warp::post()
+            .and(auth::credentials())
+            .and(warp::path::end())
+            .and(ratelimit::limit_client_ip(state.clone(), RouteClass::Write))
+            .and(warp::body::bytes())
+            .and(with_state(state.clone()))
+            .and(warp::any().map(move || unknown))
+            .and_then(handlers::handle_custom_url)
+    };
+
+    // Quota usage; mounted at /api/v1/usage and /usage
+    let usage = warp::get()
+        .and(warp::path::end())
+        .and(auth::credentials())
+        .and(with_state(state.clone()))
+        .and_then(handlers::handle_usage);
+
+    // Click counters of a link: /api/v1/links/{code}/stats
+    let link_stats = warp::get()
+        .and(warp::path::param::<String>())
+        .and(warp::path(STATS))
+        .and(warp::path::end())
+        .and(warp::query::<LinkQuery>())
+        .and(auth::credentials())
+        .and(with_state(state.clone()))
+        .and_then(handlers::handle_link_stats);
+
+    // One of the caller's links: /api/v1/links/{code}
+    let link_details = warp::get()
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::query::<LinkQuery>())
+        .and(auth::credentials())
+        .and(with_state(state.clone()))
+        .and_then(handlers::handle_link_details);
+
+    // Route: /api/v1/...
+    let v1 = warp::path(API).and(warp::path(V1));
+    let v1_links = v1.and(warp::path(LINKS));
+    let v1_routes = v1_links
+        .and(warp::path(CUSTOM))
+        .and(create_custom_link(UnknownFields::Reject))
+        .or(v1_links.and(create_link(UnknownFields::Reject)))
+        .or(v1_links.and(link_stats))
+        .or(v1_links.and(link_details))
+        .or(v1.and(warp::path(USAGE)).and(usage.clone()));
+
+    // Route: /generate_url, /custom_url, /usage (deprecated)
+    let legacy_routes = versioning::legacy(
+        warp::path(GENERATE_URL).and(create_link(UnknownFields::Ignore)),
+        GENERATE_URL,
+        Some(LINKS_PATH),
+        legacy_policy,
+    )
+    .or(versioning::legacy(
+        warp::path(CUSTOM_URL).and(create_custom_link(UnknownFields::Ignore)),
+        CUSTOM_URL,
+        Some(CUSTOM_LINKS_PATH),
+        legacy_policy,
+    ))
+    .or(versioning::legacy(
+        warp::path(USAGE).and(usage),
+        USAGE,
+        Some(USAGE_PATH),
+        legacy_policy,
+    ));
+
+    // Resolve a short code; mounted at /{code} and /dns_resolver/{code}
+    let redirect = ratelimit::limit_client_ip(state.clone(), RouteClass::Redirect)
+        .and(ratelimit::client_ip(state.config.trusted_proxies.clone()))
+        .and(warp::header::headers_cloned())
+        .and(warp::query::raw().or(warp::any().map(String::new)).unify());
+    let params = |short_url: String,
+                  path: String,
+                  client_ip: Option<IpAddr>,
+                  headers: HeaderMap,
+                  query: String| {
+        let mut map = HashMap::new();
+        if let Some(ip) = client_ip {
+            map.insert("client_ip".to_string(), ip.to_string());
+        }
+        map.insert("short_url".to_string(), short_url);
+        map.insert("path".to_string(), path);
+        map.insert("query".to_string(), query);
+        for name in ["host", "cookie", "accept"]
+            .into_iter()
+            .chain(targeting::CLIENT_HEADERS)
+        {
+            if let Some(value) = headers.get(name).and_then(|v| v.to_str().ok()) {
+                map.insert(name.to_string(), value.to_string());
+            }
+        }
+        map
+    };
+
+    // Route: /dns_resolver/:short_url (deprecated); POST submits the password form it shows
+    let legacy_link = warp::path(DNS_RESOLVER)
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::any().map(String::new))
+        .and(redirect.clone())
+        .map(params);
+    let legacy_redirect = versioning::legacy(
+        warp::get()
+            .and(legacy_link.clone())
+            .and(with_state(state.clone()))
+            .and_then(handlers::handle_redirect_url)
+            .or(warp::post()
+                .and(legacy_link)
+                .and(warp::body::content_length_limit(PASSWORD_FORM_LIMIT))
+                .and(warp::body::form::<PasswordForm>())
+                .and(with_state(state.clone()))
+                .and_then(handlers::handle_unlock_url))
+            .unify(),
+        DNS_RESOLVER,
+        Some(REDIRECT_PATH),
+        legacy_policy,
+    )
+    .with(cors.clone());
+
+    // Route: /:short_url[/...] under PUBLIC_BASE_URL, tried after every other route;
+    // /:short_url+ and /:short_url/preview show the preview page,
+    // POST submits the password form of a protected link
+    let short_link = base_url::short_link(state.config.public_base_url.clone())
+        .and(redirect)
+        .map(params);
+    let redirect_route = warp::get()
+        .and(short_link.clone())
+        .and(with_state(state.clone()))
+        .and_then(
+            |params: HashMap<String, String>, state: AppState| async move {
+                if preview::is_preview(&params) {
+                    handlers::handle_preview_url(params, state).await
+                } else {
+                    handlers::handle_redirect_url(params, state).await
+                }
+            },
+        )
+        .or(warp::post()
+            .and(short_link)
+            .and(warp::body::content_length_limit(PASSWORD_FORM_LIMIT))
+            .and(warp::body::form::<PasswordForm>())
+            .and(with_state(state.clone()))
+            .and_then(handlers::handle_unlock_url))
+        .with(cors.clone());
+
+    // Route: /ping
+    let ping = warp::path(PING)
+        .and(warp::path::end())
+        .and_then(handlers::handle_ping);
+
+    // Route: /metrics
+    let metrics = warp::path(METRICS)
+        .and(warp::path::end())
+        .and_then(handlers::handle_metrics);
+
+    // Route: /openapi.json
+    let openapi_spec = warp::path(OPENAPI)
+        .and(warp::get())
+        .and(warp::path::end())
+        .and_then(openapi::handle_openapi);
+
+    // Route: /docs
+    let docs = warp::path(DOCS)
+        .and(warp::get())
+        .and(warp::path::end())
+        .and_then(openapi::handle_docs);
+
+    let api_routes = v1_routes
+        .or(legacy_routes)
+        .or(ping)
+        .or(metrics)
+        .or(openapi_spec)
+        .or(docs)
+        .with(cors.clone());
+
+    api_routes
+        .or(legacy_redirect)
+        .or(redirect_route)
+        .recover(handlers::handle_rejection)
+}
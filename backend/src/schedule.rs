@@ -0,0 +1,142 @@
+use chrono::{DateTime, FixedOffset, SecondsFormat};
+
+/// Why a link's activation window was refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    /// The named field is not an RFC 3339 timestamp.
+    InvalidTime(&'static str),
+    /// `not_after` is not later than `not_before`.
+    EmptyWindow,
+    /// `not_before` is not earlier than the link's expiry, given here.
+    StartsAfterExpiry(DateTime<FixedOffset>),
+    /// `not_after` is not later than the link's creation.
+    EndsInPast,
+}
+
+impl ScheduleError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ScheduleError::InvalidTime(_) => "INVALID_TIME",
+            ScheduleError::EmptyWindow => "EMPTY_WINDOW",
+            ScheduleError::StartsAfterExpiry(_) => "WINDOW_AFTER_EXPIRY",
+            ScheduleError::EndsInPast => "WINDOW_IN_PAST",
+        }
+    }
+
+    pub fn field(&self) -> &'static str {
+        match self {
+            ScheduleError::InvalidTime(field) => field,
+            ScheduleError::EmptyWindow | ScheduleError::EndsInPast => "not_after",
+            ScheduleError::StartsAfterExpiry(_) => "not_before",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ScheduleError::InvalidTime(field) => format!(
+                "{} must be an RFC 3339 timestamp such as 2030-01-31T09:00:00+01:00",
+                field
+            ),
+            ScheduleError::EmptyWindow => "not_after must be later than not_before".to_string(),
+            ScheduleError::StartsAfterExpiry(expires) => format!(
+                "not_before must be earlier than the link's expiry at {}, unless not_after is set",
+                expires.to_rfc3339()
+            ),
+            ScheduleError::EndsInPast => "not_after must be in the future".to_string(),
+        }
+    }
+}
+
+/// Where a moment falls relative to a link's activation window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowState {
+    /// Before `not_before`, given as the start.
+    Pending(DateTime<FixedOffset>),
+    Active,
+    /// At or after `not_after`, given as the end.
+    Ended(DateTime<FixedOffset>),
+}
+
+/// The time a link starts and stops redirecting, independent of its storage TTL.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Window {
+    pub not_before: Option<DateTime<FixedOffset>>,
+    pub not_after: Option<DateTime<FixedOffset>>,
+}
+
+impl Window {
+    /// Parse and check the window fields of a request.
+    pub fn parse(
+        not_before: Option<&str>,
+        not_after: Option<&str>,
+    ) -> Result<Window, ScheduleError> {
+        let window = Window {
+            not_before: parse_time("not_before", not_before)?,
+            not_after: parse_time("not_after", not_after)?,
+        };
+        if let (Some(start), Some(end)) = (window.not_before, window.not_after) {
+            if end <= start {
+                return Err(ScheduleError::EmptyWindow);
+            }
+        }
+        Ok(window)
+    }
+
+    /// The storage TTL in seconds of a link created at `created`, by default kept for `ttl`.
+    ///
+    /// Links are kept until `not_after` when it is later; a link that has already closed, or
+    /// opening after it expires, would never redirect, so those are errors.
+    pub fn ttl(&self, created: DateTime<FixedOffset>, ttl: u32) -> Result<u32, ScheduleError> {
+        if self.not_after.is_some_and(|end| end <= created) {
+            return Err(ScheduleError::EndsInPast);
+        }
+        if let Some(remaining) = self.remaining(created) {
+            return Ok(u32::try_from(remaining).unwrap_or(u32::MAX).max(ttl));
+        }
+        let expires = created + chrono::Duration::seconds(ttl.into());
+        match self.not_before {
+            Some(start) if start >= expires => Err(ScheduleError::StartsAfterExpiry(expires)),
+            _ => Ok(ttl),
+        }
+    }
+
+    /// Read a window stored by [`Window::to_stored`]; unreadable bounds are ignored.
+    pub fn from_stored(not_before: Option<&str>, not_after: Option<&str>) -> Window {
+        Window {
+            not_before: not_before.and_then(|t| DateTime::parse_from_rfc3339(t).ok()),
+            not_after: not_after.and_then(|t| DateTime::parse_from_rfc3339(t).ok()),
+        }
+    }
+
+    /// The bounds as canonical RFC 3339 strings, keeping their offsets.
+    pub fn to_stored(&self) -> (Option<String>, Option<String>) {
+        let format = |t: DateTime<FixedOffset>| t.to_rfc3339_opts(SecondsFormat::AutoSi, false);
+        (self.not_before.map(format), self.not_after.map(format))
+    }
+
+    pub fn state(&self, now: DateTime<FixedOffset>) -> WindowState {
+        match (self.not_before, self.not_after) {
+            (Some(start), _) if now < start => WindowState::Pending(start),
+            (_, Some(end)) if now >= end => WindowState::Ended(end),
+            _ => WindowState::Active,
+        }
+    }
+
+    /// Seconds from `now` until the window closes, if it does.
+    pub fn remaining(&self, now: DateTime<FixedOffset>) -> Option<u64> {
+        self.not_after
+            .map(|end| end.signed_duration_since(now).num_seconds().max(0) as u64)
+    }
+}
+
+fn parse_time(
+    field: &'static str,
+    value: Option<&str>,
+) -> Result<Option<DateTime<FixedOffset>>, ScheduleError> {
+    value
+        .map(|value| {
+            DateTime::parse_from_rfc3339(value.trim())
+                .map_err(|_| ScheduleError::InvalidTime(field))
+        })
+        .transpose()
+}
@@ -0,0 +1,178 @@
+use crate::passthrough::Passthrough;
+use crate::preview;
+use crate::routes::{DNS_RESOLVER, ROUTE_NAMES};
+use std::future::Future;
+use url::Url;
+
+/// Why a destination pointing back at the shortener was refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainError {
+    /// Targets one of our domains, but not a short link (e.g. `/metrics`).
+    SelfReference,
+    /// Targets a short link that does not exist.
+    UnknownShortLink(String),
+    /// Adds a trailing path to a short link that does not forward it.
+    PathNotForwarded(String),
+    /// Following the short links comes back to one already visited.
+    Loop(String),
+    /// More short links had to be followed than allowed.
+    TooDeep(usize),
+}
+
+impl ChainError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ChainError::SelfReference => "SELF_REFERENCE",
+            ChainError::UnknownShortLink(_) => "UNKNOWN_SHORT_LINK",
+            ChainError::PathNotForwarded(_) => "PATH_NOT_FORWARDED",
+            ChainError::Loop(_) => "REDIRECT_LOOP",
+            ChainError::TooDeep(_) => "REDIRECT_CHAIN_TOO_DEEP",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ChainError::SelfReference => "long_url may not point at this service".to_string(),
+            ChainError::UnknownShortLink(code) => {
+                format!("long_url points to the unknown short link `{}`", code)
+            }
+            ChainError::PathNotForwarded(code) => format!(
+                "long_url adds a path to the short link `{}`, which does not forward paths",
+                code
+            ),
+            ChainError::Loop(code) => format!("Short link `{}` would redirect to itself", code),
+            ChainError::TooDeep(max) => {
+                format!("long_url goes through more than {} short links", max)
+            }
+        }
+    }
+}
+
+/// Where a URL points relative to our own domains.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    External,
+    /// The link `code` in the namespace of `domain` (lowercase), visited with the trailing
+    /// `path` after it, empty when there is none.
+    ShortLink {
+        domain: String,
+        code: String,
+        path: String,
+    },
+    OwnPath,
+}
+
+/// Classify `url` against the hosts the shortener is served from.
+///
+/// Short links are `/{prefix}/{code}`, possibly followed by a trailing path, or the legacy
+/// `/dns_resolver/{code}`. Their previews, `/{prefix}/{code}+` and `/{prefix}/{code}/preview`,
+/// count as the link itself.
+pub fn classify(url: &Url, short_domains: &[String], prefix: &[String]) -> Target {
+    let Some(host) = url.host_str() else {
+        return Target::External;
+    };
+    let domain = host.to_ascii_lowercase();
+    if !short_domains.contains(&domain) {
+        return Target::External;
+    }
+
+    let segments: Vec<&str> = url
+        .path_segments()
+        .map(|segments| segments.filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+    if let [DNS_RESOLVER, code] = segments.as_slice() {
+        return Target::ShortLink {
+            domain,
+            code: code.to_string(),
+            path: String::new(),
+        };
+    }
+    let under_prefix = segments.len() > prefix.len()
+        && segments
+            .iter()
+            .zip(prefix)
+            .all(|(segment, expected)| segment == expected);
+    if !under_prefix {
+        return Target::OwnPath;
+    }
+    let code = segments[prefix.len()];
+    let path = segments[prefix.len() + 1..].join("/");
+    if ROUTE_NAMES.iter().any(|r| r.eq_ignore_ascii_case(code)) {
+        return Target::OwnPath;
+    }
+    match preview::preview_code(code, &path) {
+        Some(code) => Target::ShortLink {
+            domain,
+            code: code.to_string(),
+            path: String::new(),
+        },
+        None => Target::ShortLink {
+            domain,
+            code: code.to_string(),
+            path,
+        },
+    }
+}
+
+/// What [`resolve_destination`] needs to know about a stored short link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredLink {
+    pub long_url: String,
+    /// Visits must pass the link itself (password, click limit, schedule or interstitial), so
+    /// links to it keep pointing at it instead of copying its destination.
+    pub gated: bool,
+    /// What the link forwards from a visit, e.g. a trailing path after its code.
+    pub passthrough: Passthrough,
+}
+
+/// Follow `long_url` through our own short links to its final destination.
+///
+/// `lookup` returns the stored link of a short code on a domain. At most `max_depth` links are
+/// followed; loops, targets on our domains that are not links and trailing paths the link
+/// does not forward are refused. The chain stops at the first gated link, whose short URL is
+/// returned, but is still checked to its end.
+pub async fn resolve_destination<F, Fut>(
+    long_url: String,
+    short_domains: &[String],
+    prefix: &[String],
+    max_depth: usize,
+    mut lookup: F,
+) -> Result<String, ChainError>
+where
+    F: FnMut(String, String) -> Fut,
+    Fut: Future<Output = Option<StoredLink>>,
+{
+    let mut current = long_url;
+    let mut gate: Option<String> = None;
+    let mut visited: Vec<(String, String)> = Vec::new();
+
+    loop {
+        let Ok(url) = Url::parse(&current) else {
+            return Ok(gate.unwrap_or(current));
+        };
+        let (link, path) = match classify(&url, short_domains, prefix) {
+            Target::External => return Ok(gate.unwrap_or(current)),
+            Target::OwnPath => return Err(ChainError::SelfReference),
+            Target::ShortLink { domain, code, path } => ((domain, code), path),
+        };
+
+        if visited.contains(&link) {
+            return Err(ChainError::Loop(link.1));
+        }
+        if visited.len() >= max_depth {
+            return Err(ChainError::TooDeep(max_depth));
+        }
+        let stored = lookup(link.0.clone(), link.1.clone())
+            .await
+            .ok_or_else(|| ChainError::UnknownShortLink(link.1.clone()))?;
+        if stored.gated && gate.is_none() {
+            gate = Some(current.clone());
+        }
+        // The visit goes where the link sends it, with whatever the link forwards
+        current = stored
+            .passthrough
+            .apply(&stored.long_url, &path, url.query().unwrap_or_default())
+            .ok_or_else(|| ChainError::PathNotForwarded(link.1.clone()))?;
+        visited.push(link);
+    }
+}
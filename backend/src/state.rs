@@ -0,0 +1,72 @@
+use crate::auth::Authenticator;
+use crate::config::Config;
+use crate::db::Database;
+use crate::domain_policy::DomainPolicyStore;
+use crate::geoip::GeoIp;
+use crate::interstitial::TokenSigner;
+use crate::pages::PageTemplate;
+use crate::ratelimit::RateLimiter;
+use std::convert::Infallible;
+use std::sync::Arc;
+use warp::Filter;
+
+/// Shared services handed to every route.
+#[derive(Clone)]
+pub struct AppState {
+    pub db: Database,
+    pub config: Arc<Config>,
+    pub auth: Arc<Authenticator>,
+    pub limiter: Arc<RateLimiter>,
+    pub domain_policy: Arc<DomainPolicyStore>,
+    /// Locates visitors for country rules; `None` without `GEOIP_DATABASE`.
+    pub geoip: Option<Arc<GeoIp>>,
+    /// Layout of the error pages shown to browsers.
+    pub error_page: Arc<PageTemplate>,
+    /// Signs the continue tokens of interstitial pages.
+    pub interstitial: Arc<TokenSigner>,
+}
+
+impl AppState {
+    /// Assemble the state; the domain policy starts empty until it is reloaded, and
+    /// continue tokens are signed with a random secret until one is configured.
+    pub fn new(db: Database, config: Config, auth: Authenticator) -> Self {
+        AppState {
+            interstitial: Arc::new(TokenSigner::random(config.interstitial_token_ttl)),
+            limiter: Arc::new(RateLimiter::new(Arc::clone(&db))),
+            domain_policy: Arc::new(DomainPolicyStore::new(
+                config.domain_blocklist_file.clone(),
+                config.domain_allowlist_file.clone(),
+            )),
+            db,
+            config: Arc::new(config),
+            auth: Arc::new(auth),
+            geoip: None,
+            error_page: Arc::new(PageTemplate::default()),
+        }
+    }
+
+    /// Use `geoip` to locate visitors.
+    pub fn with_geoip(mut self, geoip: Option<GeoIp>) -> Self {
+        self.geoip = geoip.map(Arc::new);
+        self
+    }
+
+    /// Sign continue tokens with `signer`.
+    pub fn with_interstitial_signer(mut self, signer: TokenSigner) -> Self {
+        self.interstitial = Arc::new(signer);
+        self
+    }
+
+    /// Show error pages to browsers in `template`.
+    pub fn with_error_page(mut self, template: PageTemplate) -> Self {
+        self.error_page = Arc::new(template);
+        self
+    }
+}
+
+/// Pass a clone of the application state into the handler functions.
+pub fn with_state(
+    state: AppState,
+) -> impl Filter<Extract = (AppState,), Error = Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Request headers the rules are evaluated against, forwarded to the redirect handler.
+pub const CLIENT_HEADERS: [&str; 4] = [
+    "user-agent",
+    "accept-language",
+    "sec-ch-ua-platform",
+    "sec-ch-ua-mobile",
+];
+
+/// Most rules a single link may carry.
+pub const MAX_RULES: usize = 20;
+
+/// Operating system family of the visitor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Ios,
+    Android,
+    Windows,
+    Macos,
+    Linux,
+    Chromeos,
+}
+
+/// Form factor of the visitor's device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Device {
+    Mobile,
+    Tablet,
+    Desktop,
+}
+
+/// A destination used instead of `long_url` for visitors matching every set condition.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DestinationRule {
+    /// Matches any of these platforms; empty matches all.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub platforms: Vec<Platform>,
+    /// Matches any of these devices; empty matches all.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<Device>,
+    /// Matches the visitor's preferred language: `fr` matches `fr-CA`, `fr-CA` only itself.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schema(example = json!(["fr"]))]
+    pub languages: Vec<String>,
+    /// Matches visitors located in any of these countries, as ISO 3166-1 alpha-2 codes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schema(example = json!(["DE", "AT"]))]
+    pub countries: Vec<String>,
+    /// Destination for matching visitors.
+    #[serde(default)]
+    #[schema(required = true, example = "https://apps.apple.com/app/id000000000")]
+    pub long_url: String,
+}
+
+impl DestinationRule {
+    /// A rule without conditions would shadow every rule after it and `long_url`.
+    pub fn has_conditions(&self) -> bool {
+        !self.platforms.is_empty()
+            || !self.devices.is_empty()
+            || !self.languages.is_empty()
+            || !self.countries.is_empty()
+    }
+
+    pub fn matches(&self, client: &Client) -> bool {
+        let platform = self.platforms.is_empty()
+            || client.platform.is_some_and(|p| self.platforms.contains(&p));
+        let device = self.devices.is_empty() || self.devices.contains(&client.device);
+        let language = self.languages.is_empty()
+            || client.language.as_deref().is_some_and(|preferred| {
+                self.languages
+                    .iter()
+                    .any(|language| language_matches(language, preferred))
+            });
+        let country = self.countries.is_empty()
+            || client.country.as_deref().is_some_and(|country| {
+                self.countries
+                    .iter()
+                    .any(|code| code.eq_ignore_ascii_case(country))
+            });
+        platform && device && language && country
+    }
+}
+
+/// The first rule matching `client`.
+pub fn select<'a>(rules: &'a [DestinationRule], client: &Client) -> Option<&'a DestinationRule> {
+    rules.iter().find(|rule| rule.matches(client))
+}
+
+/// What the request headers tell about the visitor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Client {
+    pub platform: Option<Platform>,
+    pub device: Device,
+    /// Most preferred `Accept-Language` tag, lowercase.
+    pub language: Option<String>,
+    /// Country of the client IP, when a GeoIP database is configured.
+    pub country: Option<String>,
+}
+
+impl Client {
+    /// Detect the visitor from its headers; client hints win over the User-Agent.
+    pub fn detect<'a>(header: impl Fn(&str) -> Option<&'a str>) -> Client {
+        let user_agent = header("user-agent").unwrap_or_default();
+        let (ua_platform, ua_device) = parse_user_agent(user_agent);
+
+        let platform = header("sec-ch-ua-platform")
+            .and_then(platform_hint)
+            .or(ua_platform);
+        let device = match header("sec-ch-ua-mobile").map(str::trim) {
+            Some("?1") => Device::Mobile,
+            // Chrome on Android tablets reports itself as not mobile
+            Some("?0") if platform == Some(Platform::Android) => Device::Tablet,
+            Some("?0") if ua_device != Device::Tablet => Device::Desktop,
+            _ => ua_device,
+        };
+
+        Client {
+            platform,
+            device,
+            language: header("accept-language").and_then(preferred_language),
+            country: None,
+        }
+    }
+}
+
+/// Platform and device from a User-Agent string.
+pub fn parse_user_agent(user_agent: &str) -> (Option<Platform>, Device) {
+    let ua = user_agent.to_ascii_lowercase();
+    if ua.contains("ipad") {
+        (Some(Platform::Ios), Device::Tablet)
+    } else if ua.contains("iphone") || ua.contains("ipod") {
+        (Some(Platform::Ios), Device::Mobile)
+    } else if ua.contains("android") {
+        // Android phones add "Mobile"; tablets leave it out
+        let device = if ua.contains("mobile") {
+            Device::Mobile
+        } else {
+            Device::Tablet
+        };
+        (Some(Platform::Android), device)
+    } else if ua.contains("windows phone") {
+        (Some(Platform::Windows), Device::Mobile)
+    } else if ua.contains("windows") {
+        (Some(Platform::Windows), Device::Desktop)
+    } else if ua.contains("cros") {
+        (Some(Platform::Chromeos), Device::Desktop)
+    } else if ua.contains("macintosh") || ua.contains("mac os x") {
+        (Some(Platform::Macos), Device::Desktop)
+    } else if ua.contains("linux") || ua.contains("x11") {
+        (Some(Platform::Linux), Device::Desktop)
+    } else {
+        (None, Device::Desktop)
+    }
+}
+
+/// Platform named by a `Sec-CH-UA-Platform` value such as `"macOS"`.
+fn platform_hint(value: &str) -> Option<Platform> {
+    match value.trim().trim_matches('"').to_ascii_lowercase().as_str() {
+        "ios" => Some(Platform::Ios),
+        "android" => Some(Platform::Android),
+        "windows" => Some(Platform::Windows),
+        "macos" => Some(Platform::Macos),
+        "linux" => Some(Platform::Linux),
+        "chrome os" | "chromium os" => Some(Platform::Chromeos),
+        _ => None,
+    }
+}
+
+/// The highest weighted tag of an `Accept-Language` header, lowercase.
+pub fn preferred_language(header: &str) -> Option<String> {
+    header
+        .split(',')
+        .enumerate()
+        .filter_map(|(index, item)| {
+            let mut parts = item.split(';');
+            let tag = parts.next()?.trim().to_ascii_lowercase();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            (!tag.is_empty() && tag != "*" && quality > 0.0).then_some((index, tag, quality))
+        })
+        // Ties keep the header's order
+        .max_by(|a, b| a.2.total_cmp(&b.2).then(b.0.cmp(&a.0)))
+        .map(|(_, tag, _)| tag)
+}
+
+/// Whether the rule language `rule` covers the visitor's `preferred` tag.
+fn language_matches(rule: &str, preferred: &str) -> bool {
+    let rule = rule.trim().to_ascii_lowercase();
+    preferred == rule
+        || preferred
+            .strip_prefix(&rule)
+            .is_some_and(|rest| rest.starts_with('-'))
+}
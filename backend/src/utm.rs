@@ -0,0 +1,187 @@
+use crate::passthrough::{merge_query, QueryConflict};
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
+use url::Url;
+use utoipa::ToSchema;
+
+/// Placeholders a template value may use.
+pub const PLACEHOLDERS: [&str; 4] = ["code", "domain", "campaign", "date"];
+
+/// Longest value a template field may hold.
+pub const MAX_VALUE_LENGTH: usize = 256;
+
+/// UTM parameters added to a destination when a link is resolved.
+///
+/// Values may contain `{code}`, `{domain}`, `{campaign}` and `{date}` (the click's UTC date,
+/// `YYYY-MM-DD`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct UtmTemplate {
+    #[schema(example = "newsletter")]
+    pub source: Option<String>,
+    #[schema(example = "email")]
+    pub medium: Option<String>,
+    #[schema(example = "spring-{date}")]
+    pub campaign: Option<String>,
+    pub term: Option<String>,
+    #[schema(example = "{code}")]
+    pub content: Option<String>,
+}
+
+/// Values substituted for the placeholders.
+#[derive(Debug, Clone, Default)]
+pub struct UtmContext<'a> {
+    pub code: &'a str,
+    pub domain: &'a str,
+    pub campaign: &'a str,
+    pub date: String,
+}
+
+/// Why a template was refused, with the offending field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UtmError {
+    Empty(&'static str),
+    TooLong(&'static str),
+    UnknownPlaceholder(&'static str, String),
+    Unclosed(&'static str),
+}
+
+impl UtmError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            UtmError::Empty(_) => "UTM_EMPTY",
+            UtmError::TooLong(_) => "UTM_TOO_LONG",
+            UtmError::UnknownPlaceholder(..) => "UTM_UNKNOWN_PLACEHOLDER",
+            UtmError::Unclosed(_) => "UTM_UNCLOSED_PLACEHOLDER",
+        }
+    }
+
+    /// The template field at fault, e.g. `source`.
+    pub fn field(&self) -> &'static str {
+        match self {
+            UtmError::Empty(field)
+            | UtmError::TooLong(field)
+            | UtmError::UnknownPlaceholder(field, _)
+            | UtmError::Unclosed(field) => field,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            UtmError::Empty(field) => format!("utm.{} may not be empty", field),
+            UtmError::TooLong(field) => {
+                format!(
+                    "utm.{} is longer than {} characters",
+                    field, MAX_VALUE_LENGTH
+                )
+            }
+            UtmError::UnknownPlaceholder(field, name) => format!(
+                "utm.{} uses the unknown placeholder `{{{}}}`; available are {}",
+                field,
+                name,
+                PLACEHOLDERS.map(|p| format!("{{{}}}", p)).join(", ")
+            ),
+            UtmError::Unclosed(field) => format!("utm.{} has an unclosed `{{`", field),
+        }
+    }
+}
+
+impl UtmTemplate {
+    fn fields(&self) -> [(&'static str, &Option<String>); 5] {
+        [
+            ("source", &self.source),
+            ("medium", &self.medium),
+            ("campaign", &self.campaign),
+            ("term", &self.term),
+            ("content", &self.content),
+        ]
+    }
+
+    /// Fill the fields left unset by `self` from `defaults`, e.g. a campaign's template.
+    pub fn or(self, defaults: &UtmTemplate) -> UtmTemplate {
+        UtmTemplate {
+            source: self.source.or_else(|| defaults.source.clone()),
+            medium: self.medium.or_else(|| defaults.medium.clone()),
+            campaign: self.campaign.or_else(|| defaults.campaign.clone()),
+            term: self.term.or_else(|| defaults.term.clone()),
+            content: self.content.or_else(|| defaults.content.clone()),
+        }
+    }
+
+    /// Check every value is non-empty, short enough and only uses known placeholders.
+    pub fn check(&self) -> Result<(), UtmError> {
+        for (field, value) in self.fields() {
+            let Some(value) = value else {
+                continue;
+            };
+            if value.trim().is_empty() {
+                return Err(UtmError::Empty(field));
+            }
+            if value.chars().count() > MAX_VALUE_LENGTH {
+                return Err(UtmError::TooLong(field));
+            }
+            expand(value, &UtmContext::default()).map_err(|placeholder| match placeholder {
+                Some(name) => UtmError::UnknownPlaceholder(field, name),
+                None => UtmError::Unclosed(field),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// The `utm_*` query parameters for a click.
+    pub fn render(&self, context: &UtmContext) -> Vec<(String, String)> {
+        self.fields()
+            .into_iter()
+            .filter_map(|(field, value)| {
+                let value = expand(value.as_deref()?, context).ok()?;
+                Some((format!("utm_{}", field), value))
+            })
+            .collect()
+    }
+
+    /// Add the rendered parameters to `destination`; parameters already in the URL win.
+    pub fn apply(&self, destination: &str, context: &UtmContext) -> String {
+        let Ok(mut url) = Url::parse(destination) else {
+            return destination.to_string();
+        };
+        let params = self.render(context);
+        if params.is_empty() {
+            return destination.to_string();
+        }
+        let rendered = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params)
+            .finish();
+        let merged = merge_query(
+            url.query().unwrap_or_default(),
+            &rendered,
+            QueryConflict::Keep,
+        );
+        url.set_query(Some(&merged));
+        url.to_string()
+    }
+}
+
+/// Substitute the placeholders of `value`.
+///
+/// Fails with the name of an unknown placeholder, or `None` for an unclosed `{`.
+fn expand(value: &str, context: &UtmContext) -> Result<String, Option<String>> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            return Err(None);
+        };
+        let name = &rest[start + 1..start + end];
+        expanded.push_str(match name {
+            "code" => context.code,
+            "domain" => context.domain,
+            "campaign" => context.campaign,
+            "date" => &context.date,
+            _ => return Err(Some(name.to_string())),
+        });
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
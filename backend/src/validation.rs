@@ -0,0 +1,100 @@
+use url::Url;
+
+/// Schemes that are never accepted, whatever `ALLOWED_URL_SCHEMES` says.
+const FORBIDDEN_SCHEMES: &[&str] = &["javascript", "data", "vbscript"];
+
+/// Rules applied to destination URLs before they are stored.
+#[derive(Debug, Clone)]
+pub struct UrlRules {
+    /// Lowercase scheme names links may point to.
+    pub allowed_schemes: Vec<String>,
+    /// Maximum length of the URL, before and after normalization.
+    pub max_length: usize,
+}
+
+impl Default for UrlRules {
+    fn default() -> Self {
+        UrlRules {
+            allowed_schemes: vec!["http".to_string(), "https".to_string()],
+            max_length: 2048,
+        }
+    }
+}
+
+/// Why a destination URL was refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UrlError {
+    Empty,
+    TooLong(usize),
+    Invalid(String),
+    ForbiddenScheme(String),
+    SchemeNotAllowed(String),
+    MissingHost,
+}
+
+impl UrlError {
+    /// Machine readable error code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            UrlError::Empty => "URL_REQUIRED",
+            UrlError::TooLong(_) => "URL_TOO_LONG",
+            UrlError::Invalid(_) => "URL_INVALID",
+            UrlError::ForbiddenScheme(_) => "URL_SCHEME_FORBIDDEN",
+            UrlError::SchemeNotAllowed(_) => "URL_SCHEME_NOT_ALLOWED",
+            UrlError::MissingHost => "URL_HOST_REQUIRED",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            UrlError::Empty => "long_url is required".to_string(),
+            UrlError::TooLong(max) => format!("long_url must be at most {} characters", max),
+            UrlError::Invalid(reason) => {
+                format!("long_url is not a valid absolute URL: {}", reason)
+            }
+            UrlError::ForbiddenScheme(scheme) => format!("{}: URLs are not allowed", scheme),
+            UrlError::SchemeNotAllowed(scheme) => {
+                format!("Scheme `{}` is not in the allowed list", scheme)
+            }
+            UrlError::MissingHost => "long_url must include a host".to_string(),
+        }
+    }
+}
+
+/// Validate `raw` against `rules` and return its canonical form.
+///
+/// Hosts are IDNA encoded and lowercased, and default ports are dropped, so equivalent
+/// spellings of a URL are stored identically.
+pub fn normalize_long_url(raw: &str, rules: &UrlRules) -> Result<String, UrlError> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Err(UrlError::Empty);
+    }
+    if raw.len() > rules.max_length {
+        return Err(UrlError::TooLong(rules.max_length));
+    }
+
+    let url = Url::parse(raw).map_err(|e| UrlError::Invalid(e.to_string()))?;
+
+    let scheme = url.scheme();
+    if FORBIDDEN_SCHEMES.contains(&scheme) {
+        return Err(UrlError::ForbiddenScheme(scheme.to_string()));
+    }
+    if !rules
+        .allowed_schemes
+        .iter()
+        .any(|allowed| allowed == scheme)
+    {
+        return Err(UrlError::SchemeNotAllowed(scheme.to_string()));
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        return Err(UrlError::MissingHost);
+    }
+
+    // The url crate already lowercases, IDNA encodes and drops default ports while parsing.
+    let normalized = url.to_string();
+    if normalized.len() > rules.max_length {
+        return Err(UrlError::TooLong(rules.max_length));
+    }
+    Ok(normalized)
+}
@@ -0,0 +1,163 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Most variants a single link may split between.
+pub const MAX_VARIANTS: usize = 10;
+
+/// Largest weight of a single variant.
+pub const MAX_WEIGHT: u32 = 1000;
+
+/// Longest variant name.
+pub const MAX_NAME_LENGTH: usize = 32;
+
+/// One destination of a split link.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Variant {
+    /// Identifies the variant in the cookie and the click statistics.
+    #[schema(example = "b")]
+    pub name: String,
+    #[schema(example = "https://example.com/landing-b")]
+    pub long_url: String,
+    /// Relative share of visits, from 1 to 1000.
+    #[serde(default = "default_weight")]
+    #[schema(example = 1)]
+    pub weight: u32,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+/// How a visit is assigned a variant.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitMode {
+    /// Keep each visitor on the variant they first got, using a cookie.
+    #[default]
+    Sticky,
+    /// Draw a variant on every visit.
+    Random,
+}
+
+/// Why a variant list was refused, with the index of the variant at fault.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VariantError {
+    TooMany,
+    InvalidName(usize),
+    DuplicateName(usize),
+    InvalidWeight(usize),
+}
+
+impl VariantError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            VariantError::TooMany => "TOO_MANY_VARIANTS",
+            VariantError::InvalidName(_) => "VARIANT_INVALID_NAME",
+            VariantError::DuplicateName(_) => "VARIANT_DUPLICATE_NAME",
+            VariantError::InvalidWeight(_) => "VARIANT_INVALID_WEIGHT",
+        }
+    }
+
+    /// The request field at fault, e.g. `variants[1].name`.
+    pub fn field(&self) -> String {
+        match self {
+            VariantError::TooMany => "variants".to_string(),
+            VariantError::InvalidName(index) | VariantError::DuplicateName(index) => {
+                format!("variants[{}].name", index)
+            }
+            VariantError::InvalidWeight(index) => format!("variants[{}].weight", index),
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            VariantError::TooMany => {
+                format!("A link may have at most {} variants", MAX_VARIANTS)
+            }
+            VariantError::InvalidName(_) => format!(
+                "Variant names are 1 to {} letters, digits, `_` or `-`",
+                MAX_NAME_LENGTH
+            ),
+            VariantError::DuplicateName(_) => "Variant names must be unique".to_string(),
+            VariantError::InvalidWeight(_) => {
+                format!("Weights range from 1 to {}", MAX_WEIGHT)
+            }
+        }
+    }
+}
+
+/// Check names and weights; destinations are validated like `long_url` by the caller.
+pub fn check(variants: &[Variant]) -> Result<(), VariantError> {
+    if variants.len() > MAX_VARIANTS {
+        return Err(VariantError::TooMany);
+    }
+    for (index, variant) in variants.iter().enumerate() {
+        let valid_name = (1..=MAX_NAME_LENGTH).contains(&variant.name.len())
+            && variant
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid_name {
+            return Err(VariantError::InvalidName(index));
+        }
+        if variants[..index].iter().any(|v| v.name == variant.name) {
+            return Err(VariantError::DuplicateName(index));
+        }
+        if !(1..=MAX_WEIGHT).contains(&variant.weight) {
+            return Err(VariantError::InvalidWeight(index));
+        }
+    }
+    Ok(())
+}
+
+/// The variant with the given name, if the link still has it.
+pub fn find<'a>(variants: &'a [Variant], name: &str) -> Option<&'a Variant> {
+    variants.iter().find(|variant| variant.name == name)
+}
+
+/// Draw a variant in proportion to the weights; `roll` is uniform in `0..total weight`.
+pub fn pick(variants: &[Variant], mut roll: u32) -> Option<&Variant> {
+    variants.iter().find(|variant| {
+        if roll < variant.weight {
+            true
+        } else {
+            roll -= variant.weight;
+            false
+        }
+    })
+}
+
+/// Draw a variant in proportion to the weights.
+pub fn pick_random(variants: &[Variant]) -> Option<&Variant> {
+    let total: u32 = variants.iter().map(|variant| variant.weight).sum();
+    if total == 0 {
+        return None;
+    }
+    pick(variants, rand::thread_rng().gen_range(0..total))
+}
+
+/// Cookie remembering the variant a visitor got for the link `code`.
+pub fn cookie_name(code: &str) -> String {
+    format!("rs_variant_{}", code)
+}
+
+/// The value of cookie `name` in a `Cookie` header.
+pub fn read_cookie<'a>(header: &'a str, name: &str) -> Option<&'a str> {
+    header.split(';').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key.trim() == name).then(|| value.trim())
+    })
+}
+
+/// `Set-Cookie` value keeping the visitor on `variant` for `max_age` seconds.
+pub fn set_cookie(code: &str, variant: &str, max_age: u64, secure: bool) -> String {
+    format!(
+        "{}={}; Max-Age={}; Path=/; HttpOnly; SameSite=Lax{}",
+        cookie_name(code),
+        variant,
+        max_age,
+        if secure { "; Secure" } else { "" }
+    )
+}
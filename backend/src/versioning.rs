@@ -0,0 +1,92 @@
+use chrono::{NaiveDate, NaiveTime};
+use once_cell::sync::Lazy;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use warp::http::header::{HeaderName, HeaderValue};
+use warp::{Filter, Reply};
+
+static LEGACY_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "legacy_route_requests_total",
+        "Requests served on deprecated unversioned routes",
+        &["route"]
+    )
+    .unwrap()
+});
+
+/// When the unversioned routes were deprecated and when they will be removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeprecationPolicy {
+    pub deprecated_on: NaiveDate,
+    pub sunset_on: Option<NaiveDate>,
+}
+
+impl Default for DeprecationPolicy {
+    fn default() -> Self {
+        DeprecationPolicy {
+            deprecated_on: NaiveDate::from_ymd_opt(2026, 10, 19).unwrap(),
+            sunset_on: NaiveDate::from_ymd_opt(2027, 4, 30),
+        }
+    }
+}
+
+impl DeprecationPolicy {
+    /// `Deprecation` header value (RFC 9745): `@` followed by a Unix timestamp.
+    pub fn deprecation_header(&self) -> String {
+        format!(
+            "@{}",
+            self.deprecated_on
+                .and_time(NaiveTime::MIN)
+                .and_utc()
+                .timestamp()
+        )
+    }
+
+    /// `Sunset` header value (RFC 8594): an HTTP date.
+    pub fn sunset_header(&self) -> Option<String> {
+        self.sunset_on.map(|date| {
+            date.and_time(NaiveTime::MIN)
+                .and_utc()
+                .format("%a, %d %b %Y %H:%M:%S GMT")
+                .to_string()
+        })
+    }
+
+    /// Mark `reply` as served by a deprecated route, linking to its `successor` if any.
+    pub fn apply(&self, reply: impl Reply, successor: Option<&str>) -> warp::reply::Response {
+        let mut response = reply.into_response();
+        let headers = response.headers_mut();
+        let mut insert = |name: &'static str, value: String| {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(HeaderName::from_static(name), value);
+            }
+        };
+        insert("deprecation", self.deprecation_header());
+        if let Some(sunset) = self.sunset_header() {
+            insert("sunset", sunset);
+        }
+        if let Some(successor) = successor {
+            insert(
+                "link",
+                format!("<{}>; rel=\"successor-version\"", successor),
+            );
+        }
+        response
+    }
+}
+
+/// Serve `filter` as a deprecated alias of `successor`, counting every request it answers.
+pub fn legacy<F, R>(
+    filter: F,
+    route: &'static str,
+    successor: Option<&'static str>,
+    policy: DeprecationPolicy,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = warp::Rejection> + Clone
+where
+    F: Filter<Extract = (R,), Error = warp::Rejection> + Clone,
+    R: Reply,
+{
+    filter.map(move |reply: R| {
+        LEGACY_REQUESTS.with_label_values(&[route]).inc();
+        policy.apply(reply, successor)
+    })
+}
@@ -0,0 +1,64 @@
+use url_shortener::alias::{AliasCase, AliasError, AliasRules, Charset};
+use url_shortener::routes::ROUTE_NAMES;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_charset_spec() {
+        let charset: Charset = "a-c0-9-".parse().unwrap();
+        assert!(charset.contains('b'));
+        assert!(charset.contains('7'));
+        assert!(charset.contains('-'));
+        assert!(!charset.contains('d'));
+
+        assert!("a-z/".parse::<Charset>().is_err());
+        assert!("z-a".parse::<Charset>().is_err());
+        assert!("".parse::<Charset>().is_err());
+    }
+
+    #[test]
+    fn test_default_rules() {
+        let rules = AliasRules::default();
+
+        assert_eq!(
+            rules.check("Spring-Sale_2024"),
+            Ok("Spring-Sale_2024".to_string())
+        );
+        assert_eq!(rules.check("ab"), Err(AliasError::TooShort(3)));
+        assert_eq!(rules.check(&"a".repeat(65)), Err(AliasError::TooLong(64)));
+        assert_eq!(rules.check("a/b/c"), Err(AliasError::InvalidChars('/')));
+        assert_eq!(rules.check("two words"), Err(AliasError::InvalidChars(' ')));
+        assert_eq!(rules.check("café"), Err(AliasError::InvalidChars('é')));
+        assert_eq!(
+            rules.check("Admin"),
+            Err(AliasError::Reserved("admin".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_route_names_are_always_reserved() {
+        let rules = AliasRules {
+            reserved: Vec::new(),
+            ..AliasRules::default()
+        };
+
+        for route in ROUTE_NAMES {
+            let error = rules.check(route).unwrap_err();
+            assert_eq!(error.code(), "ALIAS_RESERVED", "{}", route);
+        }
+        assert!(rules.check("PING").is_err());
+    }
+
+    #[test]
+    fn test_case_policy() {
+        let rules = AliasRules {
+            case: AliasCase::Insensitive,
+            ..AliasRules::default()
+        };
+        assert_eq!(rules.check("Promo"), Ok("promo".to_string()));
+        assert_eq!("INSENSITIVE".parse(), Ok(AliasCase::Insensitive));
+        assert!("lower".parse::<AliasCase>().is_err());
+    }
+}
@@ -0,0 +1,304 @@
+use url_shortener::auth::{
+    sign_request, ApiKeyEntry, AuthError, Authenticator, Credentials, JwtValidator, SignedRequest,
+    SCOPE_LINKS_WRITE,
+};
+use url_shortener::config::{Config, JwtConfig};
+
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use std::path::PathBuf;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+fn jwt_config() -> JwtConfig {
+    JwtConfig {
+        hs256_secret_file: Some(fixture("jwt_hs256.secret")),
+        rs256_public_key_file: None,
+        jwks_file: Some(fixture("jwks.json")),
+        issuer: Some("https://sso.example.com".to_string()),
+        audience: Some("url-shortener".to_string()),
+        owner_claim: "sub".to_string(),
+        scopes_claim: "scope".to_string(),
+    }
+}
+
+// Write an API keys file to a unique temporary file
+fn temp_keys(contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "url_shortener_auth_keys_{}_{}.json",
+        std::process::id(),
+        chrono::Utc::now().timestamp_nanos_opt().unwrap()
+    ));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn claims(scope: serde_json::Value, exp_offset: i64) -> serde_json::Value {
+    serde_json::json!({
+        "sub": "alice",
+        "iss": "https://sso.example.com",
+        "aud": "url-shortener",
+        "exp": chrono::Utc::now().timestamp() + exp_offset,
+        "scope": scope,
+    })
+}
+
+fn hs256_token(claims: &serde_json::Value) -> String {
+    let secret = std::fs::read(fixture("jwt_hs256.secret")).unwrap();
+    let key = EncodingKey::from_secret(secret.trim_ascii());
+    encode(&Header::new(Algorithm::HS256), claims, &key).unwrap()
+}
+
+fn rs256_token(claims: &serde_json::Value, kid: Option<&str>) -> String {
+    let pem = std::fs::read(fixture("jwt_rs256_private.pem")).unwrap();
+    let key = EncodingKey::from_rsa_pem(&pem).unwrap();
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = kid.map(str::to_string);
+    encode(&header, claims, &key).unwrap()
+}
+
+fn es256_token(claims: &serde_json::Value, kid: Option<&str>) -> String {
+    let pem = std::fs::read(fixture("jwt_es256_private.pem")).unwrap();
+    let key = EncodingKey::from_ec_pem(&pem).unwrap();
+    let mut header = Header::new(Algorithm::ES256);
+    header.kid = kid.map(str::to_string);
+    encode(&header, claims, &key).unwrap()
+}
+
+// The fixture JWKS with only its EC key, whose fields are replaced by `overrides`
+fn ec_jwks(overrides: serde_json::Value) -> PathBuf {
+    let jwks: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(fixture("jwks.json")).unwrap()).unwrap();
+    let mut key = jwks["keys"][1].clone();
+    for (field, value) in overrides.as_object().unwrap() {
+        match value {
+            serde_json::Value::Null => key.as_object_mut().unwrap().remove(field),
+            value => key
+                .as_object_mut()
+                .unwrap()
+                .insert(field.clone(), value.clone()),
+        };
+    }
+    temp_keys(&serde_json::json!({ "keys": [key] }).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn authenticator() -> Authenticator {
+        let keys = vec![ApiKeyEntry {
+            owner: "marketing".to_string(),
+            key: "marketing-key".to_string(),
+            scopes: vec![SCOPE_LINKS_WRITE.to_string()],
+            hmac_secret: Some("widget-secret".to_string()),
+            ..ApiKeyEntry::default()
+        }];
+        let jwt = JwtValidator::from_config(&jwt_config()).unwrap();
+        Authenticator::new(keys, Some(jwt))
+    }
+
+    #[test]
+    fn test_api_key_maps_to_owner() {
+        let auth = authenticator();
+
+        let principal = auth
+            .authenticate(&Credentials::ApiKey("marketing-key".to_string()))
+            .unwrap();
+        assert_eq!(principal.owner, "marketing");
+        assert!(principal.has_scope(SCOPE_LINKS_WRITE));
+
+        let result = auth.authenticate(&Credentials::ApiKey("wrong".to_string()));
+        assert_eq!(result, Err(AuthError::InvalidApiKey));
+
+        let result = auth.authenticate(&Credentials::Missing);
+        assert_eq!(result, Err(AuthError::MissingCredentials));
+    }
+
+    #[test]
+    fn test_hs256_token_claims_to_principal() {
+        let auth = authenticator();
+        let token = hs256_token(&claims(serde_json::json!("links:write links:read"), 300));
+
+        let principal = auth.authenticate(&Credentials::Bearer(token)).unwrap();
+        assert_eq!(principal.owner, "alice");
+        assert_eq!(principal.scopes, vec!["links:write", "links:read"]);
+    }
+
+    #[test]
+    fn test_rs256_token_from_jwks() {
+        let auth = authenticator();
+        let token = rs256_token(
+            &claims(serde_json::json!(["links:read"]), 300),
+            Some("test-key-1"),
+        );
+
+        let principal = auth.authenticate(&Credentials::Bearer(token)).unwrap();
+        assert_eq!(principal.owner, "alice");
+        assert!(!principal.has_scope(SCOPE_LINKS_WRITE));
+
+        // A kid that is not in the JWKS document is rejected.
+        let token = rs256_token(&claims(serde_json::json!([]), 300), Some("unknown"));
+        assert!(auth.authenticate(&Credentials::Bearer(token)).is_err());
+    }
+
+    #[test]
+    fn test_es256_token_from_jwks() {
+        let auth = authenticator();
+        let token = es256_token(
+            &claims(serde_json::json!("links:write"), 300),
+            Some("test-key-2"),
+        );
+        let principal = auth.authenticate(&Credentials::Bearer(token)).unwrap();
+        assert!(principal.has_scope(SCOPE_LINKS_WRITE));
+
+        // The RSA key's kid does not verify an ES256 token.
+        let token = es256_token(&claims(serde_json::json!([]), 300), Some("test-key-1"));
+        assert!(auth.authenticate(&Credentials::Bearer(token)).is_err());
+
+        // Without `alg`, the algorithm follows the key type and curve.
+        let jwks = ec_jwks(serde_json::json!({ "alg": null }));
+        let config = JwtConfig {
+            hs256_secret_file: None,
+            jwks_file: Some(jwks.clone()),
+            ..jwt_config()
+        };
+        let validator = JwtValidator::from_config(&config).unwrap();
+        std::fs::remove_file(&jwks).unwrap();
+        let token = es256_token(&claims(serde_json::json!([]), 300), None);
+        assert_eq!(validator.validate(&token).unwrap().owner, "alice");
+        // Only the configured algorithms are accepted.
+        let token = hs256_token(&claims(serde_json::json!([]), 300));
+        assert!(validator.validate(&token).is_err());
+    }
+
+    #[test]
+    fn test_jwks_rejects_unsupported_keys() {
+        for overrides in [
+            serde_json::json!({ "alg": "RSA-OAEP" }),
+            serde_json::json!({ "alg": null, "crv": "P-521" }),
+        ] {
+            let jwks = ec_jwks(overrides.clone());
+            let config = JwtConfig {
+                jwks_file: Some(jwks.clone()),
+                ..jwt_config()
+            };
+            let result = JwtValidator::from_config(&config);
+            std::fs::remove_file(&jwks).unwrap();
+            let error = result.err().expect("unsupported key was loaded");
+            assert!(error.starts_with("Unsupported key in"), "{}", overrides);
+        }
+    }
+
+    #[test]
+    fn test_rs256_token_from_pem() {
+        let config = JwtConfig {
+            rs256_public_key_file: Some(fixture("jwt_rs256_public.pem")),
+            hs256_secret_file: None,
+            jwks_file: None,
+            ..jwt_config()
+        };
+        let validator = JwtValidator::from_config(&config).unwrap();
+
+        let token = rs256_token(&claims(serde_json::json!("links:write"), 300), None);
+        let principal = validator.validate(&token).unwrap();
+        assert!(principal.has_scope(SCOPE_LINKS_WRITE));
+    }
+
+    #[test]
+    fn test_invalid_tokens_are_rejected() {
+        let auth = authenticator();
+
+        let expired = hs256_token(&claims(serde_json::json!("links:write"), -3600));
+        assert!(auth.authenticate(&Credentials::Bearer(expired)).is_err());
+
+        let mut wrong_audience = claims(serde_json::json!("links:write"), 300);
+        wrong_audience["aud"] = serde_json::json!("another-service");
+        let token = hs256_token(&wrong_audience);
+        assert!(auth.authenticate(&Credentials::Bearer(token)).is_err());
+
+        let tampered = format!("{}x", hs256_token(&claims(serde_json::json!(""), 300)));
+        assert!(auth.authenticate(&Credentials::Bearer(tampered)).is_err());
+    }
+
+    fn signed(timestamp: i64, nonce: &str, body: &[u8]) -> SignedRequest {
+        let timestamp = timestamp.to_string();
+        SignedRequest {
+            key_id: "marketing".to_string(),
+            signature: sign_request(b"widget-secret", &timestamp, nonce, body),
+            timestamp,
+            nonce: nonce.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_signed_request_verification() {
+        let auth = authenticator();
+        let body = br#"{"long_url":"https://example.com"}"#;
+        let now = 1_700_000_000;
+
+        let principal = auth
+            .verify_signature(&signed(now, "n-1", body), body, now + 10)
+            .unwrap();
+        assert_eq!(principal.owner, "marketing");
+
+        // Any change to the body invalidates the signature.
+        let tampered = br#"{"long_url":"https://evil.example"}"#;
+        let result = auth.verify_signature(&signed(now, "n-1", body), tampered, now);
+        assert!(matches!(result, Err(AuthError::InvalidSignature(_))));
+
+        // Stale timestamps are rejected even with a valid signature.
+        let result = auth.verify_signature(&signed(now - 3600, "n-1", body), body, now);
+        assert!(matches!(result, Err(AuthError::InvalidSignature(_))));
+
+        let mut unknown = signed(now, "n-1", body);
+        unknown.key_id = "someone-else".to_string();
+        assert!(auth.verify_signature(&unknown, body, now).is_err());
+    }
+
+    #[test]
+    fn test_configured_signature_skew() {
+        let keys = temp_keys(
+            r#"[{"owner": "marketing", "key": "marketing-key", "scopes": ["links:write"],
+                "hmac_secret": "widget-secret"}]"#,
+        );
+        let from_config = |skew| {
+            Authenticator::from_config(&Config {
+                api_key: "shared".to_string(),
+                api_keys_file: Some(keys.clone()),
+                signature_max_skew: skew,
+                ..Config::default()
+            })
+            .unwrap()
+        };
+        let body = b"{}";
+        let now = 1_700_000_000;
+
+        let strict = from_config(60);
+        assert!(strict
+            .verify_signature(&signed(now - 30, "n-1", body), body, now)
+            .is_ok());
+        assert!(strict
+            .verify_signature(&signed(now - 120, "n-1", body), body, now)
+            .is_err());
+
+        let lenient = from_config(7200);
+        assert!(lenient
+            .verify_signature(&signed(now - 3600, "n-1", body), body, now)
+            .is_ok());
+        std::fs::remove_file(&keys).unwrap();
+    }
+
+    #[test]
+    fn test_signed_credentials_need_the_body() {
+        let auth = authenticator();
+        let credentials = Credentials::Signed(signed(0, "n-1", b"{}"));
+        assert!(matches!(
+            auth.authenticate(&credentials),
+            Err(AuthError::InvalidSignature(_))
+        ));
+    }
+}
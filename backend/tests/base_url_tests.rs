@@ -0,0 +1,108 @@
+use url_shortener::base_url::{short_link, PublicBaseUrl};
+use url_shortener::config::Config;
+
+// A deployment serving two brands besides its primary host
+fn multi_domain_config() -> Config {
+    Config {
+        public_base_url: "https://sho.rt/go".parse().unwrap(),
+        short_domains: vec!["go.brand-a.com".to_string(), "brand-b.link".to_string()],
+        ..Config::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_base_urls() {
+        let base: PublicBaseUrl = "https://Sho.RT".parse().unwrap();
+        assert_eq!(base.host(), "sho.rt");
+        assert!(base.prefix().is_empty());
+        assert_eq!(base.short_url("abc1234"), "https://sho.rt/abc1234");
+
+        let base: PublicBaseUrl = "https://example.com:8443/go/".parse().unwrap();
+        assert_eq!(base.prefix(), ["go".to_string()]);
+        assert_eq!(base.to_string(), "https://example.com:8443/go");
+        assert_eq!(base.short_url("promo"), "https://example.com:8443/go/promo");
+
+        assert!("ftp://sho.rt".parse::<PublicBaseUrl>().is_err());
+        assert!("https://sho.rt/?q=1".parse::<PublicBaseUrl>().is_err());
+        assert!("sho.rt".parse::<PublicBaseUrl>().is_err());
+    }
+
+    #[test]
+    fn test_extract_short_codes() {
+        let root: PublicBaseUrl = "https://sho.rt".parse().unwrap();
+        assert_eq!(root.short_code("/abc1234"), Some("abc1234"));
+        assert_eq!(root.short_code("/"), None);
+        assert_eq!(root.short_code("/abc/def"), None);
+
+        let prefixed: PublicBaseUrl = "https://example.com/go".parse().unwrap();
+        assert_eq!(prefixed.short_code("/go/abc1234"), Some("abc1234"));
+        assert_eq!(prefixed.short_code("/abc1234"), None);
+        assert_eq!(prefixed.short_code("/go/"), None);
+
+        // The rest of the path is kept for passthrough
+        assert_eq!(root.short_link("/abc/def/"), Some(("abc", "def/")));
+        assert_eq!(
+            prefixed.short_link("/go/docs/intro"),
+            Some(("docs", "intro"))
+        );
+        assert_eq!(prefixed.short_link("/go/docs"), Some(("docs", "")));
+        assert_eq!(prefixed.short_link("/gone/docs"), None);
+    }
+
+    #[test]
+    fn test_domain_namespaces() {
+        let config = multi_domain_config();
+
+        assert_eq!(
+            config.domain_for_host(Some("Go.Brand-A.com:443")),
+            "go.brand-a.com"
+        );
+        assert_eq!(config.domain_for_host(Some("brand-b.link")), "brand-b.link");
+        assert_eq!(config.domain_for_host(Some("localhost:15555")), "sho.rt");
+        assert_eq!(config.domain_for_host(None), "sho.rt");
+
+        // Aliases are unique per domain; the primary host keeps bare keys
+        assert_eq!(config.link_key("sho.rt", "promo"), "promo");
+        assert_eq!(
+            config.link_key("go.brand-a.com", "promo"),
+            "go.brand-a.com/promo"
+        );
+        assert_ne!(
+            config.link_key("go.brand-a.com", "promo"),
+            config.link_key("brand-b.link", "promo")
+        );
+
+        assert_eq!(
+            config.short_url("brand-b.link", "promo"),
+            "https://brand-b.link/go/promo"
+        );
+        assert_eq!(
+            config.short_url("sho.rt", "promo"),
+            "https://sho.rt/go/promo"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_short_link_filter() {
+        let filter = short_link("https://example.com/go".parse().unwrap());
+
+        let link = warp::test::request()
+            .path("/go/promo/spring?x=1")
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(link, ("promo".to_string(), "spring".to_string()));
+        assert!(!warp::test::request().path("/promo").matches(&filter).await);
+        // Route names are never short codes
+        assert!(
+            !warp::test::request()
+                .path("/go/api/v1")
+                .matches(&filter)
+                .await
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use url_shortener::click_limits::{self, MAX_CLICKS};
+use url_shortener::db::Database;
+use url_shortener::models::{parse_body, CreateLinkRequest};
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+// Initialize a test Redis database connection
+async fn init_test_db() -> Database {
+    let client = redis::Client::open("redis://127.0.0.1/").expect("Failed to create Redis client");
+    let connection = client
+        .get_multiplexed_async_connection()
+        .await
+        .expect("Failed to connect to Redis");
+    Arc::new(Mutex::new(connection))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_max_clicks() {
+        assert!(click_limits::check(1).is_ok());
+        assert!(click_limits::check(MAX_CLICKS).is_ok());
+        assert!(click_limits::check(0).is_err());
+        assert!(click_limits::check(MAX_CLICKS + 1).is_err());
+
+        let request: CreateLinkRequest =
+            parse_body(br#"{"long_url": "https://example.com/file.zip", "max_clicks": 1}"#)
+                .unwrap();
+        assert_eq!(request.options.max_clicks, Some(1));
+
+        let error = parse_body::<CreateLinkRequest>(
+            br#"{"long_url": "https://example.com", "max_clicks": -1}"#,
+        )
+        .unwrap_err();
+        assert_eq!(error.errors[0].field, "max_clicks");
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_consume_never_overshoots() {
+        let db = init_test_db().await;
+        let key = format!(
+            "click-limit-test-{}",
+            chrono::Utc::now().timestamp_nanos_opt().unwrap()
+        );
+        click_limits::init(db.clone(), &key, 5, 60).await.unwrap();
+
+        let visits = (0..20).map(|_| {
+            let db = db.clone();
+            let key = key.clone();
+            tokio::spawn(async move { click_limits::consume(db, &key).await.unwrap() })
+        });
+        let mut served = Vec::new();
+        for visit in visits {
+            if let Some(left) = visit.await.unwrap() {
+                served.push(left);
+            }
+        }
+        served.sort_unstable();
+        assert_eq!(served, [0, 1, 2, 3, 4]);
+        assert_eq!(click_limits::remaining(db.clone(), &key).await.unwrap(), 0);
+
+        // A link whose counter is gone is treated as exhausted
+        assert_eq!(
+            click_limits::consume(db, "missing-counter").await.unwrap(),
+            None
+        );
+    }
+}
@@ -23,8 +23,8 @@ mod tests {
     #[tokio::test]
     async fn test_concurrent_store_and_retrieve() {
         let db = init_test_db().await;
-        let short_url_ids = vec!["test_key1", "test_key2", "test_key3"];
-        let long_urls = vec![
+        let short_url_ids = ["test_key1", "test_key2", "test_key3"];
+        let long_urls = [
             "http://example.com/url1",
             "http://example.com/url2",
             "http://example.com/url3",
@@ -43,6 +43,7 @@ mod tests {
                     shortened_url: format!("http://localhost/{}", short_url_id),
                     long_url,
                     ttl: 30,
+                    ..Default::default()
                 };
 
                 // Store data in Redis
@@ -75,7 +76,7 @@ mod tests {
     #[tokio::test]
     async fn test_concurrent_ttl() {
         let db = init_test_db().await;
-        let short_url_ids = vec!["ttl_key1", "ttl_key2", "ttl_key3"];
+        let short_url_ids = ["ttl_key1", "ttl_key2", "ttl_key3"];
 
         let mut handles = vec![];
 
@@ -89,6 +90,7 @@ mod tests {
                     shortened_url: format!("http://localhost/{}", short_url_id),
                     long_url: "http://example.com/some/long/url".to_string(),
                     ttl: 2,
+                    ..Default::default()
                 };
 
                 // Store data in Redis
@@ -120,7 +122,7 @@ mod tests {
     #[tokio::test]
     async fn test_concurrent_delete() {
         let db = init_test_db().await;
-        let short_url_ids = vec!["delete_key1", "delete_key2", "delete_key3"];
+        let short_url_ids = ["delete_key1", "delete_key2", "delete_key3"];
 
         let mut handles = vec![];
 
@@ -134,6 +136,7 @@ mod tests {
                     shortened_url: format!("http://localhost/{}", short_url_id),
                     long_url: "http://example.com/some/long/url".to_string(),
                     ttl: 30,
+                    ..Default::default()
                 };
 
                 // Store data in Redis
@@ -158,4 +161,29 @@ mod tests {
             handle.await.unwrap();
         }
     }
+
+    // Test that concurrent claims of one key let a single link through
+    #[tokio::test]
+    async fn test_concurrent_claim() {
+        let db = init_test_db().await;
+        let short_url_id = format!("test_claim_{}", std::process::id());
+        delete_data(db.clone(), &short_url_id).await.unwrap();
+
+        let handles: Vec<_> = (0..5)
+            .map(|_| {
+                let db = db.clone();
+                let short_url_id = short_url_id.clone();
+                task::spawn(async move { claim_key(db, &short_url_id, 30).await.unwrap() })
+            })
+            .collect();
+        let mut claimed = 0;
+        for handle in handles {
+            claimed += handle.await.unwrap() as usize;
+        }
+        assert_eq!(claimed, 1);
+
+        // The placeholder reads as missing until the link is stored
+        assert!(retrieve_data(db.clone(), &short_url_id).await.is_none());
+        delete_data(db, &short_url_id).await.unwrap();
+    }
 }
@@ -0,0 +1,105 @@
+use url_shortener::domain_policy::{
+    DomainDenied, DomainList, DomainPolicy, DomainPolicyStore, DomainRule,
+};
+
+use std::path::PathBuf;
+
+// Write a domain list to a unique temporary file
+fn temp_list(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "url_shortener_{}_{}_{}.txt",
+        name,
+        std::process::id(),
+        chrono::Utc::now().timestamp_nanos_opt().unwrap()
+    ));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_kinds() {
+        let exact = DomainRule::parse("Example.com").unwrap();
+        assert!(exact.matches("example.com"));
+        assert!(!exact.matches("www.example.com"));
+
+        let suffix = DomainRule::parse(".example.com").unwrap();
+        assert!(suffix.matches("example.com"));
+        assert!(suffix.matches("login.example.com"));
+        assert!(!suffix.matches("badexample.com"));
+
+        let wildcard = DomainRule::parse("paypa*.*").unwrap();
+        assert!(wildcard.matches("paypal-secure.com"));
+        assert!(wildcard.matches("paypa1.net"));
+        assert!(!wildcard.matches("www.paypal.com"));
+
+        // Unicode rules match the IDNA form stored for links.
+        let idn = DomainRule::parse("bücher.example").unwrap();
+        assert!(idn.matches("xn--bcher-kva.example"));
+    }
+
+    #[test]
+    fn test_list_file_format() {
+        let list = DomainList::parse(
+            "# phishing\nevil.com\n\n.tracker.net   # and subdomains\n*.competitor.*\n",
+        )
+        .unwrap();
+
+        assert!(list.find("EVIL.com.").is_some());
+        assert!(list.find("ads.tracker.net").is_some());
+        assert!(list.find("shop.competitor.io").is_some());
+        assert!(list.find("example.org").is_none());
+    }
+
+    #[test]
+    fn test_blocklist_wins_over_allowlist() {
+        let policy = DomainPolicy {
+            blocklist: DomainList::parse("evil.example.com").unwrap(),
+            allowlist: DomainList::parse(".example.com").unwrap(),
+        };
+
+        assert_eq!(policy.check("docs.example.com"), Ok(()));
+        assert!(matches!(
+            policy.check("evil.example.com"),
+            Err(DomainDenied::Blocked(_))
+        ));
+        assert_eq!(policy.check("example.org"), Err(DomainDenied::NotAllowed));
+
+        let (host, _) = policy
+            .check_url("https://Evil.Example.com/login")
+            .unwrap_err();
+        assert_eq!(host, "evil.example.com");
+    }
+
+    #[test]
+    fn test_store_reloads_changed_files() {
+        let blocklist = temp_list("blocklist", "evil.com\n");
+        let store = DomainPolicyStore::new(Some(blocklist.clone()), None);
+
+        assert!(store.current().check("evil.com").is_ok());
+        store.reload().unwrap();
+        assert!(store.current().check("evil.com").is_err());
+        assert_eq!(store.reload_if_changed(), Ok(false));
+
+        std::fs::write(&blocklist, "evil.com\nworse.com\n").unwrap();
+        let modified = std::time::SystemTime::now() + std::time::Duration::from_secs(5);
+        std::fs::File::options()
+            .write(true)
+            .open(&blocklist)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        assert_eq!(store.reload_if_changed(), Ok(true));
+        assert!(store.current().check("worse.com").is_err());
+
+        // A broken file keeps the previous policy in place.
+        std::fs::write(&blocklist, "bad domain with spaces\n").unwrap();
+        assert!(store.reload().is_err());
+        assert!(store.current().check("worse.com").is_err());
+
+        std::fs::remove_file(blocklist).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+use std::path::PathBuf;
+use url_shortener::fallback::{self, DomainFallback};
+use url_shortener::models::{parse_body, CreateLinkRequest};
+use url_shortener::pages::{prefers_html, PageTemplate};
+use warp::http::StatusCode;
+
+const BROWSER_ACCEPT: &str =
+    "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8";
+
+// Write a page template to a unique temporary file
+fn temp_template(contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "url_shortener_template_{}_{}.html",
+        std::process::id(),
+        chrono::Utc::now().timestamp_nanos_opt().unwrap()
+    ));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_domain_fallbacks() {
+        let fallbacks: Vec<DomainFallback> = [
+            "Sho.rt=https://example.com/",
+            "go.example.com = http://example.com/links",
+        ]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect();
+        assert_eq!(fallbacks[0].domain, "sho.rt");
+        assert_eq!(
+            fallback::for_domain(&fallbacks, "sho.rt"),
+            Some("https://example.com/")
+        );
+        assert_eq!(
+            fallback::for_domain(&fallbacks, "go.example.com"),
+            Some("http://example.com/links")
+        );
+        assert_eq!(fallback::for_domain(&fallbacks, "other.example"), None);
+
+        for invalid in [
+            "https://example.com/",
+            "sho.rt=",
+            "=https://example.com/",
+            "sho.rt=ftp://example.com/",
+            "sho.rt=not a url",
+        ] {
+            assert!(invalid.parse::<DomainFallback>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_prefers_html() {
+        assert!(prefers_html(BROWSER_ACCEPT));
+        assert!(prefers_html("text/html"));
+        assert!(prefers_html("text/*"));
+        assert!(prefers_html("TEXT/HTML; q=0.9, application/json;q=0.5"));
+
+        // curl and most HTTP libraries
+        assert!(!prefers_html("*/*"));
+        assert!(!prefers_html(""));
+        assert!(!prefers_html("application/json"));
+        assert!(!prefers_html("application/problem+json, text/html;q=0.5"));
+        assert!(!prefers_html("text/html, application/json"));
+        assert!(!prefers_html("text/html;q=0, */*"));
+        assert!(!prefers_html("text/html;q=abc"));
+    }
+
+    #[test]
+    fn test_default_page() {
+        let page = PageTemplate::default().render(
+            StatusCode::NOT_FOUND,
+            "Link not found",
+            "Short URL <not> found",
+        );
+        assert!(page.contains("<title>Link not found</title>"));
+        assert!(page.contains("<p>Short URL &lt;not&gt; found</p>"));
+        assert!(!page.contains("{{"));
+    }
+
+    #[test]
+    fn test_custom_template() {
+        let path = temp_template(
+            "<html><h1>{{status}} {{title}}</h1><main>{{message}}</main><p>{{message}}</p></html>",
+        );
+        let template = PageTemplate::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let page = template.render(StatusCode::GONE, "Gone & done", "\"Bye\"");
+        assert_eq!(
+            page,
+            "<html><h1>410 Gone &amp; done</h1><main>&quot;Bye&quot;</main>\
+             <p>&quot;Bye&quot;</p></html>"
+        );
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        assert!(PageTemplate::new("<h1>{{title}}</h1>".to_string()).is_err());
+
+        let missing = std::env::temp_dir().join("url_shortener_no_such_template.html");
+        let error = PageTemplate::load(&missing).unwrap_err();
+        assert!(error.contains("url_shortener_no_such_template.html"));
+    }
+
+    #[test]
+    fn test_expired_url_in_requests() {
+        let request: CreateLinkRequest = parse_body(
+            br#"{"long_url": "https://example.com/spring-sale",
+                "expired_url": "https://example.com/sales"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            request.options.expired_url.as_deref(),
+            Some("https://example.com/sales")
+        );
+    }
+}
@@ -0,0 +1,105 @@
+#!/usr/bin/env python3
+"""Write geoip_country_test.mmdb, a tiny MaxMind DB with GeoIP2-Country records.
+
+Run from this directory after changing NETWORKS; the tests only read the output file.
+"""
+import ipaddress
+import struct
+
+NETWORKS = {
+    "2.0.0.0/16": "FR",
+    "81.2.69.0/24": "GB",
+    "89.160.20.0/24": "SE",
+    "175.16.199.0/24": "CN",
+    "216.160.83.0/24": "US",
+    "2a01:e00::/26": "FR",
+    "2001:480::/32": "US",
+}
+NAMES = {"FR": "France", "GB": "United Kingdom", "SE": "Sweden", "CN": "China", "US": "United States"}
+
+
+def control(type_, size):
+    if type_ <= 7:
+        first, extended = type_ << 5, b""
+    else:
+        first, extended = 0, bytes([type_ - 7])
+    if size < 29:
+        return bytes([first | size]) + extended
+    if size < 285:
+        return bytes([first | 29]) + extended + bytes([size - 29])
+    return bytes([first | 30]) + extended + struct.pack(">H", size - 285)
+
+
+def encode(value):
+    if isinstance(value, str):
+        raw = value.encode()
+        return control(2, len(raw)) + raw
+    if isinstance(value, dict):
+        out = control(7, len(value))
+        for key, item in value.items():
+            out += encode(key) + encode(item)
+        return out
+    if isinstance(value, list):
+        return control(11, len(value)) + b"".join(encode(item) for item in value)
+    if isinstance(value, tuple):  # (type, int) for sized unsigned integers
+        type_, number = value
+        raw = number.to_bytes(16, "big").lstrip(b"\0")
+        return control(type_, len(raw)) + raw
+    raise TypeError(value)
+
+
+def main():
+    data, offsets = b"", {}
+    for iso_code in sorted(set(NETWORKS.values())):
+        offsets[iso_code] = len(data)
+        data += encode({"country": {"iso_code": iso_code, "names": {"en": NAMES[iso_code]}}})
+
+    # Binary trie over 128 bit addresses; IPv4 lives under ::/96
+    nodes = [[None, None]]
+    for network, iso_code in NETWORKS.items():
+        net = ipaddress.ip_network(network)
+        bits, prefix = int(net.network_address), net.prefixlen
+        if net.version == 4:
+            prefix += 96
+        node = 0
+        for depth in range(prefix):
+            bit = (bits >> (127 - depth)) & 1
+            if depth == prefix - 1:
+                nodes[node][bit] = ("data", iso_code)
+            else:
+                if not isinstance(nodes[node][bit], int):
+                    nodes.append([None, None])
+                    nodes[node][bit] = len(nodes) - 1
+                node = nodes[node][bit]
+
+    node_count = len(nodes)
+
+    def record(value):
+        if value is None:
+            return node_count
+        if isinstance(value, int):
+            return value
+        return node_count + 16 + offsets[value[1]]
+
+    tree = b"".join(
+        record(left).to_bytes(3, "big") + record(right).to_bytes(3, "big") for left, right in nodes
+    )
+    metadata = encode(
+        {
+            "binary_format_major_version": (5, 2),
+            "binary_format_minor_version": (5, 0),
+            "build_epoch": (9, 1700000000),
+            "database_type": "GeoIP2-Country",
+            "description": {"en": "url_shortener test fixture"},
+            "ip_version": (5, 6),
+            "languages": ["en"],
+            "node_count": (6, node_count),
+            "record_size": (5, 24),
+        }
+    )
+    with open("geoip_country_test.mmdb", "wb") as out:
+        out.write(tree + b"\0" * 16 + data + b"\xab\xcd\xefMaxMind.com" + metadata)
+
+
+if __name__ == "__main__":
+    main()
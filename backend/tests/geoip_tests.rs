@@ -0,0 +1,116 @@
+use url_shortener::geoip::{is_country_code, GeoIp};
+use url_shortener::models::{parse_body, CreateLinkRequest};
+use url_shortener::ratelimit::resolve_client_ip;
+use url_shortener::targeting::{select, Client, DestinationRule};
+
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+// Built by tests/fixtures/make_geoip_fixture.py
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+fn geoip() -> GeoIp {
+    GeoIp::open(&fixture("geoip_country_test.mmdb")).unwrap()
+}
+
+fn visitor(country: Option<&str>) -> Client {
+    let mut client = Client::detect(|_| None);
+    client.country = country.map(str::to_string);
+    client
+}
+
+fn country_rules() -> Vec<DestinationRule> {
+    vec![
+        DestinationRule {
+            countries: vec!["FR".to_string(), "BE".to_string()],
+            long_url: "https://example.com/fr".to_string(),
+            ..DestinationRule::default()
+        },
+        DestinationRule {
+            countries: vec!["us".to_string()],
+            long_url: "https://example.com/us".to_string(),
+            ..DestinationRule::default()
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_country_lookup() {
+        let geoip = geoip();
+        let country = |ip: &str| geoip.country(ip.parse().unwrap());
+
+        assert_eq!(country("2.0.1.1").as_deref(), Some("FR"));
+        assert_eq!(country("81.2.69.142").as_deref(), Some("GB"));
+        assert_eq!(country("89.160.20.112").as_deref(), Some("SE"));
+        assert_eq!(country("216.160.83.56").as_deref(), Some("US"));
+        assert_eq!(country("2a01:e0a:1::1").as_deref(), Some("FR"));
+        assert_eq!(country("2001:480:10::1").as_deref(), Some("US"));
+        // IPv4-mapped IPv6, as seen on dual-stack listeners
+        assert_eq!(country("::ffff:81.2.69.142").as_deref(), Some("GB"));
+
+        assert_eq!(country("10.0.0.1"), None);
+        assert_eq!(country("2.1.0.1"), None);
+        assert_eq!(country("2606:4700::1"), None);
+    }
+
+    #[test]
+    fn test_open_errors() {
+        assert!(GeoIp::open(&fixture("missing.mmdb")).is_err());
+        assert!(GeoIp::open(&fixture("jwks.json")).is_err());
+    }
+
+    #[test]
+    fn test_country_rules() {
+        let rules = country_rules();
+        let destination =
+            |country| select(&rules, &visitor(country)).map(|rule| rule.long_url.as_str());
+
+        assert_eq!(destination(Some("FR")), Some("https://example.com/fr"));
+        assert_eq!(destination(Some("BE")), Some("https://example.com/fr"));
+        assert_eq!(destination(Some("US")), Some("https://example.com/us"));
+        assert_eq!(destination(Some("GB")), None);
+        // Visitors we cannot locate match no country rule
+        assert_eq!(destination(None), None);
+        assert!(rules[1].has_conditions());
+    }
+
+    #[test]
+    fn test_country_of_proxied_visitor() {
+        // The country comes from the client IP resolved through trusted proxies
+        let geoip = geoip();
+        let proxies = vec!["10.0.0.0/8".parse().unwrap()];
+        let remote: IpAddr = "10.0.0.5".parse().unwrap();
+
+        let ip = resolve_client_ip(Some(remote), Some("216.160.83.56, 10.0.0.9"), &proxies);
+        assert_eq!(ip.and_then(|ip| geoip.country(ip)).as_deref(), Some("US"));
+
+        // An untrusted peer cannot claim another address
+        let spoofed: IpAddr = "81.2.69.142".parse().unwrap();
+        let ip = resolve_client_ip(Some(spoofed), Some("216.160.83.56"), &proxies);
+        assert_eq!(ip.and_then(|ip| geoip.country(ip)).as_deref(), Some("GB"));
+    }
+
+    #[test]
+    fn test_countries_in_requests() {
+        assert!(is_country_code("DE"));
+        assert!(is_country_code("de"));
+        assert!(!is_country_code("DEU"));
+        assert!(!is_country_code("D1"));
+
+        let request: CreateLinkRequest = parse_body(
+            br#"{"long_url": "https://example.com", "rules": [
+                {"countries": ["DE", "AT"], "long_url": "https://example.de"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(request.options.rules[0].countries, ["DE", "AT"]);
+    }
+}
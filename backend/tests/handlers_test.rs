@@ -1,9 +1,13 @@
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use url_shortener::auth::{Authenticator, Credentials};
+use url_shortener::config::Config;
 use url_shortener::db::{self, Data};
 use url_shortener::handlers::{handle_generate_url, handle_redirect_url};
+use url_shortener::models::UnknownFields;
+use url_shortener::state::AppState;
 use warp::http::StatusCode;
-use std::sync::Arc;
-use tokio::sync::Mutex;
-use serde_json::json;
 use warp::reply::Reply;
 
 // Initialize a test Redis database connection
@@ -16,6 +20,16 @@ async fn init_test_db() -> db::Database {
     Arc::new(Mutex::new(connection))
 }
 
+// Build the application state around a test database
+fn init_test_state(db: db::Database, api_key: &str) -> AppState {
+    let config = Config {
+        api_key: api_key.to_string(),
+        ..Config::default()
+    };
+    let auth = Authenticator::from_config(&config).unwrap();
+    AppState::new(db, config, auth)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -31,15 +45,25 @@ mod tests {
             "long_url": "http://example.com/some/long/url"
         });
 
+        let state = init_test_state(db.clone(), &api_key);
+
         // Simulate a request to generate a short URL
-        let response = handle_generate_url(api_key.clone(), body, db.clone(), api_key).await.unwrap();
+        let body = body.to_string().into();
+        let response = handle_generate_url(
+            Credentials::ApiKey(api_key),
+            body,
+            state,
+            UnknownFields::Reject,
+        )
+        .await
+        .unwrap();
         let response = response.into_response();
-        
+
         assert_eq!(response.status(), StatusCode::OK);
-        
+
         let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
         let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
-        
+
         assert_eq!(json["status"], "success");
         assert!(json["short_url"].is_string());
     }
@@ -48,32 +72,35 @@ mod tests {
     async fn test_handle_redirect_url() {
         let db = init_test_db().await;
         let short_url_id = "test_redirect_key".to_string();
-        
+
         // Prepare and store data in Redis
         let data = Data {
             creation_data: chrono::Local::now().to_rfc3339(),
             shortened_url: format!("http://localhost/{}", short_url_id),
             long_url: "http://example.com/some/long/url".to_string(),
             ttl: 30,
+            ..Default::default()
         };
 
-        db::store_data(db.clone(), short_url_id.clone(), data).await.unwrap();
+        db::store_data(db.clone(), short_url_id.clone(), data)
+            .await
+            .unwrap();
 
         // Prepare parameters for redirecting
         let mut params = HashMap::new();
         params.insert("short_url".to_string(), short_url_id.clone());
 
         // Simulate a request to redirect based on the short URL
-        let response = handle_redirect_url(params, db.clone()).await.unwrap();
+        let state = init_test_state(db.clone(), "test_api_key");
+        let response = handle_redirect_url(params, state).await.unwrap();
         let response = response.into_response();
-        
+
         assert_eq!(response.status(), StatusCode::OK);
-        
+
         let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
         let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
-        
+
         assert_eq!(json["status"], "success");
         assert_eq!(json["redirect_to"], "http://example.com/some/long/url");
     }
-
 }
@@ -0,0 +1,140 @@
+use url_shortener::interstitial::{
+    continue_url, select, take_token, DomainInterstitial, Interstitial, TokenSigner, TOKEN_PARAM,
+};
+use url_shortener::models::{parse_body, CustomLinkRequest};
+use url_shortener::pages::render_interstitial;
+
+const NOW: i64 = 1_900_000_000;
+
+fn rules(spec: &[&str]) -> Vec<DomainInterstitial> {
+    spec.iter().map(|rule| rule.parse().unwrap()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_domain_rules() {
+        let rules = rules(&[".example.com=off", "evil.example=reported", "*=leaving"]);
+        let select = |link, url| select(&rules, link, url);
+
+        assert_eq!(select(None, "https://docs.example.com/a"), None);
+        assert_eq!(
+            select(None, "https://partner.example/"),
+            Some(Interstitial::Leaving)
+        );
+        assert_eq!(
+            select(None, "https://EVIL.example/login"),
+            Some(Interstitial::Reported)
+        );
+
+        // Links choose their own page, but cannot hide a reported domain
+        assert_eq!(
+            select(Some(Interstitial::Leaving), "https://example.com/"),
+            Some(Interstitial::Leaving)
+        );
+        assert_eq!(
+            select(Some(Interstitial::Off), "https://partner.example/"),
+            None
+        );
+        assert_eq!(
+            select(Some(Interstitial::Off), "https://evil.example/"),
+            Some(Interstitial::Reported)
+        );
+
+        assert_eq!(super::select(&[], None, "https://partner.example/"), None);
+        for invalid in ["example.com", "example.com=maybe", "=leaving"] {
+            assert!(
+                invalid.parse::<DomainInterstitial>().is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_tokens() {
+        let signer = TokenSigner::new(b"interstitial secret", 300);
+        let token = signer.sign("promo", "203.0.113.7", NOW);
+        assert!(signer.verify(&token, "promo", "203.0.113.7", NOW));
+        assert!(signer.verify(&token, "promo", "203.0.113.7", NOW + 300));
+
+        // Expired, or for another link, visitor or secret
+        assert!(!signer.verify(&token, "promo", "203.0.113.7", NOW + 301));
+        assert!(!signer.verify(&token, "other", "203.0.113.7", NOW));
+        assert!(!signer.verify(&token, "promo", "198.51.100.1", NOW));
+        assert!(!TokenSigner::new(b"other secret", 300).verify(
+            &token,
+            "promo",
+            "203.0.113.7",
+            NOW
+        ));
+
+        // Tampered expiry or tag
+        let (expires, tag) = token.split_once('.').unwrap();
+        let later = format!("{}.{}", expires.parse::<i64>().unwrap() + 60, tag);
+        assert!(!signer.verify(&later, "promo", "203.0.113.7", NOW));
+        for malformed in ["", "abc", "123.zz", &format!("{}.", expires)] {
+            assert!(!signer.verify(malformed, "promo", "203.0.113.7", NOW));
+        }
+
+        // Tokens far in the future were not signed with this TTL
+        let long = TokenSigner::new(b"interstitial secret", 3600).sign("promo", "203.0.113.7", NOW);
+        assert!(!signer.verify(&long, "promo", "203.0.113.7", NOW));
+    }
+
+    #[test]
+    fn test_continue_url_round_trip() {
+        let url = continue_url("http://sho.rt/promo", "", "", "123.ab");
+        assert_eq!(url, format!("http://sho.rt/promo?{}=123.ab", TOKEN_PARAM));
+
+        let url = continue_url("http://sho.rt/docs", "guide/intro", "a=1&b=2", "123.ab");
+        assert_eq!(
+            url,
+            "http://sho.rt/docs/guide/intro?a=1&b=2&rs_continue=123.ab"
+        );
+
+        let (token, query) = take_token("a=1&rs_continue=123.ab&b=2");
+        assert_eq!(token.as_deref(), Some("123.ab"));
+        assert_eq!(query, "a=1&b=2");
+        assert_eq!(
+            take_token("a=1&rs_continued=x"),
+            (None, "a=1&rs_continued=x".to_string())
+        );
+        assert_eq!(take_token(""), (None, String::new()));
+    }
+
+    #[test]
+    fn test_render_interstitial() {
+        let page = render_interstitial(
+            Interstitial::Reported,
+            "https://evil.example/login?next=<x>",
+            "http://sho.rt/promo?a=1&rs_continue=123.ab",
+        );
+        assert!(page.contains("<title>This link was reported</title>"));
+        assert!(page.contains("evil.example, which has been reported"));
+        assert!(page.contains("https://evil.example/login?next=&lt;x&gt;"));
+        assert!(page.contains(r#"href="http://sho.rt/promo?a=1&amp;rs_continue=123.ab""#));
+
+        let page = render_interstitial(Interstitial::Leaving, "https://partner.example/", "/x");
+        assert!(page.contains("You are leaving our site"));
+        assert!(!page.contains(r#"class="error""#));
+    }
+
+    #[test]
+    fn test_interstitial_in_requests() {
+        let request: CustomLinkRequest = parse_body(
+            br#"{"long_url": "https://partner.example/", "custom_short": "partner",
+                "interstitial": "leaving"}"#,
+        )
+        .unwrap();
+        assert_eq!(request.options.interstitial, Some(Interstitial::Leaving));
+
+        assert!(parse_body::<CustomLinkRequest>(
+            br#"{"long_url": "https://partner.example/", "custom_short": "partner",
+                "interstitial": "always"}"#,
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use url_shortener::error::{ApiError, PROBLEM_JSON};
+use url_shortener::handlers::{handle_rejection, RedisError};
+use url_shortener::models::{
+    parse_body, parse_body_with, CreateLinkRequest, CustomLinkRequest, UnknownFields,
+};
+use warp::http::StatusCode;
+use warp::Filter;
+
+// Read a problem document from a response
+async fn problem(response: warp::http::Response<warp::hyper::Body>) -> serde_json::Value {
+    assert_eq!(response.headers()["content-type"], PROBLEM_JSON);
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warp::Reply;
+
+    #[test]
+    fn test_parse_valid_body() {
+        let request: CustomLinkRequest =
+            parse_body(br#"{"long_url": "https://example.com", "custom_short": "promo"}"#).unwrap();
+        assert_eq!(request.options.long_url, "https://example.com");
+        assert_eq!(request.custom_short, "promo");
+    }
+
+    #[test]
+    fn test_field_level_errors() {
+        let error = parse_body::<CustomLinkRequest>(br#"{"long_url": " "}"#).unwrap_err();
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.code, "VALIDATION_FAILED");
+        let fields: Vec<_> = error.errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["long_url", "custom_short"]);
+        assert!(error.errors.iter().all(|e| e.code == "FIELD_REQUIRED"));
+
+        let error = parse_body::<CreateLinkRequest>(br#"{"long_url": 42}"#).unwrap_err();
+        assert_eq!(error.errors[0].field, "long_url");
+        assert_eq!(error.errors[0].code, "INVALID_TYPE");
+
+        let error = parse_body::<CreateLinkRequest>(br#"{"long_url": "https://a.b", "lnog": 1}"#)
+            .unwrap_err();
+        assert_eq!(error.errors[0].field, "lnog");
+        assert_eq!(error.errors[0].code, "FIELD_UNKNOWN");
+
+        let error = parse_body::<CreateLinkRequest>(b"{\"long_url\": ").unwrap_err();
+        assert_eq!(error.code, "INVALID_JSON");
+        assert!(error.errors.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        let body =
+            br#"{"long_url": "https://a.b", "custom_short": "promo", "tags": ["x"], "lnog": 1}"#;
+        let error = parse_body::<CustomLinkRequest>(body).unwrap_err();
+        assert_eq!(error.code, "VALIDATION_FAILED");
+        let fields: Vec<_> = error.errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["lnog", "tags"]);
+        assert!(error.errors.iter().all(|e| e.code == "FIELD_UNKNOWN"));
+
+        // Legacy routes ignore them, but still check the fields they know
+        let request: CustomLinkRequest = parse_body_with(body, UnknownFields::Ignore).unwrap();
+        assert_eq!(request.custom_short, "promo");
+        assert_eq!(request.options.long_url, "https://a.b");
+        let error = parse_body_with::<CreateLinkRequest>(
+            br#"{"long_url": "https://a.b", "max_clicks": "1", "lnog": 1}"#,
+            UnknownFields::Ignore,
+        )
+        .unwrap_err();
+        assert_eq!(error.errors[0].field, "max_clicks");
+        assert_eq!(error.errors[0].code, "INVALID_TYPE");
+        assert!(!error.errors[0].message.contains("at `"));
+    }
+
+    #[tokio::test]
+    async fn test_problem_document() {
+        let error = ApiError::field("custom_short", "ALIAS_RESERVED", "`ping` is reserved")
+            .with_header("Retry-After", 5);
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers()["retry-after"], "5");
+
+        let json = problem(response).await;
+        assert_eq!(json["type"], "about:blank");
+        assert_eq!(json["title"], "Bad Request");
+        assert_eq!(json["status"], 400);
+        assert_eq!(json["code"], "ALIAS_RESERVED");
+        assert_eq!(json["detail"], "`ping` is reserved");
+        assert_eq!(json["errors"][0]["field"], "custom_short");
+    }
+
+    #[tokio::test]
+    async fn test_rejections_become_problems() {
+        let routes = warp::path("store")
+            .and_then(|| async {
+                Err::<String, _>(warp::reject::custom(RedisError(
+                    "connection refused".into(),
+                )))
+            })
+            .or(warp::path("json")
+                .and(warp::body::json())
+                .map(|body: serde_json::Value| body.to_string()))
+            .recover(handle_rejection);
+
+        let response = warp::test::request().path("/store").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["code"], "STORAGE_UNAVAILABLE");
+        // Internal details are logged, not returned.
+        assert!(!body["detail"].as_str().unwrap().contains("refused"));
+
+        let response = warp::test::request()
+            .path("/json")
+            .body("{oops")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["code"], "INVALID_JSON");
+
+        let response = warp::test::request().path("/missing").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()["content-type"], PROBLEM_JSON);
+    }
+}
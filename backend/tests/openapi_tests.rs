@@ -0,0 +1,215 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use url_shortener::auth::Authenticator;
+use url_shortener::config::Config;
+use url_shortener::openapi::{docs_page, ApiDoc};
+use url_shortener::routes::{self, Endpoint, ENDPOINTS, REDIRECT_PATH, ROUTE_NAMES};
+use url_shortener::state::AppState;
+use utoipa::OpenApi;
+use warp::http::StatusCode;
+
+// Every (method, path, deprecated) triple documented in the spec
+fn documented_endpoints() -> BTreeSet<(String, String, bool)> {
+    let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+    let mut endpoints = BTreeSet::new();
+    for (path, item) in spec["paths"].as_object().unwrap() {
+        for (method, operation) in item.as_object().unwrap() {
+            let deprecated = operation["deprecated"] == true;
+            endpoints.insert((method.to_uppercase(), path.clone(), deprecated));
+        }
+    }
+    endpoints
+}
+
+// Serve a Redis that answers every command with an error, so routes can be built without one
+async fn failing_redis() -> redis::aio::MultiplexedConnection {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((socket, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let (reader, mut writer) = socket.into_split();
+                let mut reader = BufReader::new(reader);
+                let mut line = String::new();
+                // Commands are arrays of bulk strings: *<count>, then $<length> and the bytes
+                while reader.read_line(&mut line).await.unwrap_or(0) > 0 {
+                    let count: usize = line.trim()[1..].parse().unwrap();
+                    for _ in 0..count {
+                        line.clear();
+                        reader.read_line(&mut line).await.unwrap();
+                        let length: usize = line.trim()[1..].parse().unwrap();
+                        let mut bulk = vec![0; length + 2];
+                        reader.read_exact(&mut bulk).await.unwrap();
+                    }
+                    line.clear();
+                    if writer.write_all(b"-ERR unavailable\r\n").await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    redis::Client::open(format!("redis://{}/", address))
+        .unwrap()
+        .get_multiplexed_async_connection()
+        .await
+        .unwrap()
+}
+
+// A request to `endpoint`, with its path parameters filled in
+fn request_for(endpoint: &Endpoint) -> warp::test::RequestBuilder {
+    let path = endpoint
+        .path
+        .replace("{short_url}", "abc1234")
+        .replace("{code}", "abc1234");
+    let request = warp::test::request().method(endpoint.method).path(&path);
+    match endpoint.method {
+        "POST" if endpoint.path.ends_with("{short_url}") => request
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body("password=secret"),
+        "POST" => request
+            .header("content-type", "application/json")
+            .body("{}"),
+        _ => request,
+    }
+}
+
+fn first_segment(endpoint: &Endpoint) -> &str {
+    endpoint
+        .path
+        .trim_start_matches('/')
+        .split('/')
+        .next()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_matches_route_table() {
+        let routes: BTreeSet<(String, String, bool)> = ENDPOINTS
+            .iter()
+            .map(|e| (e.method.to_string(), e.path.to_string(), e.deprecated))
+            .collect();
+        assert_eq!(documented_endpoints(), routes);
+    }
+
+    #[test]
+    fn test_route_names_match_endpoints() {
+        // Every endpoint but the root redirect and its preview starts with a reserved
+        // top-level name
+        let segments: BTreeSet<&str> = ENDPOINTS
+            .iter()
+            .filter(|e| !e.path.starts_with(REDIRECT_PATH))
+            .map(first_segment)
+            .collect();
+        let names: BTreeSet<&str> = ROUTE_NAMES.iter().copied().collect();
+        assert_eq!(segments, names);
+    }
+
+    #[tokio::test]
+    async fn test_route_table_matches_filter() {
+        let db = Arc::new(Mutex::new(failing_redis().await));
+        let config = Config::default();
+        let auth = Authenticator::from_config(&config).unwrap();
+        let filter = routes::filter(AppState::new(db, config, auth));
+
+        for endpoint in ENDPOINTS {
+            let response = request_for(endpoint).reply(&filter).await;
+            let label = format!("{} {}", endpoint.method, endpoint.path);
+            assert_ne!(
+                response.status(),
+                StatusCode::METHOD_NOT_ALLOWED,
+                "{}",
+                label
+            );
+            let body: serde_json::Value =
+                serde_json::from_slice(response.body()).unwrap_or_default();
+            assert_ne!(body["detail"], "No such route", "{}", label);
+
+            let headers = response.headers();
+            assert_eq!(
+                headers.contains_key("deprecation"),
+                endpoint.deprecated,
+                "{}",
+                label
+            );
+            let successor = endpoint
+                .successor
+                .map(|path| format!("<{}>; rel=\"successor-version\"", path));
+            assert_eq!(
+                headers.get("link").map(|link| link.to_str().unwrap()),
+                successor.as_deref(),
+                "{}",
+                label
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_password_form_posts_through_legacy_route() {
+        let db = Arc::new(Mutex::new(failing_redis().await));
+        let config = Config::default();
+        let auth = Authenticator::from_config(&config).unwrap();
+        let filter = routes::filter(AppState::new(db, config, auth));
+
+        // The form has no action, so a link opened at /dns_resolver/{code} posts back there
+        let response = warp::test::request()
+            .method("POST")
+            .path("/dns_resolver/abc1234?ref=x")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body("password=secret")
+            .reply(&filter)
+            .await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap_or_default();
+        assert_ne!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_ne!(body["detail"], "No such route");
+        assert_eq!(body["code"], "LINK_NOT_FOUND");
+        assert!(response.headers().contains_key("deprecation"));
+    }
+
+    #[test]
+    fn test_legacy_routes_point_to_successors() {
+        for endpoint in ENDPOINTS.iter().filter(|e| e.successor.is_some()) {
+            let successor = endpoint.successor.unwrap();
+            assert!(endpoint.deprecated);
+            assert!(
+                ENDPOINTS
+                    .iter()
+                    .any(|e| e.path == successor && e.method == endpoint.method && !e.deprecated),
+                "{} has no live successor",
+                endpoint.path
+            );
+        }
+    }
+
+    #[test]
+    fn test_models_are_documented() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let schemas = &spec["components"]["schemas"];
+        assert_eq!(
+            schemas["LinkOptions"]["required"],
+            serde_json::json!(["long_url"])
+        );
+        assert_eq!(
+            schemas["CustomLinkRequest"]["allOf"][0]["$ref"],
+            "#/components/schemas/LinkOptions"
+        );
+        assert!(schemas["ProblemDetails"]["properties"]["code"].is_object());
+        assert!(spec["components"]["securitySchemes"]["api_key"].is_object());
+
+        // Every referenced schema is registered
+        let json = spec.to_string();
+        for reference in json.split("\"$ref\":\"#/components/schemas/").skip(1) {
+            let name = reference.split('"').next().unwrap();
+            assert!(schemas[name].is_object(), "{} is not registered", name);
+        }
+
+        assert!(docs_page().contains("spec-url=\"/openapi.json\""));
+    }
+}
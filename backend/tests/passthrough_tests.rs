@@ -0,0 +1,113 @@
+use url_shortener::models::{parse_body, CreateLinkRequest};
+use url_shortener::passthrough::{merge_query, Passthrough, QueryConflict};
+
+fn forwarding(conflict: QueryConflict) -> Passthrough {
+    Passthrough {
+        query: true,
+        path: true,
+        query_conflict: conflict,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_conflict_policies() {
+        let own = "utm_source=site&ref=a";
+        let visitor = "utm_source=x&lang=en";
+
+        assert_eq!(
+            merge_query(own, visitor, QueryConflict::Keep),
+            "utm_source=site&ref=a&lang=en"
+        );
+        assert_eq!(
+            merge_query(own, visitor, QueryConflict::Override),
+            "ref=a&utm_source=x&lang=en"
+        );
+        assert_eq!(
+            merge_query(own, visitor, QueryConflict::Append),
+            "utm_source=site&ref=a&utm_source=x&lang=en"
+        );
+        assert_eq!(
+            merge_query("", "q=a+b%26c", QueryConflict::Keep),
+            "q=a+b%26c"
+        );
+    }
+
+    #[test]
+    fn test_destination_wins_by_default() {
+        let request: CreateLinkRequest = parse_body(
+            br#"{"long_url": "https://example.com/?v=2&ref=site", "passthrough": {"query": true}}"#,
+        )
+        .unwrap();
+        let passthrough = request.options.passthrough;
+        assert_eq!(passthrough.query_conflict, QueryConflict::Keep);
+        assert_eq!(
+            passthrough
+                .apply(&request.options.long_url, "", "ref=x&v=3&lang=en&v=4")
+                .as_deref(),
+            Some("https://example.com/?v=2&ref=site&lang=en")
+        );
+        // Encoded spellings of a destination parameter do not get through either
+        assert_eq!(
+            merge_query(
+                "utm_source=site",
+                "utm%5Fsource=x&utm_source=y",
+                QueryConflict::default()
+            ),
+            "utm_source=site"
+        );
+    }
+
+    #[test]
+    fn test_apply_passthrough() {
+        let destination = "https://example.com/docs/?v=2";
+
+        // Disabled links ignore the query string and refuse extra path segments
+        let disabled = Passthrough::default();
+        assert_eq!(
+            disabled.apply(destination, "", "utm_source=x").as_deref(),
+            Some(destination)
+        );
+        assert_eq!(disabled.apply(destination, "intro", ""), None);
+
+        let enabled = forwarding(QueryConflict::Override);
+        assert_eq!(
+            enabled
+                .apply(destination, "guides/intro", "utm_source=x&v=3")
+                .as_deref(),
+            Some("https://example.com/docs/guides/intro?utm_source=x&v=3")
+        );
+        assert_eq!(
+            enabled.apply("https://example.com", "a b", "").as_deref(),
+            Some("https://example.com/a%20b")
+        );
+
+        // Dot segments could climb out of the destination's section
+        assert_eq!(enabled.apply(destination, "../admin", ""), None);
+        assert_eq!(enabled.apply(destination, "x/%2E%2e/admin", ""), None);
+    }
+
+    #[test]
+    fn test_passthrough_request_options() {
+        let request: CreateLinkRequest = parse_body(
+            br#"{"long_url": "https://example.com", "passthrough": {"query": true, "query_conflict": "append"}}"#,
+        )
+        .unwrap();
+        assert!(request.options.passthrough.query);
+        assert!(!request.options.passthrough.path);
+        assert_eq!(
+            request.options.passthrough.query_conflict,
+            QueryConflict::Append
+        );
+
+        let error = parse_body::<CreateLinkRequest>(
+            br#"{"long_url": "https://example.com", "passthrough": {"query_conflict": "merge"}}"#,
+        )
+        .unwrap_err();
+        assert_eq!(error.errors[0].field, "passthrough.query_conflict");
+        assert_eq!(error.errors[0].code, "INVALID_TYPE");
+    }
+}
@@ -0,0 +1,81 @@
+use url_shortener::models::{parse_body, CustomLinkRequest, PasswordForm};
+use url_shortener::pages::render_password_form;
+use url_shortener::password::{self, MAX_LENGTH, MIN_LENGTH};
+
+// Few rounds keep the tests fast; the format and checks are the same as in production
+const TEST_ITERATIONS: u32 = 1_000;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_and_verify() {
+        let stored = password::hash_with("correct horse", TEST_ITERATIONS);
+        let parts: Vec<&str> = stored.split('$').collect();
+        assert_eq!(parts[0], "pbkdf2-sha256");
+        assert_eq!(parts[1], "1000");
+        assert_eq!(parts[2].len(), 32);
+        assert_eq!(parts[3].len(), 64);
+        assert!(!stored.contains("correct horse"));
+
+        assert!(password::verify("correct horse", &stored));
+        assert!(!password::verify("correct horse ", &stored));
+        assert!(!password::verify("Correct horse", &stored));
+        assert!(!password::verify("", &stored));
+
+        // Every hash gets its own salt
+        assert_ne!(
+            stored,
+            password::hash_with("correct horse", TEST_ITERATIONS)
+        );
+    }
+
+    #[test]
+    fn test_malformed_hashes_never_verify() {
+        let stored = password::hash_with("secret", TEST_ITERATIONS);
+        let (_, rest) = stored.split_once('$').unwrap();
+        for malformed in [
+            String::new(),
+            "secret".to_string(),
+            format!("bcrypt${}", rest),
+            format!("pbkdf2-sha256$0${}", rest.split_once('$').unwrap().1),
+            format!("{}$extra", stored),
+            stored.replace('$', ":"),
+        ] {
+            assert!(!password::verify("secret", &malformed), "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn test_password_length() {
+        assert!(password::check(&"a".repeat(MIN_LENGTH)).is_ok());
+        assert!(password::check(&"é".repeat(MAX_LENGTH)).is_ok());
+        assert!(password::check(&"a".repeat(MIN_LENGTH - 1)).is_err());
+        assert!(password::check(&"a".repeat(MAX_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_password_form() {
+        let form = render_password_form(None);
+        assert!(form.contains(r#"<form method="post">"#));
+        assert!(form.contains(r#"name="password" type="password""#));
+        assert!(!form.contains(r#"class="error""#));
+
+        let form = render_password_form(Some("<b>Incorrect</b> password"));
+        assert!(form.contains("&lt;b&gt;Incorrect&lt;/b&gt; password"));
+    }
+
+    #[test]
+    fn test_password_in_requests() {
+        let request: CustomLinkRequest = parse_body(
+            br#"{"long_url": "https://docs.example.com/internal", "custom_short": "handbook",
+                "password": "correct horse"}"#,
+        )
+        .unwrap();
+        assert_eq!(request.options.password.as_deref(), Some("correct horse"));
+
+        let form: PasswordForm = serde_json::from_value(serde_json::json!({})).unwrap();
+        assert_eq!(form.password, "");
+    }
+}
@@ -0,0 +1,119 @@
+use chrono::DateTime;
+use std::collections::HashMap;
+use url_shortener::alias::{AliasRules, Charset};
+use url_shortener::domain_policy::{DomainList, DomainPolicy};
+use url_shortener::interstitial::DomainInterstitial;
+use url_shortener::pages::render_preview;
+use url_shortener::preview::{is_preview, preview_code, warning, LinkPreview};
+
+fn preview() -> LinkPreview {
+    LinkPreview {
+        short_url: "http://rustyshortener/spring-sale".to_string(),
+        destination: Some("https://example.com/sale?a=1&b=<2>".to_string()),
+        varies: false,
+        created_at: DateTime::parse_from_rfc3339("2030-03-01T09:00:00+01:00").unwrap(),
+        expires_at: DateTime::parse_from_rfc3339("2030-03-31T09:00:00+01:00").unwrap(),
+        not_before: None,
+        not_after: None,
+        warning: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_code() {
+        assert_eq!(preview_code("abc1234+", ""), Some("abc1234"));
+        assert_eq!(preview_code("abc1234", "preview"), Some("abc1234"));
+        assert_eq!(preview_code("abc1234", "preview/"), Some("abc1234"));
+
+        assert_eq!(preview_code("abc1234", ""), None);
+        assert_eq!(preview_code("+", ""), None);
+        assert_eq!(preview_code("abc1234+", "docs"), None);
+        assert_eq!(preview_code("abc1234", "docs/preview"), None);
+        assert_eq!(preview_code("abc1234", "previews"), None);
+
+        let params = HashMap::from([
+            ("short_url".to_string(), "abc1234".to_string()),
+            ("path".to_string(), "preview".to_string()),
+        ]);
+        assert!(is_preview(&params));
+        assert!(!is_preview(&HashMap::from([(
+            "short_url".to_string(),
+            "abc1234".to_string()
+        )])));
+    }
+
+    #[test]
+    fn test_render_preview() {
+        let page = render_preview(&preview());
+        assert!(page.contains("<code>https://example.com/sale?a=1&amp;b=&lt;2&gt;</code>"));
+        assert!(page.contains(
+            r#"<time datetime="2030-03-01T09:00:00+01:00">2030-03-01 09:00 +01:00</time>"#
+        ));
+        assert!(page.contains("2030-03-31 09:00 +01:00"));
+        assert!(page.contains(r#"href="http://rustyshortener/spring-sale""#));
+        assert!(!page.contains("Warning"));
+        assert!(!page.contains("Opens"));
+        assert!(!page.contains("different destination"));
+    }
+
+    #[test]
+    fn test_render_flagged_or_protected_preview() {
+        let page = render_preview(&LinkPreview {
+            destination: None,
+            varies: true,
+            not_after: Some(DateTime::parse_from_rfc3339("2030-03-15T00:00:00Z").unwrap()),
+            warning: Some("Links to evil.example are blocked".to_string()),
+            ..preview()
+        });
+        assert!(page.contains("<strong>Warning:</strong> Links to evil.example are blocked"));
+        assert!(page.contains("Hidden until the link's password is entered"));
+        assert!(!page.contains("example.com/sale"));
+        assert!(page.contains("Closes"));
+        assert!(page.contains("different destination"));
+    }
+
+    #[test]
+    fn test_protected_preview_hides_flagged_host() {
+        let policy = DomainPolicy {
+            blocklist: DomainList::parse("blocked.example").unwrap(),
+            ..DomainPolicy::default()
+        };
+        let reported: Vec<DomainInterstitial> = vec!["evil.example=reported".parse().unwrap()];
+        let blocked = ["https://example.com/", "https://blocked.example/login"];
+        let unsafe_site = ["https://evil.example/login"];
+
+        let open = warning(&policy, &reported, &blocked, false).unwrap();
+        assert!(open.contains("blocked.example"));
+        let open = warning(&policy, &reported, &unsafe_site, false).unwrap();
+        assert!(open.contains("evil.example"));
+
+        // Protected links are still flagged, without the host giving the destination away
+        for destinations in [&blocked[..], &unsafe_site[..]] {
+            let protected = warning(&policy, &reported, destinations, true).unwrap();
+            assert!(!protected.contains("example"), "{}", protected);
+            let page = render_preview(&LinkPreview {
+                destination: None,
+                warning: Some(protected),
+                ..preview()
+            });
+            assert!(page.contains("<strong>Warning:</strong> This link leads to a site"));
+            assert!(!page.contains("evil.example") && !page.contains("blocked.example"));
+        }
+
+        assert_eq!(
+            warning(&policy, &reported, &["https://example.com/"], true),
+            None
+        );
+    }
+
+    #[test]
+    fn test_preview_suffix_is_not_an_alias() {
+        // `/{code}+` can never shadow an alias, as no charset may contain `+`
+        assert!("a-z+".parse::<Charset>().is_err());
+        assert!(AliasRules::default().check("clang+").is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use url_shortener::auth::ApiKeyEntry;
+use url_shortener::db::Database;
+use url_shortener::quotas::{self, Quota, QuotaExceeded};
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+// Initialize a test Redis database connection
+async fn init_test_db() -> Database {
+    let client = redis::Client::open("redis://127.0.0.1/").expect("Failed to create Redis client");
+    let connection = client
+        .get_multiplexed_async_connection()
+        .await
+        .expect("Failed to connect to Redis");
+    Arc::new(Mutex::new(connection))
+}
+
+fn usage_period() -> String {
+    chrono::Utc::now().format("%Y-%m").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owner_quota_falls_back_to_defaults() {
+        let entry: ApiKeyEntry = serde_json::from_value(serde_json::json!({
+            "owner": "marketing",
+            "key": "k",
+            "quota": { "max_active_links": 10 }
+        }))
+        .unwrap();
+        let defaults = Quota {
+            max_active_links: Some(100),
+            max_custom_aliases: Some(5),
+            monthly_creations: None,
+        };
+
+        let quota = entry.quota.unwrap().or(defaults);
+        assert_eq!(quota.max_active_links, Some(10));
+        assert_eq!(quota.max_custom_aliases, Some(5));
+        assert_eq!(quota.monthly_creations, None);
+    }
+
+    #[tokio::test]
+    async fn test_reserve_enforces_limits() {
+        let db = init_test_db().await;
+        let owner = format!(
+            "quota-test-{}",
+            chrono::Utc::now().timestamp_nanos_opt().unwrap()
+        );
+        let expires_at = chrono::Utc::now().timestamp() + 60;
+        let quota = Quota {
+            max_active_links: Some(2),
+            max_custom_aliases: Some(1),
+            monthly_creations: None,
+        };
+
+        let first = quotas::reserve(db.clone(), &owner, "a1", expires_at, true, &quota).await;
+        assert!(first.unwrap().is_ok());
+
+        let second = quotas::reserve(db.clone(), &owner, "a2", expires_at, true, &quota).await;
+        assert_eq!(second.unwrap(), Err(QuotaExceeded::CustomAliases(1)));
+
+        let third = quotas::reserve(db.clone(), &owner, "g1", expires_at, false, &quota).await;
+        let third = third.unwrap().unwrap();
+        assert_eq!(third.period, usage_period());
+
+        let fourth = quotas::reserve(db.clone(), &owner, "g2", expires_at, false, &quota).await;
+        assert_eq!(fourth.unwrap(), Err(QuotaExceeded::ActiveLinks(2)));
+
+        let usage = quotas::usage(db.clone(), &owner, &quota).await.unwrap();
+        assert_eq!(usage.active_links, 2);
+        assert_eq!(usage.custom_aliases, 1);
+        assert_eq!(usage.monthly_creations, 2);
+
+        quotas::release(db.clone(), &owner, "g1", &third)
+            .await
+            .unwrap();
+        let usage = quotas::usage(db.clone(), &owner, &quota).await.unwrap();
+        assert_eq!(usage.active_links, 1);
+        assert_eq!(usage.monthly_creations, 1);
+
+        // A release after the month changed uncounts the month of the reservation
+        let last_month = quotas::Reservation {
+            period: "2000-01".to_string(),
+        };
+        quotas::release(db.clone(), &owner, "a1", &last_month)
+            .await
+            .unwrap();
+        let usage = quotas::usage(db, &owner, &quota).await.unwrap();
+        assert_eq!(usage.active_links, 0);
+        assert_eq!(usage.monthly_creations, 1);
+    }
+}
@@ -0,0 +1,80 @@
+use url_shortener::ratelimit::{resolve_client_ip, take_token, IpNet, RateLimit};
+
+use std::net::IpAddr;
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rate_limit() {
+        let limit: RateLimit = "30/60".parse().unwrap();
+        assert_eq!(limit.capacity, 30);
+        assert_eq!(limit.refill_per_sec, 0.5);
+
+        assert!("30".parse::<RateLimit>().is_err());
+        assert!("0/60".parse::<RateLimit>().is_err());
+        assert!("10/0".parse::<RateLimit>().is_err());
+    }
+
+    #[test]
+    fn test_token_bucket_drains_and_refills() {
+        let limit: RateLimit = "2/2".parse().unwrap();
+
+        let (allowed, tokens) = take_token(&limit, 2.0, 0, 0);
+        assert!(allowed);
+        let (allowed, tokens) = take_token(&limit, tokens, 0, 0);
+        assert!(allowed);
+        let (allowed, tokens) = take_token(&limit, tokens, 0, 0);
+        assert!(!allowed);
+
+        // One token per second is refilled, but never beyond the capacity.
+        let (allowed, tokens) = take_token(&limit, tokens, 0, 1_000);
+        assert!(allowed);
+        assert_eq!(tokens, 0.0);
+        let (_, tokens) = take_token(&limit, tokens, 1_000, 60_000);
+        assert_eq!(tokens, 1.0);
+    }
+
+    #[test]
+    fn test_ip_networks() {
+        let net: IpNet = "10.0.0.0/8".parse().unwrap();
+        assert!(net.contains(&ip("10.20.30.40")));
+        assert!(net.contains(&ip("::ffff:10.1.1.1")));
+        assert!(!net.contains(&ip("11.0.0.1")));
+
+        let host: IpNet = "fd00::1".parse().unwrap();
+        assert!(host.contains(&ip("fd00::1")));
+        assert!(!host.contains(&ip("fd00::2")));
+
+        assert!("10.0.0.0/33".parse::<IpNet>().is_err());
+        assert!("not-an-ip".parse::<IpNet>().is_err());
+    }
+
+    #[test]
+    fn test_client_ip_honours_trusted_proxies_only() {
+        let trusted: Vec<IpNet> = vec!["172.16.0.0/12".parse().unwrap()];
+
+        // Untrusted peers cannot spoof their address with X-Forwarded-For.
+        let client = resolve_client_ip(Some(ip("203.0.113.9")), Some("1.2.3.4"), &trusted);
+        assert_eq!(client, Some(ip("203.0.113.9")));
+
+        // Behind a trusted proxy the right-most untrusted hop is the client.
+        let client = resolve_client_ip(
+            Some(ip("172.18.0.2")),
+            Some("1.2.3.4, 198.51.100.7, 172.18.0.5"),
+            &trusted,
+        );
+        assert_eq!(client, Some(ip("198.51.100.7")));
+
+        // Without the header the proxy address is all we have.
+        let client = resolve_client_ip(Some(ip("172.18.0.2")), None, &trusted);
+        assert_eq!(client, Some(ip("172.18.0.2")));
+
+        assert_eq!(resolve_client_ip(None, Some("1.2.3.4"), &trusted), None);
+    }
+}
@@ -0,0 +1,59 @@
+use url_shortener::models::{parse_body, CreateLinkRequest};
+use url_shortener::redirect::RedirectType;
+use warp::http::{StatusCode, Uri};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_redirect_types() {
+        assert_eq!("301".parse(), Ok(RedirectType::MovedPermanently));
+        assert_eq!(" 308 ".parse(), Ok(RedirectType::Permanent));
+        assert!("300".parse::<RedirectType>().is_err());
+        assert!("permanent".parse::<RedirectType>().is_err());
+        assert_eq!(RedirectType::default(), RedirectType::Temporary);
+
+        let request: CreateLinkRequest =
+            parse_body(br#"{"long_url": "https://example.com", "redirect_type": 302}"#).unwrap();
+        assert_eq!(request.options.redirect_type, Some(RedirectType::Found));
+
+        let error = parse_body::<CreateLinkRequest>(
+            br#"{"long_url": "https://a.b", "redirect_type": 303}"#,
+        )
+        .unwrap_err();
+        assert_eq!(error.errors[0].field, "redirect_type");
+        assert_eq!(error.errors[0].code, "INVALID_TYPE");
+
+        assert_eq!(
+            serde_json::to_string(&RedirectType::Permanent).unwrap(),
+            "308"
+        );
+    }
+
+    #[test]
+    fn test_redirect_replies() {
+        let uri: Uri = "https://example.com/landing".parse().unwrap();
+
+        let response = RedirectType::MovedPermanently.reply(&uri, 3600);
+        assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(
+            response.headers()["location"],
+            "https://example.com/landing"
+        );
+        assert_eq!(response.headers()["cache-control"], "public, max-age=3600");
+
+        let response = RedirectType::Permanent.reply(&uri, 0);
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()["cache-control"], "public, max-age=0");
+
+        for (redirect, status) in [
+            (RedirectType::Found, StatusCode::FOUND),
+            (RedirectType::Temporary, StatusCode::TEMPORARY_REDIRECT),
+        ] {
+            let response = redirect.reply(&uri, 3600);
+            assert_eq!(response.status(), status);
+            assert_eq!(response.headers()["cache-control"], "no-store");
+        }
+    }
+}
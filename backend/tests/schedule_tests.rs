@@ -0,0 +1,173 @@
+use url_shortener::models::{parse_body, CustomLinkRequest};
+use url_shortener::schedule::{ScheduleError, Window, WindowState};
+
+use chrono::{DateTime, FixedOffset};
+
+fn at(time: &str) -> DateTime<FixedOffset> {
+    DateTime::parse_from_rfc3339(time).unwrap()
+}
+
+fn launch_window() -> Window {
+    Window::parse(
+        Some("2030-03-01T09:00:00+01:00"),
+        Some("2030-03-31T23:59:59+02:00"),
+    )
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_window() {
+        assert_eq!(Window::parse(None, None), Ok(Window::default()));
+        assert!(Window::parse(Some("2030-03-01T09:00:00Z"), None).is_ok());
+
+        let error = Window::parse(Some("2030-03-01 09:00"), None).unwrap_err();
+        assert_eq!(error, ScheduleError::InvalidTime("not_before"));
+        assert_eq!(error.field(), "not_before");
+        assert_eq!(error.code(), "INVALID_TIME");
+        assert_eq!(
+            Window::parse(None, Some("tomorrow")),
+            Err(ScheduleError::InvalidTime("not_after"))
+        );
+
+        // Offsets are compared as instants: 09:00+01:00 is 08:00Z
+        assert_eq!(
+            Window::parse(
+                Some("2030-03-01T09:00:00+01:00"),
+                Some("2030-03-01T08:00:00Z")
+            ),
+            Err(ScheduleError::EmptyWindow)
+        );
+        assert!(Window::parse(
+            Some("2030-03-01T09:00:00+01:00"),
+            Some("2030-03-01T08:00:01Z")
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_window_state() {
+        let window = launch_window();
+        assert_eq!(
+            window.state(at("2030-03-01T07:59:59Z")),
+            WindowState::Pending(at("2030-03-01T09:00:00+01:00"))
+        );
+        assert_eq!(
+            window.state(at("2030-03-01T08:00:00Z")),
+            WindowState::Active
+        );
+        assert_eq!(
+            window.state(at("2030-03-31T21:59:58Z")),
+            WindowState::Active
+        );
+        assert_eq!(
+            window.state(at("2030-03-31T21:59:59Z")),
+            WindowState::Ended(at("2030-03-31T23:59:59+02:00"))
+        );
+
+        let open_ended = Window::parse(Some("2030-03-01T09:00:00Z"), None).unwrap();
+        assert_eq!(
+            open_ended.state(at("2099-01-01T00:00:00Z")),
+            WindowState::Active
+        );
+        assert_eq!(
+            Window::default().state(at("2000-01-01T00:00:00Z")),
+            WindowState::Active
+        );
+    }
+
+    #[test]
+    fn test_remaining() {
+        let window = launch_window();
+        assert_eq!(window.remaining(at("2030-03-31T21:58:59Z")), Some(60));
+        assert_eq!(window.remaining(at("2030-04-01T00:00:00Z")), Some(0));
+        assert_eq!(
+            Window::default().remaining(at("2030-03-31T21:58:59Z")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_stored_window_round_trips() {
+        let window = launch_window();
+        let (not_before, not_after) = window.to_stored();
+        assert_eq!(not_before.as_deref(), Some("2030-03-01T09:00:00+01:00"));
+        assert_eq!(
+            Window::from_stored(not_before.as_deref(), not_after.as_deref()),
+            window
+        );
+        assert_eq!(
+            Window::from_stored(Some("garbage"), None),
+            Window::default()
+        );
+    }
+
+    #[test]
+    fn test_window_ttl() {
+        let created = at("2030-02-01T00:00:00Z");
+        assert_eq!(Window::default().ttl(created, 30), Ok(30));
+
+        // Links are kept until their window ends
+        assert_eq!(launch_window().ttl(created, 30), Ok(5_090_399));
+        let ending = Window::parse(None, Some("2030-02-01T00:00:10Z")).unwrap();
+        assert_eq!(ending.ttl(created, 30), Ok(30));
+
+        // A link opening after it expires would never redirect
+        let opening = Window::parse(Some("2030-02-01T00:00:30Z"), None).unwrap();
+        let error = opening.ttl(created, 30).unwrap_err();
+        assert_eq!(
+            error,
+            ScheduleError::StartsAfterExpiry(at("2030-02-01T00:00:30Z"))
+        );
+        assert_eq!(error.field(), "not_before");
+        assert_eq!(error.code(), "WINDOW_AFTER_EXPIRY");
+        assert!(error.message().contains("2030-02-01T00:00:30+00:00"));
+        assert_eq!(opening.ttl(created, 60), Ok(60));
+    }
+
+    #[test]
+    fn test_window_ending_in_the_past() {
+        let created = at("2030-02-01T00:00:00Z");
+        for end in ["2030-01-31T23:59:59Z", "2030-02-01T00:00:00Z"] {
+            let ended = Window::parse(None, Some(end)).unwrap();
+            let error = ended.ttl(created, 30).unwrap_err();
+            assert_eq!(error, ScheduleError::EndsInPast);
+            assert_eq!(error.field(), "not_after");
+            assert_eq!(error.code(), "WINDOW_IN_PAST");
+        }
+
+        let closed = Window::parse(Some("2030-01-01T00:00:00Z"), Some("2030-01-02T00:00:00Z"));
+        assert_eq!(
+            closed.unwrap().ttl(created, 30),
+            Err(ScheduleError::EndsInPast)
+        );
+        assert_eq!(
+            Window::parse(None, Some("2030-02-01T00:00:01Z"))
+                .unwrap()
+                .ttl(created, 30),
+            Ok(30)
+        );
+    }
+
+    #[test]
+    fn test_window_in_requests() {
+        let request: CustomLinkRequest = parse_body(
+            br#"{"long_url": "https://example.com/sale", "custom_short": "spring-sale",
+                "not_before": "2030-03-01T09:00:00+01:00",
+                "inactive_url": "https://example.com/coming-soon"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            request.options.not_before.as_deref(),
+            Some("2030-03-01T09:00:00+01:00")
+        );
+        assert_eq!(request.options.not_after, None);
+        assert_eq!(
+            request.options.inactive_url.as_deref(),
+            Some("https://example.com/coming-soon")
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use url::Url;
+use url_shortener::db::Data;
+use url_shortener::interstitial::Interstitial;
+use url_shortener::passthrough::Passthrough;
+use url_shortener::self_links::{classify, resolve_destination, ChainError, StoredLink, Target};
+
+use std::collections::HashMap;
+
+fn short_domains() -> Vec<String> {
+    vec!["rustyshortener".to_string(), "sho.rt".to_string()]
+}
+
+fn link(domain: &str, code: &str) -> Target {
+    link_path(domain, code, "")
+}
+
+fn link_path(domain: &str, code: &str, path: &str) -> Target {
+    Target::ShortLink {
+        domain: domain.to_string(),
+        code: code.to_string(),
+        path: path.to_string(),
+    }
+}
+
+// Resolve `long_url` against an in-memory set of stored `domain/code` links
+async fn resolve(
+    links: &[(&str, &str)],
+    long_url: &str,
+    max_depth: usize,
+) -> Result<String, ChainError> {
+    resolve_gated(links, &[], long_url, max_depth).await
+}
+
+// Like `resolve`, with the `domain/code` links in `gated` behind a password or other gate
+async fn resolve_gated(
+    links: &[(&str, &str)],
+    gated: &[&str],
+    long_url: &str,
+    max_depth: usize,
+) -> Result<String, ChainError> {
+    let links: HashMap<String, StoredLink> = links
+        .iter()
+        .map(|(code, url)| {
+            let link = StoredLink {
+                long_url: url.to_string(),
+                gated: gated.contains(code),
+                passthrough: Passthrough::default(),
+            };
+            (code.to_string(), link)
+        })
+        .collect();
+    resolve_stored(&links, long_url, max_depth).await
+}
+
+// Resolve `long_url` against stored links keyed by `domain/code`
+async fn resolve_stored(
+    links: &HashMap<String, StoredLink>,
+    long_url: &str,
+    max_depth: usize,
+) -> Result<String, ChainError> {
+    resolve_destination(
+        long_url.to_string(),
+        &short_domains(),
+        &[],
+        max_depth,
+        |domain, code| std::future::ready(links.get(&format!("{}/{}", domain, code)).cloned()),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_targets() {
+        let domains = short_domains();
+        let classify_str = |url: &str| classify(&Url::parse(url).unwrap(), &domains, &[]);
+
+        assert_eq!(
+            classify_str("https://example.com/dns_resolver/abc"),
+            Target::External
+        );
+        assert_eq!(
+            classify_str("http://RustyShortener/dns_resolver/abc"),
+            link("rustyshortener", "abc")
+        );
+        assert_eq!(
+            classify_str("https://sho.rt/dns_resolver/abc/"),
+            link("sho.rt", "abc")
+        );
+        assert_eq!(
+            classify_str("http://rustyshortener/metrics"),
+            Target::OwnPath
+        );
+        assert_eq!(classify_str("http://rustyshortener/"), Target::OwnPath);
+
+        // Root-level codes, but not the routes sharing the root namespace
+        assert_eq!(
+            classify_str("https://sho.rt/abc1234"),
+            link("sho.rt", "abc1234")
+        );
+        assert_eq!(classify_str("https://sho.rt/Ping"), Target::OwnPath);
+        assert_eq!(classify_str("https://sho.rt/api/v1/links"), Target::OwnPath);
+
+        // With a path prefix only `/{prefix}/{code}` is a link
+        let prefix = ["go".to_string()];
+        let classify_prefixed = |url: &str| classify(&Url::parse(url).unwrap(), &domains, &prefix);
+        assert_eq!(
+            classify_prefixed("https://sho.rt/go/abc1234"),
+            link("sho.rt", "abc1234")
+        );
+        assert_eq!(classify_prefixed("https://sho.rt/abc1234"), Target::OwnPath);
+    }
+
+    #[test]
+    fn test_classify_previews_and_paths() {
+        let domains = short_domains();
+        let classify_str = |url: &str| classify(&Url::parse(url).unwrap(), &domains, &[]);
+
+        assert_eq!(
+            classify_str("https://sho.rt/abc1234+"),
+            link("sho.rt", "abc1234")
+        );
+        assert_eq!(
+            classify_str("https://sho.rt/abc1234/preview"),
+            link("sho.rt", "abc1234")
+        );
+        assert_eq!(
+            classify_str("https://sho.rt/abc1234/docs/intro?v=2"),
+            link_path("sho.rt", "abc1234", "docs/intro")
+        );
+        assert_eq!(classify_str("https://sho.rt/ping/abc"), Target::OwnPath);
+
+        let prefix = ["go".to_string()];
+        let classify_prefixed = |url: &str| classify(&Url::parse(url).unwrap(), &domains, &prefix);
+        assert_eq!(
+            classify_prefixed("https://sho.rt/go/abc1234+"),
+            link("sho.rt", "abc1234")
+        );
+        assert_eq!(
+            classify_prefixed("https://sho.rt/go/abc1234/docs"),
+            link_path("sho.rt", "abc1234", "docs")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_previews_and_forwarded_paths_resolve() {
+        let stored = |long_url: &str, path: bool| StoredLink {
+            long_url: long_url.to_string(),
+            gated: false,
+            passthrough: Passthrough {
+                path,
+                ..Passthrough::default()
+            },
+        };
+        let links = HashMap::from([
+            (
+                "sho.rt/handbook".to_string(),
+                stored("https://example.com/docs/", true),
+            ),
+            (
+                "sho.rt/plain".to_string(),
+                stored("https://example.com/plain", false),
+            ),
+        ]);
+
+        assert_eq!(
+            resolve_stored(&links, "https://sho.rt/handbook/guides/intro", 5).await,
+            Ok("https://example.com/docs/guides/intro".to_string())
+        );
+        assert_eq!(
+            resolve_stored(&links, "https://sho.rt/handbook+", 5).await,
+            Ok("https://example.com/docs/".to_string())
+        );
+        assert_eq!(
+            resolve_stored(&links, "https://sho.rt/plain/preview", 5).await,
+            Ok("https://example.com/plain".to_string())
+        );
+
+        // A visit with a path the link does not forward would be a 404
+        let error = resolve_stored(&links, "https://sho.rt/plain/extra", 5)
+            .await
+            .unwrap_err();
+        assert_eq!(error, ChainError::PathNotForwarded("plain".to_string()));
+        assert_eq!(error.code(), "PATH_NOT_FORWARDED");
+    }
+
+    #[tokio::test]
+    async fn test_chains_resolve_to_final_destination() {
+        let links = [
+            ("rustyshortener/a", "http://rustyshortener/dns_resolver/b"),
+            ("rustyshortener/b", "https://sho.rt/dns_resolver/c"),
+            ("sho.rt/c", "https://example.com/landing"),
+        ];
+
+        assert_eq!(
+            resolve(&links, "https://example.org/", 5).await,
+            Ok("https://example.org/".to_string())
+        );
+        assert_eq!(
+            resolve(&links, "http://rustyshortener/dns_resolver/a", 5).await,
+            Ok("https://example.com/landing".to_string())
+        );
+        assert_eq!(
+            resolve(&links, "http://rustyshortener/dns_resolver/a", 2).await,
+            Err(ChainError::TooDeep(2))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejects_loops_and_unknown_links() {
+        let links = [
+            ("rustyshortener/a", "http://rustyshortener/dns_resolver/b"),
+            ("rustyshortener/b", "http://rustyshortener/dns_resolver/a"),
+            // The same code on another domain is a different link
+            ("sho.rt/a", "http://rustyshortener/a"),
+            ("rustyshortener/c", "https://sho.rt/c"),
+        ];
+
+        assert_eq!(
+            resolve(&links, "http://rustyshortener/dns_resolver/a", 5).await,
+            Err(ChainError::Loop("a".to_string()))
+        );
+        assert_eq!(
+            resolve(&links, "https://sho.rt/a", 5).await,
+            Err(ChainError::Loop("a".to_string()))
+        );
+        assert_eq!(
+            resolve(&links, "http://rustyshortener/c", 5).await,
+            Err(ChainError::UnknownShortLink("c".to_string()))
+        );
+        assert_eq!(
+            resolve(&links, "http://rustyshortener/dns_resolver/missing", 5).await,
+            Err(ChainError::UnknownShortLink("missing".to_string()))
+        );
+        assert_eq!(
+            resolve(&links, "http://rustyshortener/generate_url", 5).await,
+            Err(ChainError::SelfReference)
+        );
+        // Short links can be disabled altogether.
+        assert_eq!(
+            resolve(&links, "http://rustyshortener/dns_resolver/a", 0).await,
+            Err(ChainError::TooDeep(0))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gated_links_are_not_flattened() {
+        let links = [
+            ("rustyshortener/a", "http://rustyshortener/secret"),
+            ("rustyshortener/secret", "https://docs.example.com/internal"),
+            ("rustyshortener/loop", "http://rustyshortener/locked"),
+            ("rustyshortener/locked", "http://rustyshortener/loop"),
+        ];
+        let gated = ["rustyshortener/secret", "rustyshortener/locked"];
+
+        // The protected link's destination is never copied into the new link
+        assert_eq!(
+            resolve_gated(&links, &gated, "http://rustyshortener/secret", 5).await,
+            Ok("http://rustyshortener/secret".to_string())
+        );
+        assert_eq!(
+            resolve_gated(&links, &gated, "http://rustyshortener/a", 5).await,
+            Ok("http://rustyshortener/secret".to_string())
+        );
+        // ...but the chain behind it is still checked
+        assert_eq!(
+            resolve_gated(&links, &gated, "http://rustyshortener/loop", 5).await,
+            Err(ChainError::Loop("loop".to_string()))
+        );
+        assert_eq!(
+            resolve_gated(&links, &gated, "http://rustyshortener/a", 1).await,
+            Err(ChainError::TooDeep(1))
+        );
+    }
+
+    #[test]
+    fn test_gated_data() {
+        assert!(!Data::default().is_gated());
+        for data in [
+            Data {
+                password_hash: Some("pbkdf2-sha256$1$00$00".to_string()),
+                ..Data::default()
+            },
+            Data {
+                max_clicks: Some(1),
+                ..Data::default()
+            },
+            Data {
+                not_after: Some("2030-03-31T23:59:59+02:00".to_string()),
+                ..Data::default()
+            },
+            Data {
+                interstitial: Some(Interstitial::Leaving),
+                ..Data::default()
+            },
+        ] {
+            assert!(data.is_gated(), "{:?}", data);
+        }
+        let opted_out = Data {
+            interstitial: Some(Interstitial::Off),
+            ..Data::default()
+        };
+        assert!(!opted_out.is_gated());
+    }
+}
@@ -0,0 +1,175 @@
+use url_shortener::models::{parse_body, CreateLinkRequest};
+use url_shortener::targeting::{
+    parse_user_agent, preferred_language, select, Client, DestinationRule, Device, Platform,
+};
+
+use std::collections::HashMap;
+
+// User-Agent strings captured from real browsers and in-app webviews
+const IPHONE_SAFARI: &str = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Mobile/15E148 Safari/604.1";
+const IPHONE_INSTAGRAM: &str = "Mozilla/5.0 (iPhone; CPU iPhone OS 16_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148 Instagram 302.0.0.23.114 (iPhone14,5; iOS 16_6; en_US; en; scale=3.00; 1170x2532; 522637798)";
+const IPAD_SAFARI: &str = "Mozilla/5.0 (iPad; CPU OS 16_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.6 Mobile/15E148 Safari/604.1";
+const ANDROID_CHROME: &str = "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.6367.82 Mobile Safari/537.36";
+const ANDROID_SAMSUNG: &str = "Mozilla/5.0 (Linux; Android 13; SAMSUNG SM-S918B) AppleWebKit/537.36 (KHTML, like Gecko) SamsungBrowser/24.0 Chrome/117.0.0.0 Mobile Safari/537.36";
+const ANDROID_TABLET: &str = "Mozilla/5.0 (Linux; Android 13; SM-X710) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/123.0.0.0 Safari/537.36";
+const ANDROID_FACEBOOK: &str = "Mozilla/5.0 (Linux; Android 12; M2101K6G Build/SKQ1.210908.001; wv) AppleWebKit/537.36 (KHTML, like Gecko) Version/4.0 Chrome/118.0.5993.111 Mobile Safari/537.36 [FB_IAB/FB4A;FBAV/439.0.0.44.117;]";
+const WINDOWS_CHROME: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36";
+const WINDOWS_EDGE: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36 Edg/124.0.2478.67";
+const MAC_SAFARI: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4.1 Safari/605.1.15";
+const MAC_FIREFOX: &str =
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 14.4; rv:125.0) Gecko/20100101 Firefox/125.0";
+const LINUX_FIREFOX: &str =
+    "Mozilla/5.0 (X11; Ubuntu; Linux x86_64; rv:125.0) Gecko/20100101 Firefox/125.0";
+const CHROMEBOOK: &str = "Mozilla/5.0 (X11; CrOS x86_64 14541.0.0) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36";
+const CURL: &str = "curl/8.5.0";
+
+fn client(headers: &[(&str, &str)]) -> Client {
+    let headers: HashMap<String, String> = headers
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    Client::detect(|name| headers.get(name).map(String::as_str))
+}
+
+fn app_rules() -> Vec<DestinationRule> {
+    vec![
+        DestinationRule {
+            platforms: vec![Platform::Ios],
+            long_url: "https://apps.apple.com/app/id1".to_string(),
+            ..DestinationRule::default()
+        },
+        DestinationRule {
+            platforms: vec![Platform::Android],
+            devices: vec![Device::Mobile, Device::Tablet],
+            long_url: "https://play.google.com/store/apps/details?id=app".to_string(),
+            ..DestinationRule::default()
+        },
+        DestinationRule {
+            devices: vec![Device::Desktop],
+            languages: vec!["fr".to_string()],
+            long_url: "https://example.com/fr".to_string(),
+            ..DestinationRule::default()
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_real_user_agents() {
+        let cases = [
+            (IPHONE_SAFARI, Some(Platform::Ios), Device::Mobile),
+            (IPHONE_INSTAGRAM, Some(Platform::Ios), Device::Mobile),
+            (IPAD_SAFARI, Some(Platform::Ios), Device::Tablet),
+            (ANDROID_CHROME, Some(Platform::Android), Device::Mobile),
+            (ANDROID_SAMSUNG, Some(Platform::Android), Device::Mobile),
+            (ANDROID_TABLET, Some(Platform::Android), Device::Tablet),
+            (ANDROID_FACEBOOK, Some(Platform::Android), Device::Mobile),
+            (WINDOWS_CHROME, Some(Platform::Windows), Device::Desktop),
+            (WINDOWS_EDGE, Some(Platform::Windows), Device::Desktop),
+            (MAC_SAFARI, Some(Platform::Macos), Device::Desktop),
+            (MAC_FIREFOX, Some(Platform::Macos), Device::Desktop),
+            (LINUX_FIREFOX, Some(Platform::Linux), Device::Desktop),
+            (CHROMEBOOK, Some(Platform::Chromeos), Device::Desktop),
+            (CURL, None, Device::Desktop),
+        ];
+        for (user_agent, platform, device) in cases {
+            assert_eq!(
+                parse_user_agent(user_agent),
+                (platform, device),
+                "{}",
+                user_agent
+            );
+        }
+    }
+
+    #[test]
+    fn test_client_hints_win() {
+        // Chrome's reduced User-Agent on Android tablets, with its low-entropy hints
+        let tablet = client(&[
+            ("user-agent", ANDROID_TABLET),
+            ("sec-ch-ua-platform", "\"Android\""),
+            ("sec-ch-ua-mobile", "?0"),
+        ]);
+        assert_eq!(tablet.platform, Some(Platform::Android));
+        assert_eq!(tablet.device, Device::Tablet);
+
+        let desktop_mode = client(&[
+            ("user-agent", LINUX_FIREFOX),
+            ("sec-ch-ua-platform", "\"Chrome OS\""),
+        ]);
+        assert_eq!(desktop_mode.platform, Some(Platform::Chromeos));
+
+        let phone = client(&[("user-agent", CURL), ("sec-ch-ua-mobile", "?1")]);
+        assert_eq!(phone.device, Device::Mobile);
+    }
+
+    #[test]
+    fn test_preferred_language() {
+        assert_eq!(
+            preferred_language("fr-CA,fr;q=0.9,en;q=0.8").as_deref(),
+            Some("fr-ca")
+        );
+        assert_eq!(
+            preferred_language("en;q=0.5, de;q=0.7, *;q=0.9").as_deref(),
+            Some("de")
+        );
+        assert_eq!(preferred_language("es, en").as_deref(), Some("es"));
+        assert_eq!(preferred_language("fr;q=0, *").as_deref(), None);
+        assert_eq!(preferred_language("").as_deref(), None);
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let rules = app_rules();
+        let destination = |headers: &[(&str, &str)]| {
+            select(&rules, &client(headers)).map(|rule| rule.long_url.as_str())
+        };
+
+        assert_eq!(
+            destination(&[("user-agent", IPHONE_SAFARI)]),
+            Some("https://apps.apple.com/app/id1")
+        );
+        assert_eq!(
+            destination(&[("user-agent", IPAD_SAFARI), ("accept-language", "fr")]),
+            Some("https://apps.apple.com/app/id1")
+        );
+        assert_eq!(
+            destination(&[("user-agent", ANDROID_FACEBOOK)]),
+            Some("https://play.google.com/store/apps/details?id=app")
+        );
+        assert_eq!(
+            destination(&[
+                ("user-agent", WINDOWS_EDGE),
+                ("accept-language", "fr-FR,en;q=0.5")
+            ]),
+            Some("https://example.com/fr")
+        );
+        // No rule matches: the link's long_url applies
+        assert_eq!(
+            destination(&[("user-agent", MAC_SAFARI), ("accept-language", "en-US")]),
+            None
+        );
+        assert_eq!(destination(&[]), None);
+    }
+
+    #[test]
+    fn test_rules_in_requests() {
+        let request: CreateLinkRequest = parse_body(
+            br#"{"long_url": "https://example.com", "rules": [
+                {"platforms": ["ios"], "long_url": "https://apps.apple.com/app/id1"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(request.options.rules[0].platforms, [Platform::Ios]);
+
+        let error = parse_body::<CreateLinkRequest>(
+            br#"{"long_url": "https://example.com", "rules": [{"platforms": ["symbian"]}]}"#,
+        )
+        .unwrap_err();
+        assert_eq!(error.errors[0].field, "rules[0].platforms[0]");
+        assert_eq!(error.errors[0].code, "INVALID_TYPE");
+    }
+}
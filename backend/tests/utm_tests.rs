@@ -0,0 +1,109 @@
+use url_shortener::auth::Authenticator;
+use url_shortener::config::Config;
+use url_shortener::utm::{UtmContext, UtmError, UtmTemplate};
+
+use std::path::PathBuf;
+
+fn template(source: &str, campaign: &str) -> UtmTemplate {
+    UtmTemplate {
+        source: Some(source.to_string()),
+        campaign: Some(campaign.to_string()),
+        ..UtmTemplate::default()
+    }
+}
+
+// Write an API keys file to a unique temporary file
+fn temp_keys(contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "url_shortener_keys_{}_{}.json",
+        std::process::id(),
+        chrono::Utc::now().timestamp_nanos_opt().unwrap()
+    ));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+// Load `API_KEYS_FILE` contents the way the server does at startup
+fn load_keys(contents: &str) -> Result<Authenticator, String> {
+    let config = Config {
+        api_key: "shared".to_string(),
+        api_keys_file: Some(temp_keys(contents)),
+        ..Config::default()
+    };
+    Authenticator::from_config(&config)
+}
+
+fn click() -> UtmContext<'static> {
+    UtmContext {
+        code: "abc1234",
+        domain: "sho.rt",
+        campaign: "spring",
+        date: "2026-10-19".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_validation() {
+        assert_eq!(template("newsletter", "{campaign}-{date}").check(), Ok(()));
+        assert_eq!(
+            template("newsletter", "{campaing}").check(),
+            Err(UtmError::UnknownPlaceholder(
+                "campaign",
+                "campaing".to_string()
+            ))
+        );
+        assert_eq!(
+            template("news{code", "x").check(),
+            Err(UtmError::Unclosed("source"))
+        );
+        assert_eq!(template(" ", "x").check(), Err(UtmError::Empty("source")));
+        assert_eq!(
+            template(&"x".repeat(257), "x").check().unwrap_err().code(),
+            "UTM_TOO_LONG"
+        );
+    }
+
+    #[test]
+    fn test_apply_template() {
+        let utm = UtmTemplate {
+            content: Some("{code}@{domain}".to_string()),
+            ..template("newsletter", "{campaign}-{date}")
+        };
+
+        assert_eq!(
+            utm.apply("https://example.com/landing", &click()),
+            "https://example.com/landing?utm_source=newsletter&utm_campaign=spring-2026-10-19&utm_content=abc1234%40sho.rt"
+        );
+        // Parameters already in the destination win
+        assert_eq!(
+            utm.apply("https://example.com/?utm_source=site&x=1", &click()),
+            "https://example.com/?utm_source=site&x=1&utm_campaign=spring-2026-10-19&utm_content=abc1234%40sho.rt"
+        );
+
+        // A link's own fields take precedence over its campaign's
+        let merged = template("partner", "{code}").or(&utm);
+        assert_eq!(merged.source.as_deref(), Some("partner"));
+        assert_eq!(merged.content, utm.content);
+    }
+
+    #[test]
+    fn test_campaigns_are_checked_at_startup() {
+        let auth = load_keys(
+            r#"[{"owner": "marketing", "key": "k", "campaigns": {"spring": {"source": "{date}"}}}]"#,
+        )
+        .unwrap();
+        let campaigns = &auth.owner_settings("marketing").unwrap().campaigns;
+        assert_eq!(campaigns["spring"].source.as_deref(), Some("{date}"));
+
+        let error = load_keys(
+            r#"[{"owner": "marketing", "key": "k", "campaigns": {"spring": {"source": "{today}"}}}]"#,
+        )
+        .err()
+        .unwrap();
+        assert!(error.contains("spring"), "{}", error);
+    }
+}
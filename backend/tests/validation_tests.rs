@@ -0,0 +1,84 @@
+use url_shortener::validation::{normalize_long_url, UrlError, UrlRules};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonicalizes_urls() {
+        let rules = UrlRules::default();
+
+        let url = normalize_long_url("  HTTP://Example.COM:80/Path?q=1#top ", &rules).unwrap();
+        assert_eq!(url, "http://example.com/Path?q=1#top");
+
+        let url = normalize_long_url("https://example.com:443", &rules).unwrap();
+        assert_eq!(url, "https://example.com/");
+
+        // Non-default ports are kept.
+        let url = normalize_long_url("https://example.com:8443/a", &rules).unwrap();
+        assert_eq!(url, "https://example.com:8443/a");
+    }
+
+    #[test]
+    fn test_idna_hosts_are_punycoded() {
+        let rules = UrlRules::default();
+
+        let url = normalize_long_url("https://BÜCHER.example/straße", &rules).unwrap();
+        assert_eq!(url, "https://xn--bcher-kva.example/stra%C3%9Fe");
+    }
+
+    #[test]
+    fn test_rejects_unsafe_and_malformed_urls() {
+        let rules = UrlRules::default();
+
+        assert_eq!(normalize_long_url("   ", &rules), Err(UrlError::Empty));
+        assert!(matches!(
+            normalize_long_url("example.com/path", &rules),
+            Err(UrlError::Invalid(_))
+        ));
+        assert_eq!(
+            normalize_long_url("javascript:alert(1)", &rules),
+            Err(UrlError::ForbiddenScheme("javascript".to_string()))
+        );
+        assert_eq!(
+            normalize_long_url("DATA:text/html,<script>alert(1)</script>", &rules),
+            Err(UrlError::ForbiddenScheme("data".to_string()))
+        );
+        assert_eq!(
+            normalize_long_url("ftp://example.com/file", &rules),
+            Err(UrlError::SchemeNotAllowed("ftp".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_configurable_schemes_and_length() {
+        let rules = UrlRules {
+            allowed_schemes: vec![
+                "https".to_string(),
+                "mailto".to_string(),
+                "javascript".to_string(),
+            ],
+            max_length: 40,
+        };
+
+        assert!(normalize_long_url("https://example.com/", &rules).is_ok());
+        assert_eq!(
+            normalize_long_url("http://example.com/", &rules),
+            Err(UrlError::SchemeNotAllowed("http".to_string()))
+        );
+        // Dangerous schemes stay forbidden even when allow-listed.
+        assert!(matches!(
+            normalize_long_url("javascript:void(0)", &rules),
+            Err(UrlError::ForbiddenScheme(_))
+        ));
+        // Schemes without a host are refused.
+        assert_eq!(
+            normalize_long_url("mailto:someone@example.com", &rules),
+            Err(UrlError::MissingHost)
+        );
+        assert_eq!(
+            normalize_long_url("https://example.com/a-very-long-path-segment", &rules),
+            Err(UrlError::TooLong(40))
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use url_shortener::models::{parse_body, CreateLinkRequest};
+use url_shortener::variants::{
+    check, cookie_name, find, pick, read_cookie, set_cookie, SplitMode, Variant, VariantError,
+    MAX_VARIANTS,
+};
+
+fn variant(name: &str, weight: u32) -> Variant {
+    Variant {
+        name: name.to_string(),
+        long_url: format!("https://example.com/{}", name),
+        weight,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_variants() {
+        assert_eq!(check(&[variant("a", 3), variant("b-2", 1)]), Ok(()));
+        assert_eq!(check(&[]), Ok(()));
+
+        let too_many: Vec<Variant> = (0..=MAX_VARIANTS)
+            .map(|i| variant(&format!("v{}", i), 1))
+            .collect();
+        assert_eq!(check(&too_many), Err(VariantError::TooMany));
+
+        let error = check(&[variant("a", 1), variant("a b", 1)]).unwrap_err();
+        assert_eq!(error, VariantError::InvalidName(1));
+        assert_eq!(error.field(), "variants[1].name");
+        assert_eq!(error.code(), "VARIANT_INVALID_NAME");
+        assert_eq!(check(&[variant("", 1)]), Err(VariantError::InvalidName(0)));
+        assert_eq!(
+            check(&[variant("a", 1), variant("a", 2)]),
+            Err(VariantError::DuplicateName(1))
+        );
+
+        let error = check(&[variant("a", 0)]).unwrap_err();
+        assert_eq!(error.field(), "variants[0].weight");
+        assert_eq!(
+            check(&[variant("a", 1001)]),
+            Err(VariantError::InvalidWeight(0))
+        );
+    }
+
+    #[test]
+    fn test_pick_follows_weights() {
+        let variants = [variant("a", 3), variant("b", 1), variant("c", 2)];
+        let names: Vec<&str> = (0..6)
+            .map(|roll| pick(&variants, roll).unwrap().name.as_str())
+            .collect();
+        assert_eq!(names, ["a", "a", "a", "b", "c", "c"]);
+        assert!(pick(&variants, 6).is_none());
+
+        assert_eq!(find(&variants, "b"), Some(&variants[1]));
+        assert_eq!(find(&variants, "d"), None);
+    }
+
+    #[test]
+    fn test_variant_cookie() {
+        assert_eq!(cookie_name("abc"), "rs_variant_abc");
+        assert_eq!(
+            read_cookie(
+                "theme=dark; rs_variant_abc=b; rs_variant_xyz=a",
+                "rs_variant_abc"
+            ),
+            Some("b")
+        );
+        assert_eq!(read_cookie("rs_variant_abcd=b", "rs_variant_abc"), None);
+        assert_eq!(read_cookie("", "rs_variant_abc"), None);
+
+        assert_eq!(
+            set_cookie("abc", "b", 60, false),
+            "rs_variant_abc=b; Max-Age=60; Path=/; HttpOnly; SameSite=Lax"
+        );
+        assert!(set_cookie("abc", "b", 60, true).ends_with("; Secure"));
+    }
+
+    #[test]
+    fn test_variants_in_requests() {
+        let request: CreateLinkRequest = parse_body(
+            br#"{"long_url": "https://example.com", "variants": [
+                {"name": "a", "long_url": "https://example.com/a", "weight": 3},
+                {"name": "b", "long_url": "https://example.com/b"}
+            ], "split": "random"}"#,
+        )
+        .unwrap();
+        assert_eq!(request.options.variants[0].weight, 3);
+        assert_eq!(request.options.variants[1].weight, 1);
+        assert_eq!(request.options.split, SplitMode::Random);
+
+        let request: CreateLinkRequest =
+            parse_body(br#"{"long_url": "https://example.com"}"#).unwrap();
+        assert!(request.options.variants.is_empty());
+        assert_eq!(request.options.split, SplitMode::Sticky);
+
+        let error = parse_body::<CreateLinkRequest>(
+            br#"{"long_url": "https://example.com", "split": "roundrobin"}"#,
+        )
+        .unwrap_err();
+        assert_eq!(error.errors[0].field, "split");
+    }
+}
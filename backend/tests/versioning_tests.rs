@@ -0,0 +1,59 @@
+use chrono::NaiveDate;
+use url_shortener::versioning::{legacy, DeprecationPolicy};
+use warp::Filter;
+
+fn policy() -> DeprecationPolicy {
+    DeprecationPolicy {
+        deprecated_on: NaiveDate::from_ymd_opt(2026, 10, 19).unwrap(),
+        sunset_on: NaiveDate::from_ymd_opt(2027, 4, 30),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_values() {
+        assert_eq!(policy().deprecation_header(), "@1792368000");
+        assert_eq!(
+            policy().sunset_header().as_deref(),
+            Some("Fri, 30 Apr 2027 00:00:00 GMT")
+        );
+
+        let open_ended = DeprecationPolicy {
+            sunset_on: None,
+            ..policy()
+        };
+        assert_eq!(open_ended.sunset_header(), None);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_route_headers() {
+        let route = legacy(
+            warp::path("old").map(|| "hello"),
+            "old",
+            Some("/api/v1/new"),
+            policy(),
+        );
+
+        let response = warp::test::request().path("/old").reply(&route).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), "hello");
+        assert_eq!(response.headers()["deprecation"], "@1792368000");
+        assert_eq!(
+            response.headers()["sunset"],
+            "Fri, 30 Apr 2027 00:00:00 GMT"
+        );
+        assert_eq!(
+            response.headers()["link"],
+            "</api/v1/new>; rel=\"successor-version\""
+        );
+
+        let counted = prometheus::gather()
+            .into_iter()
+            .find(|family| family.get_name() == "legacy_route_requests_total")
+            .unwrap();
+        assert_eq!(counted.get_metric()[0].get_counter().get_value(), 1.0);
+    }
+}